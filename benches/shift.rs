@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use running_average::{RealTimeRunningAverage, RunningAverage};
+use running_average::staged::StagedRunningAverage;
+
+const CAPACITIES: [usize; 3] = [16, 64, 256];
+const INSERTS_PER_MEASUREMENT: [usize; 2] = [1000, 10000];
+
+fn insert_every_slot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_every_slot");
+
+    for &capacity in CAPACITIES.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(capacity), &capacity, |b, &capacity| {
+            let mut running_average = RunningAverage::<u64, Instant>::with_capacity(Duration::from_secs(capacity as u64), capacity);
+            let mut now = Instant::now();
+
+            b.iter(|| {
+                now += Duration::from_secs(1);
+                running_average.insert(now, 1);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn insert_after_idle_window(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_after_idle_window");
+
+    for &capacity in CAPACITIES.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(capacity), &capacity, |b, &capacity| {
+            let mut running_average = RunningAverage::<u64, Instant>::with_capacity(Duration::from_secs(capacity as u64), capacity);
+            let mut now = Instant::now();
+
+            b.iter(|| {
+                // Whole window expired since last insert
+                now += Duration::from_secs(capacity as u64);
+                running_average.insert(now, 1);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn measurement_within_slot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("measurement_within_slot");
+
+    for &capacity in CAPACITIES.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(capacity), &capacity, |b, &capacity| {
+            let mut running_average = RunningAverage::<u64, Instant>::with_capacity(Duration::from_secs(capacity as u64), capacity);
+            let now = Instant::now();
+            running_average.insert(now, 1);
+
+            b.iter(|| running_average.measurement(now).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn staged_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("staged_insert");
+
+    for &inserts in INSERTS_PER_MEASUREMENT.iter() {
+        group.bench_with_input(BenchmarkId::new("plain", inserts), &inserts, |b, &inserts| {
+            let mut running_average = RealTimeRunningAverage::<u64>::new(Duration::from_secs(8));
+
+            b.iter(|| {
+                for _ in 0..inserts {
+                    running_average.insert(1);
+                }
+                running_average.measurement().unwrap()
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("staged", inserts), &inserts, |b, &inserts| {
+            let mut running_average = StagedRunningAverage::<u64>::new(Duration::from_secs(8));
+
+            b.iter(|| {
+                for _ in 0..inserts {
+                    running_average.insert(1);
+                }
+                running_average.measurement().unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert_every_slot, insert_after_idle_window, measurement_within_slot, staged_insert);
+criterion_main!(benches);
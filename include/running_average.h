@@ -0,0 +1,54 @@
+#ifndef RUNNING_AVERAGE_H
+#define RUNNING_AVERAGE_H
+
+/* Generated with `cbindgen --config cbindgen.toml --output include/running_average.h`; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Running average of `f64` values over time window using real time clock.
+typedef struct RaWindow RaWindow;
+
+// Create new running average with window of given width in seconds consisting of given number of buckets.
+// Returns NULL if window width is not positive or too large or capacity is 0 or greater than number of nanoseconds in the window.
+// The returned pointer needs to be released with `ra_free()`.
+struct RaWindow *ra_new(double window_seconds,
+                        uintptr_t capacity);
+
+// Insert value to be averaged over now.
+//
+// # Safety
+// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+void ra_insert(struct RaWindow *window,
+               double value);
+
+// Insert integer value to be averaged over now.
+//
+// # Safety
+// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+void ra_insert_u64(struct RaWindow *window,
+                   uint64_t value);
+
+// Returns rate (sum of values within time window ending now per second).
+// Returns NaN if window is NULL.
+//
+// # Safety
+// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL.
+double ra_measure(struct RaWindow *window);
+
+// Returns sum of values within time window ending now.
+// Returns NaN if window is NULL.
+//
+// # Safety
+// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL.
+double ra_value(struct RaWindow *window);
+
+// Release running average.
+//
+// # Safety
+// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+void ra_free(struct RaWindow *window);
+
+#endif  /* RUNNING_AVERAGE_H */
@@ -0,0 +1,33 @@
+//! Attribute macros instrumenting functions with running-average.
+//!
+//! Use through `running-average` crate with `macros` feature enabled.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr};
+
+/// Record calls of the function and time spent in them into the global registry.
+///
+/// Each call inserts `1` into `<name>.calls` and call duration in seconds into `<name>.seconds` registry rates,
+/// so rate of the former is number of calls per second and ratio of the latter to the former is average call duration.
+#[proc_macro_attribute]
+pub fn measure_rate(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = parse_macro_input!(attr as LitStr);
+    let mut function = parse_macro_input!(item as ItemFn);
+
+    let calls = LitStr::new(&format!("{}.calls", name.value()), name.span());
+    let seconds = LitStr::new(&format!("{}.seconds", name.value()), name.span());
+    let body = function.block.clone();
+
+    *function.block = syn::parse_quote!({
+        let _running_average_timer = {
+            static CALL_SITE: ::running_average::registry::CallSite = ::running_average::registry::CallSite::new(#calls, #seconds);
+            CALL_SITE.start()
+        };
+        #body
+    });
+
+    quote!(#function).into()
+}
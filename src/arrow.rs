@@ -0,0 +1,188 @@
+//! Conversion of bucket and measurement history into Arrow `RecordBatch` (requires `arrow` feature).
+//!
+//! Produced batches can be written to Parquet files using the `parquet::arrow::ArrowWriter` or loaded into dataframes for offline analysis.
+//! ```
+//! # extern crate running_average;
+//! use std::time::SystemTime;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::sink::MeasurementSink;
+//! use running_average::arrow::{bucket_batch, MeasurementHistory};
+//!
+//! let mut tw = RealTimeRunningAverage::default();
+//! tw.insert(2000);
+//!
+//! // Per bucket values of the current time window
+//! let buckets = bucket_batch(tw.bucket_duration(), tw.buckets());
+//! assert_eq!(buckets.num_rows(), 16);
+//!
+//! // History of periodically taken measurements
+//! let mut history = MeasurementHistory::new();
+//! history.record(SystemTime::now(), &tw.measurement()).unwrap();
+//! assert_eq!(history.finish().num_rows(), 1);
+//! ```
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_array::builder::{ArrayBuilder, Float64Builder, TimestampNanosecondBuilder};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+
+use super::Measurement;
+use super::dts;
+use super::sink::MeasurementSink;
+
+/// Returns schema of `RecordBatch` produced by `bucket_batch()`.
+///
+/// Columns are: `age` - seconds since the end of time span covered by the bucket (0 for the most recent bucket), `value` - sum of samples in the bucket,
+/// `rate` - value divided by the bucket duration.
+pub fn bucket_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("age", DataType::Float64, false),
+        Field::new("value", DataType::Float64, false),
+        Field::new("rate", DataType::Float64, false),
+    ]))
+}
+
+/// Convert buckets of time window (most recent first, e.g. as returned by `RunningAverage::buckets()`) to `RecordBatch` with one row per bucket.
+pub fn bucket_batch<'i, V, B>(bucket_duration: Duration, buckets: B) -> RecordBatch where V: Clone + Into<f64> + 'i, B: IntoIterator<Item = &'i V> {
+    let mut age = Float64Builder::new();
+    let mut value = Float64Builder::new();
+    let mut rate = Float64Builder::new();
+    let bucket_seconds = dts(bucket_duration);
+
+    for (index, bucket) in buckets.into_iter().enumerate() {
+        age.append_value(index as f64 * bucket_seconds);
+        value.append_value(bucket.clone().into());
+        rate.append_value(bucket.clone().into() / dts(bucket_duration));
+    }
+
+    let columns: Vec<ArrayRef> = vec![Arc::new(age.finish()), Arc::new(value.finish()), Arc::new(rate.finish())];
+    RecordBatch::try_new(bucket_schema(), columns).expect("bucket batch columns do not match schema")
+}
+
+/// Returns schema of `RecordBatch` produced by `MeasurementHistory::finish()`.
+///
+/// Columns are: `timestamp` - time the measurement was taken at (UTC), `window` - width of the time window in seconds,
+/// `value` - sum of all samples within time window, `rate` - calculated running average.
+pub fn measurement_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())), false),
+        Field::new("window", DataType::Float64, false),
+        Field::new("value", DataType::Float64, false),
+        Field::new("rate", DataType::Float64, false),
+    ]))
+}
+
+fn unix_nanos(ts: SystemTime) -> i64 {
+    match ts.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64 * 1_000_000_000 + i64::from(since.subsec_nanos()),
+        Err(err) => -(err.duration().as_secs() as i64 * 1_000_000_000 + i64::from(err.duration().subsec_nanos())),
+    }
+}
+
+/// MeasurementSink that collects recorded measurements in memory so they can be converted to `RecordBatch`.
+#[derive(Debug)]
+pub struct MeasurementHistory {
+    timestamp: TimestampNanosecondBuilder,
+    window: Float64Builder,
+    value: Float64Builder,
+    rate: Float64Builder,
+}
+
+impl Default for MeasurementHistory {
+    fn default() -> MeasurementHistory {
+        MeasurementHistory::new()
+    }
+}
+
+impl MeasurementHistory {
+    /// Create new empty MeasurementHistory.
+    pub fn new() -> MeasurementHistory {
+        MeasurementHistory {
+            timestamp: TimestampNanosecondBuilder::new().with_timezone("UTC"),
+            window: Float64Builder::new(),
+            value: Float64Builder::new(),
+            rate: Float64Builder::new(),
+        }
+    }
+
+    /// Returns number of measurements collected since last `finish()`.
+    pub fn len(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Returns true if no measurements were collected since last `finish()`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Convert collected measurements to `RecordBatch` with one row per measurement leaving this history empty.
+    pub fn finish(&mut self) -> RecordBatch {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.window.finish()),
+            Arc::new(self.value.finish()),
+            Arc::new(self.rate.finish()),
+        ];
+        RecordBatch::try_new(measurement_schema(), columns).expect("measurement batch columns do not match schema")
+    }
+}
+
+impl<V> MeasurementSink<V> for MeasurementHistory where V: Clone + Into<f64> {
+    type Error = Infallible;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Infallible> {
+        self.timestamp.append_value(unix_nanos(ts));
+        self.window.append_value(dts(measurement.duration()));
+        self.value.append_value(measurement.value().clone().into());
+        self.rate.append_value(measurement.rate_f64());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn buckets_to_batch() {
+        use super::*;
+        use arrow_array::Float64Array;
+        use super::super::RunningAverage;
+
+        let mut tw = RunningAverage::with_capacity(Duration::from_secs(4), 4);
+        tw.insert(0.0, 10);
+        tw.insert(1.0, 20);
+        tw.insert(3.0, 40);
+
+        let batch = bucket_batch(tw.bucket_duration(), tw.buckets(3.5));
+        let column = |name: &str| batch.column_by_name(name).unwrap().as_any().downcast_ref::<Float64Array>().unwrap().values().to_vec();
+
+        assert_eq!(column("age"), vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(column("value"), vec![40.0, 0.0, 20.0, 10.0]);
+        assert_eq!(column("rate"), vec![40.0, 0.0, 20.0, 10.0]);
+    }
+
+    #[test]
+    fn measurements_to_batch() {
+        use super::*;
+        use arrow_array::{Array, Float64Array, TimestampNanosecondArray};
+
+        let mut history = MeasurementHistory::new();
+        history.record(UNIX_EPOCH + Duration::from_millis(1500), &Measurement { value: 40, duration: Duration::from_secs(4) }).unwrap();
+        history.record(UNIX_EPOCH + Duration::from_secs(2), &Measurement { value: 20, duration: Duration::from_secs(4) }).unwrap();
+        assert_eq!(history.len(), 2);
+
+        let batch = history.finish();
+        assert!(history.is_empty());
+        assert_eq!(batch.schema(), measurement_schema());
+
+        let timestamp = batch.column(0).as_any().downcast_ref::<TimestampNanosecondArray>().unwrap();
+        assert_eq!(timestamp.values().to_vec(), vec![1_500_000_000, 2_000_000_000]);
+        assert_eq!(timestamp.timezone(), Some("UTC"));
+
+        let rate = batch.column_by_name("rate").unwrap().as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(rate.values().to_vec(), vec![10.0, 5.0]);
+        assert_eq!(rate.len(), 2);
+    }
+}
@@ -0,0 +1,466 @@
+//! Asynchronous I/O wrappers inserting number of transferred bytes into running average.
+//!
+//! Tokio `AsyncRead`/`AsyncWrite` traits are implemented with `tokio` feature and futures-io traits are implemented with `futures` feature.
+//! With `tokio` feature `ThrottledAsyncWriter` delays writes to keep measured throughput under a limit.
+//! ```
+//! # #[cfg(not(feature = "tokio"))] fn main() {}
+//! # #[cfg(feature = "tokio")]
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//! use running_average::async_io::{MeasuredAsyncReader, MeasuredAsyncWriter};
+//! use running_average::shared::SharedRunningAverage;
+//!
+//! let ingress = SharedRunningAverage::<u64>::default();
+//! let egress = SharedRunningAverage::<u64>::default();
+//!
+//! let mut reader = MeasuredAsyncReader::new(&b"hello world"[..], ingress.clone());
+//! let mut writer = MeasuredAsyncWriter::new(Vec::new(), egress.clone());
+//!
+//! let mut buf = Vec::new();
+//! reader.read_to_end(&mut buf).await.unwrap();
+//! writer.write_all(&buf[..5]).await.unwrap();
+//!
+//! assert_eq!(ingress.measurement().unwrap(), 11);
+//! assert_eq!(egress.measurement().unwrap(), 5);
+//! # }
+//! ```
+
+use std::io::{self, IoSlice};
+#[cfg(feature = "futures")]
+use std::io::IoSliceMut;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use std::{fmt, future::Future, task::ready, time::Duration};
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+
+use super::Insert;
+use super::io::insert_count;
+#[cfg(feature = "tokio")]
+use super::{Measurement, RealTimeRunningAverage, dts};
+#[cfg(feature = "tokio")]
+use super::tokio_reporter::TokioTimeSource;
+
+fn measure<R: Insert>(recorder: &mut R, poll: Poll<io::Result<usize>>) -> Poll<io::Result<usize>> where R::Value: From<u32> {
+    if let Poll::Ready(Ok(count)) = poll {
+        if count > 0 {
+            insert_count(recorder, count as u64);
+        }
+    }
+    poll
+}
+
+/// Asynchronous reader wrapper that inserts number of bytes read into running average.
+/// Note: The wrapped reader and running average need to be `Unpin`; use `Box::pin` to wrap readers that are not.
+#[derive(Debug)]
+pub struct MeasuredAsyncReader<T, R> {
+    reader: T,
+    bytes: R,
+}
+
+impl<T, R> MeasuredAsyncReader<T, R> {
+    /// Wrap given reader inserting number of bytes read into given running average.
+    pub fn new(reader: T, bytes: R) -> MeasuredAsyncReader<T, R> {
+        MeasuredAsyncReader {
+            reader,
+            bytes,
+        }
+    }
+
+    /// Return reference to the underlying reader.
+    pub fn get_ref(&self) -> &T {
+        &self.reader
+    }
+
+    /// Return mutable reference to the underlying reader.
+    /// Note: Bytes read directly from the underlying reader are not measured.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.reader
+    }
+
+    /// Return reference to running average bytes read are inserted into.
+    pub fn bytes(&self) -> &R {
+        &self.bytes
+    }
+
+    /// Return the underlying reader consuming self.
+    pub fn into_inner(self) -> T {
+        self.reader
+    }
+}
+
+/// Asynchronous writer wrapper that inserts number of bytes written into running average and optionally inserts 1 on each successful flush into another.
+/// Note: The wrapped writer and running averages need to be `Unpin`; use `Box::pin` to wrap writers that are not.
+#[derive(Debug)]
+pub struct MeasuredAsyncWriter<T, R, FR = R> {
+    writer: T,
+    bytes: R,
+    flushes: Option<FR>,
+}
+
+impl<T, R> MeasuredAsyncWriter<T, R, R> {
+    /// Wrap given writer inserting number of bytes written into given running average.
+    pub fn new(writer: T, bytes: R) -> MeasuredAsyncWriter<T, R, R> {
+        MeasuredAsyncWriter {
+            writer,
+            bytes,
+            flushes: None,
+        }
+    }
+}
+
+impl<T, R, FR> MeasuredAsyncWriter<T, R, FR> {
+    /// Wrap given writer inserting number of bytes written into given running average and 1 for each successful flush into another.
+    pub fn with_flushes(writer: T, bytes: R, flushes: FR) -> MeasuredAsyncWriter<T, R, FR> {
+        MeasuredAsyncWriter {
+            writer,
+            bytes,
+            flushes: Some(flushes),
+        }
+    }
+
+    /// Return reference to the underlying writer.
+    pub fn get_ref(&self) -> &T {
+        &self.writer
+    }
+
+    /// Return mutable reference to the underlying writer.
+    /// Note: Bytes written directly to the underlying writer are not measured.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.writer
+    }
+
+    /// Return reference to running average bytes written are inserted into.
+    pub fn bytes(&self) -> &R {
+        &self.bytes
+    }
+
+    /// Return reference to running average flushes are inserted into if any.
+    pub fn flushes(&self) -> Option<&FR> {
+        self.flushes.as_ref()
+    }
+
+    /// Return the underlying writer consuming self.
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+
+    fn flushed(&mut self, poll: Poll<io::Result<()>>) -> Poll<io::Result<()>> where FR: Insert, FR::Value: From<u8> {
+        if let (Poll::Ready(Ok(())), Some(flushes)) = (&poll, self.flushes.as_mut()) {
+            flushes.insert(From::from(1));
+        }
+        poll
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, R> tokio::io::AsyncRead for MeasuredAsyncReader<T, R> where T: tokio::io::AsyncRead + Unpin, R: Insert + Unpin, R::Value: From<u32> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+
+        match Pin::new(&mut this.reader).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                let read = buf.filled().len() - filled;
+                if read > 0 {
+                    insert_count(&mut this.bytes, read as u64);
+                }
+                Poll::Ready(Ok(()))
+            }
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, R, FR> tokio::io::AsyncWrite for MeasuredAsyncWriter<T, R, FR>
+    where T: tokio::io::AsyncWrite + Unpin, R: Insert + Unpin, R::Value: From<u32>, FR: Insert + Unpin, FR::Value: From<u8> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_write(cx, buf);
+        measure(&mut this.bytes, poll)
+    }
+
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_write_vectored(cx, bufs);
+        measure(&mut this.bytes, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.writer.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_flush(cx);
+        this.flushed(poll)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_shutdown(cx)
+    }
+}
+
+/// Cloneable handle to bytes per second limit of `ThrottledAsyncWriter` that can be adjusted at runtime.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct ThrottleLimit {
+    bytes_per_second: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "tokio")]
+impl ThrottleLimit {
+    /// Create new limit of given bytes per second.
+    pub fn new(bytes_per_second: f64) -> ThrottleLimit {
+        ThrottleLimit {
+            bytes_per_second: Arc::new(AtomicU64::new(bytes_per_second.to_bits())),
+        }
+    }
+
+    /// Returns current limit in bytes per second.
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.bytes_per_second.load(Ordering::Relaxed))
+    }
+
+    /// Set limit to given bytes per second; use `f64::INFINITY` to disable throttling.
+    /// Writes that are already delayed wait until the end of their delay.
+    pub fn set(&self, bytes_per_second: f64) {
+        self.bytes_per_second.store(bytes_per_second.to_bits(), Ordering::Relaxed)
+    }
+}
+
+/// Asynchronous writer wrapper that delays writes with Tokio timer to keep throughput measured over time window under a limit.
+/// Writes are split into chunks of at most limit times width of a single bucket of the time window (at least one byte) so that throughput is kept smooth.
+/// Note: The wrapped writer needs to be `Unpin`; use `Box::pin` to wrap writers that are not.
+#[cfg(feature = "tokio")]
+pub struct ThrottledAsyncWriter<T> {
+    writer: T,
+    bytes: RealTimeRunningAverage<f64, TokioTimeSource>,
+    limit: ThrottleLimit,
+    delay: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<T: fmt::Debug> fmt::Debug for ThrottledAsyncWriter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThrottledAsyncWriter").field("writer", &self.writer).field("bytes", &self.bytes).field("limit", &self.limit).finish()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T> ThrottledAsyncWriter<T> {
+    /// Wrap given writer keeping throughput measured over 1 second time window under given bytes per second limit.
+    pub fn new(writer: T, bytes_per_second: f64) -> ThrottledAsyncWriter<T> {
+        ThrottledAsyncWriter::with_window(writer, Duration::from_secs(1), 16, ThrottleLimit::new(bytes_per_second))
+    }
+
+    /// Wrap given writer keeping throughput measured over time window of given width duration and number of buckets under given limit.
+    pub fn with_window(writer: T, duration: Duration, capacity: usize, limit: ThrottleLimit) -> ThrottledAsyncWriter<T> {
+        ThrottledAsyncWriter {
+            writer,
+            bytes: RealTimeRunningAverage::with_time_source(duration, capacity, TokioTimeSource),
+            limit,
+            delay: None,
+        }
+    }
+
+    /// Return handle that can be used to adjust the limit.
+    pub fn limit(&self) -> &ThrottleLimit {
+        &self.limit
+    }
+
+    /// Calculate throughput of written bytes using time window ending now.
+    pub fn measurement(&mut self) -> Measurement<f64> {
+        self.bytes.measurement()
+    }
+
+    /// Return reference to the underlying writer.
+    pub fn get_ref(&self) -> &T {
+        &self.writer
+    }
+
+    /// Return mutable reference to the underlying writer.
+    /// Note: Bytes written directly to the underlying writer are not throttled.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.writer
+    }
+
+    /// Return the underlying writer consuming self.
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+
+    /// Wait until chunk of given size can be written without exceeding the limit; returns maximum size of the chunk.
+    fn poll_throttle(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<usize> {
+        loop {
+            if let Some(delay) = self.delay.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                self.delay = None;
+            }
+
+            let limit = self.limit.get();
+            let chunk = (limit * dts(self.bytes.bucket_duration())).max(1.0).min(len as f64) as usize;
+
+            // Leave room for the chunk in the window
+            let target_rate = (limit - chunk as f64 / dts(self.bytes.inner.duration)).max(0.0);
+            let delay = self.bytes.suggest_delay(target_rate);
+            if delay == Duration::from_secs(0) {
+                return Poll::Ready(chunk)
+            }
+            self.delay = Some(Box::pin(tokio::time::sleep(delay)));
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T> tokio::io::AsyncWrite for ThrottledAsyncWriter<T> where T: tokio::io::AsyncWrite + Unpin {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Pin::new(&mut this.writer).poll_write(cx, buf)
+        }
+
+        let chunk = ready!(this.poll_throttle(cx, buf.len()));
+        let poll = Pin::new(&mut this.writer).poll_write(cx, &buf[..chunk]);
+        if let Poll::Ready(Ok(count)) = poll {
+            this.bytes.insert(count as f64);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T, R> futures_io::AsyncRead for MeasuredAsyncReader<T, R> where T: futures_io::AsyncRead + Unpin, R: Insert + Unpin, R::Value: From<u32> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.reader).poll_read(cx, buf);
+        measure(&mut this.bytes, poll)
+    }
+
+    fn poll_read_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &mut [IoSliceMut<'_>]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.reader).poll_read_vectored(cx, bufs);
+        measure(&mut this.bytes, poll)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T, R, FR> futures_io::AsyncWrite for MeasuredAsyncWriter<T, R, FR>
+    where T: futures_io::AsyncWrite + Unpin, R: Insert + Unpin, R::Value: From<u32>, FR: Insert + Unpin, FR::Value: From<u8> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_write(cx, buf);
+        measure(&mut this.bytes, poll)
+    }
+
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_write_vectored(cx, bufs);
+        measure(&mut this.bytes, poll)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.writer).poll_flush(cx);
+        this.flushed(poll)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::super::{Accumulate, RealTimeRunningAverage, ManualTimeSource};
+
+    fn window<V: Accumulate>() -> RealTimeRunningAverage<V, ManualTimeSource> {
+        RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn tokio_measure_transferred_bytes() {
+        use super::*;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut ingress = window::<u32>();
+        let mut egress = window::<u32>();
+        let mut flushes = window::<u8>();
+
+        {
+            let mut reader = MeasuredAsyncReader::new(&b"hello world"[..], &mut ingress);
+            let mut writer = MeasuredAsyncWriter::with_flushes(Vec::new(), &mut egress, &mut flushes);
+
+            let mut buf = [0; 5];
+            reader.read_exact(&mut buf).await.unwrap();
+            writer.write_all(&buf).await.unwrap();
+            writer.flush().await.unwrap();
+            assert_eq!(writer.get_ref(), b"hello");
+        }
+
+        assert_eq!(ingress.measurement().unwrap(), 5);
+        assert_eq!(egress.measurement().unwrap(), 5);
+        assert_eq!(flushes.measurement().unwrap(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn throttle_writes_to_limit() {
+        use super::*;
+        use tokio::io::AsyncWriteExt;
+        use tokio::time::Instant;
+
+        let mut writer = ThrottledAsyncWriter::new(Vec::new(), 100.0);
+        let start = Instant::now();
+
+        writer.write_all(&[0; 1000]).await.unwrap();
+        assert!(writer.measurement().rate() <= 100.0);
+
+        // First second worth of data goes without delay
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(8) && elapsed <= Duration::from_secs(10), "elapsed: {:?}", elapsed);
+
+        writer.limit().set(f64::INFINITY);
+        let start = Instant::now();
+        writer.write_all(&[0; 1000]).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+        assert_eq!(writer.get_ref().len(), 2000);
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn futures_measure_transferred_bytes() {
+        use super::*;
+        use std::future::poll_fn;
+        use futures_io::{AsyncRead, AsyncWrite};
+
+        let mut ingress = window::<u32>();
+        let mut egress = window::<u32>();
+
+        {
+            let mut reader = MeasuredAsyncReader::new(&b"hello world"[..], &mut ingress);
+            let mut writer = MeasuredAsyncWriter::new(Vec::new(), &mut egress);
+
+            let mut buf = [0; 5];
+            assert_eq!(poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)).await.unwrap(), 5);
+            assert_eq!(poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, &buf[..3])).await.unwrap(), 3);
+            poll_fn(|cx| Pin::new(&mut writer).poll_flush(cx)).await.unwrap();
+        }
+
+        assert_eq!(ingress.measurement().unwrap(), 5);
+        assert_eq!(egress.measurement().unwrap(), 3);
+    }
+}
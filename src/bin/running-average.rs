@@ -0,0 +1,125 @@
+//! Copy input to standard output printing throughput measured over time window to standard error (requires `cli` feature).
+//!
+//! Usage: `running-average [-i INTERVAL] [-w WINDOW] [FILE]`
+//!
+//! Reads FILE (or standard input if not given or `-`) and writes it to standard output
+//! printing rate of bytes written averaged over WINDOW seconds (default 8) every INTERVAL seconds (default 1).
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::{Duration, SystemTime};
+
+use running_average::Measurement;
+use running_average::format::SiRate;
+use running_average::io::MeasuredWriter;
+use running_average::reporter::Reporter;
+use running_average::shared::SharedRunningAverage;
+use running_average::sink::MeasurementSink;
+
+const USAGE: &str = "usage: running-average [-i INTERVAL] [-w WINDOW] [FILE]";
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    interval: Duration,
+    window: Duration,
+    file: Option<String>,
+}
+
+fn seconds(value: Option<String>) -> Result<Duration, String> {
+    let value = value.ok_or_else(|| USAGE.to_owned())?;
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+        _ => Err(format!("invalid number of seconds: {}", value)),
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut parsed = Args {
+        interval: Duration::from_secs(1),
+        window: Duration::from_secs(8),
+        file: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" => parsed.interval = seconds(args.next())?,
+            "-w" => parsed.window = seconds(args.next())?,
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            "-" => parsed.file = None,
+            _ if parsed.file.is_none() && !arg.starts_with('-') => parsed.file = Some(arg),
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn fmt_bytes(bytes: f64) -> String {
+    let (bytes, prefix) = SiRate::new(bytes).binary().scaled();
+    format!("{:.1} {}B", bytes, prefix)
+}
+
+/// Prints rate over the previous line on standard error.
+struct StatusLine;
+
+impl MeasurementSink<f64> for StatusLine {
+    type Error = io::Error;
+
+    fn record(&mut self, _ts: SystemTime, measurement: &Measurement<f64>) -> Result<(), io::Error> {
+        let mut stderr = io::stderr();
+        write!(stderr, "\r\x1b[2K{}/s", fmt_bytes(measurement.rate()))?;
+        stderr.flush()
+    }
+}
+
+fn run(args: Args) -> io::Result<u64> {
+    let mut input: Box<dyn Read> = match args.file {
+        Some(ref file) => Box::new(File::open(file)?),
+        None => Box::new(io::stdin()),
+    };
+
+    let shared = SharedRunningAverage::<f64>::new(args.window);
+    let reporter = Reporter::spawn(shared.clone(), args.interval, StatusLine);
+
+    let stdout = io::stdout();
+    let mut output = MeasuredWriter::new(stdout.lock(), shared);
+    let copied = io::copy(&mut input, &mut output)?;
+    output.flush()?;
+
+    reporter.stop()?;
+    eprintln!("\r\x1b[2K{} copied", fmt_bytes(copied as f64));
+    Ok(copied)
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2)
+        }
+    };
+
+    if let Err(err) = run(args) {
+        eprintln!("running-average: {}", err);
+        process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_arguments() {
+        use super::*;
+
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(args(&[]).unwrap(), Args { interval: Duration::from_secs(1), window: Duration::from_secs(8), file: None });
+        assert_eq!(args(&["-i", "0.5", "-w", "60", "data.bin"]).unwrap(), Args { interval: Duration::from_millis(500), window: Duration::from_secs(60), file: Some("data.bin".to_owned()) });
+        assert!(args(&["-i", "0"]).is_err());
+        assert!(args(&["-w"]).is_err());
+        assert!(args(&["a", "b"]).is_err());
+        assert_eq!(&fmt_bytes(1.8 * 1024.0 * 1024.0), "1.8 MiB");
+    }
+}
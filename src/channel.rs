@@ -0,0 +1,379 @@
+//! Channel wrappers inserting weight of each sent or received message into running average.
+//!
+//! `Measured` wraps sending or receiving end of `std::sync::mpsc` channel, `crossbeam-channel` channel (with `crossbeam` feature)
+//! or `tokio::sync::mpsc` channel (with `tokio` feature).
+//! By default each message is counted as 1; use `Measured::with_weight()` to insert custom weight per message.
+//! Note: Sending methods take `&mut self`; clone the wrapper (with shared running average) to send from multiple places.
+//! ```
+//! use std::sync::mpsc::channel;
+//! use running_average::channel::Measured;
+//! use running_average::shared::SharedRunningAverage;
+//!
+//! let ingress = SharedRunningAverage::<u32>::default();
+//! let egress = SharedRunningAverage::<u32>::default();
+//!
+//! let (tx, rx) = channel();
+//! let mut tx = Measured::new(tx, ingress.clone());
+//! let mut rx = Measured::with_weight(rx, egress.clone(), |message: &String| message.len() as u32);
+//!
+//! tx.send("hello".to_owned()).unwrap();
+//! tx.send("world".to_owned()).unwrap();
+//! rx.recv().unwrap();
+//!
+//! assert_eq!(ingress.measurement().unwrap(), 2);
+//! assert_eq!(egress.measurement().unwrap(), 5);
+//! ```
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::Insert;
+use super::iter::CountItem;
+
+/// Types implementing this trait are sending or receiving channel ends that can be wrapped with `Measured`.
+pub trait ChannelEnd {
+    /// Type of messages transferred by the channel.
+    type Item;
+}
+
+/// Channel end wrapper that inserts weight of each successfully sent or received message into running average.
+#[derive(Debug, Clone)]
+pub struct Measured<C, R, F> {
+    inner: C,
+    recorder: R,
+    weight: F,
+}
+
+impl<C: ChannelEnd, R: Insert> Measured<C, R, CountItem<C::Item, R::Value>> where R::Value: From<u8> {
+    /// Wrap given channel end inserting 1 for each message into given running average.
+    pub fn new(inner: C, recorder: R) -> Measured<C, R, CountItem<C::Item, R::Value>> {
+        Measured {
+            inner,
+            recorder,
+            weight: |_| From::from(1),
+        }
+    }
+}
+
+impl<C: ChannelEnd, R: Insert, F: FnMut(&C::Item) -> R::Value> Measured<C, R, F> {
+    /// Wrap given channel end inserting weight calculated by given function from each message into given running average.
+    pub fn with_weight(inner: C, recorder: R, weight: F) -> Measured<C, R, F> {
+        Measured {
+            inner,
+            recorder,
+            weight,
+        }
+    }
+
+    fn sending<E>(&mut self, message: C::Item, send: impl FnOnce(&C, C::Item) -> Result<(), E>) -> Result<(), E> {
+        let weight = (self.weight)(&message);
+        send(&self.inner, message)?;
+        self.recorder.insert(weight);
+        Ok(())
+    }
+
+    fn received<E>(&mut self, result: Result<C::Item, E>) -> Result<C::Item, E> {
+        let message = result?;
+        self.recorder.insert((self.weight)(&message));
+        Ok(message)
+    }
+}
+
+impl<C, R, F> Measured<C, R, F> {
+    /// Return reference to the underlying channel end.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Return reference to running average messages are inserted into.
+    pub fn recorder(&self) -> &R {
+        &self.recorder
+    }
+
+    /// Return the underlying channel end consuming self.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<T> ChannelEnd for mpsc::Sender<T> {
+    type Item = T;
+}
+
+impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<mpsc::Sender<T>, R, F> {
+    /// Send message measuring it if it was sent successfully.
+    pub fn send(&mut self, message: T) -> Result<(), mpsc::SendError<T>> {
+        self.sending(message, |sender, message| sender.send(message))
+    }
+}
+
+impl<T> ChannelEnd for mpsc::SyncSender<T> {
+    type Item = T;
+}
+
+impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<mpsc::SyncSender<T>, R, F> {
+    /// Send message blocking if channel is full measuring it if it was sent successfully.
+    pub fn send(&mut self, message: T) -> Result<(), mpsc::SendError<T>> {
+        self.sending(message, |sender, message| sender.send(message))
+    }
+
+    /// Try to send message without blocking measuring it if it was sent successfully.
+    pub fn try_send(&mut self, message: T) -> Result<(), mpsc::TrySendError<T>> {
+        self.sending(message, |sender, message| sender.try_send(message))
+    }
+}
+
+impl<T> ChannelEnd for mpsc::Receiver<T> {
+    type Item = T;
+}
+
+impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<mpsc::Receiver<T>, R, F> {
+    /// Receive message blocking until one is available measuring it.
+    pub fn recv(&mut self) -> Result<T, mpsc::RecvError> {
+        let result = self.inner.recv();
+        self.received(result)
+    }
+
+    /// Try to receive message without blocking measuring it if one was received.
+    pub fn try_recv(&mut self) -> Result<T, mpsc::TryRecvError> {
+        let result = self.inner.try_recv();
+        self.received(result)
+    }
+
+    /// Receive message blocking for up to given timeout measuring it if one was received.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, mpsc::RecvTimeoutError> {
+        let result = self.inner.recv_timeout(timeout);
+        self.received(result)
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+mod crossbeam {
+    use std::time::Duration;
+    use crossbeam_channel::{Sender, Receiver, SendError, TrySendError, SendTimeoutError, RecvError, TryRecvError, RecvTimeoutError};
+
+    use super::{ChannelEnd, Measured};
+    use super::super::Insert;
+
+    impl<T> ChannelEnd for Sender<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<Sender<T>, R, F> {
+        /// Send message blocking if channel is full measuring it if it was sent successfully.
+        pub fn send(&mut self, message: T) -> Result<(), SendError<T>> {
+            self.sending(message, |sender, message| sender.send(message))
+        }
+
+        /// Try to send message without blocking measuring it if it was sent successfully.
+        pub fn try_send(&mut self, message: T) -> Result<(), TrySendError<T>> {
+            self.sending(message, |sender, message| sender.try_send(message))
+        }
+
+        /// Send message blocking for up to given timeout if channel is full measuring it if it was sent successfully.
+        pub fn send_timeout(&mut self, message: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+            self.sending(message, |sender, message| sender.send_timeout(message, timeout))
+        }
+    }
+
+    impl<T> ChannelEnd for Receiver<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<Receiver<T>, R, F> {
+        /// Receive message blocking until one is available measuring it.
+        pub fn recv(&mut self) -> Result<T, RecvError> {
+            let result = self.inner.recv();
+            self.received(result)
+        }
+
+        /// Try to receive message without blocking measuring it if one was received.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let result = self.inner.try_recv();
+            self.received(result)
+        }
+
+        /// Receive message blocking for up to given timeout measuring it if one was received.
+        pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+            let result = self.inner.recv_timeout(timeout);
+            self.received(result)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_mpsc {
+    use tokio::sync::mpsc::{Sender, UnboundedSender, Receiver, UnboundedReceiver};
+    use tokio::sync::mpsc::error::{SendError, TrySendError, TryRecvError};
+
+    use super::{ChannelEnd, Measured};
+    use super::super::Insert;
+
+    impl<T> ChannelEnd for Sender<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<Sender<T>, R, F> {
+        /// Send message waiting for channel capacity measuring it if it was sent successfully.
+        pub async fn send(&mut self, message: T) -> Result<(), SendError<T>> {
+            let weight = (self.weight)(&message);
+            self.inner.send(message).await?;
+            self.recorder.insert(weight);
+            Ok(())
+        }
+
+        /// Try to send message without waiting measuring it if it was sent successfully.
+        pub fn try_send(&mut self, message: T) -> Result<(), TrySendError<T>> {
+            self.sending(message, |sender, message| sender.try_send(message))
+        }
+    }
+
+    impl<T> ChannelEnd for UnboundedSender<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<UnboundedSender<T>, R, F> {
+        /// Send message measuring it if it was sent successfully.
+        pub fn send(&mut self, message: T) -> Result<(), SendError<T>> {
+            self.sending(message, |sender, message| sender.send(message))
+        }
+    }
+
+    impl<T> ChannelEnd for Receiver<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<Receiver<T>, R, F> {
+        /// Receive message waiting until one is available measuring it; returns `None` if channel is closed.
+        pub async fn recv(&mut self) -> Option<T> {
+            let message = self.inner.recv().await;
+            self.received(message.ok_or(())).ok()
+        }
+
+        /// Try to receive message without waiting measuring it if one was received.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let result = self.inner.try_recv();
+            self.received(result)
+        }
+    }
+
+    impl<T> ChannelEnd for UnboundedReceiver<T> {
+        type Item = T;
+    }
+
+    impl<T, R: Insert, F: FnMut(&T) -> R::Value> Measured<UnboundedReceiver<T>, R, F> {
+        /// Receive message waiting until one is available measuring it; returns `None` if channel is closed.
+        pub async fn recv(&mut self) -> Option<T> {
+            let message = self.inner.recv().await;
+            self.received(message.ok_or(())).ok()
+        }
+
+        /// Try to receive message without waiting measuring it if one was received.
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let result = self.inner.try_recv();
+            self.received(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Duration;
+    use super::super::{RealTimeRunningAverage, ManualTimeSource};
+
+    fn window() -> RealTimeRunningAverage<u32, ManualTimeSource> {
+        RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new())
+    }
+
+    #[test]
+    fn std_channel() {
+        use super::*;
+
+        let mut sent = window();
+        let mut received = window();
+
+        {
+            let (tx, rx) = mpsc::sync_channel(1);
+            let mut tx = Measured::new(tx, &mut sent);
+            let mut rx = Measured::with_weight(rx, &mut received, |message: &u32| *message);
+
+            tx.send(10).unwrap();
+            assert!(tx.try_send(20).is_err());
+            assert_eq!(rx.recv().unwrap(), 10);
+            assert!(rx.try_recv().is_err());
+
+            tx.try_send(30).unwrap();
+            drop(tx);
+            assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), 30);
+            assert!(rx.recv().is_err());
+        }
+
+        assert_eq!(sent.measurement().unwrap(), 2);
+        assert_eq!(received.measurement().unwrap(), 40);
+    }
+
+    #[test]
+    fn failed_send_is_not_measured() {
+        use super::*;
+
+        let mut sent = window();
+
+        {
+            let (tx, rx) = mpsc::channel();
+            drop(rx);
+            let mut tx = Measured::new(tx, &mut sent);
+            assert!(tx.send(()).is_err());
+        }
+
+        assert_eq!(sent.measurement().unwrap(), 0);
+    }
+
+    #[cfg(feature = "crossbeam")]
+    #[test]
+    fn crossbeam_channel() {
+        use super::*;
+
+        let mut sent = window();
+        let mut received = window();
+
+        {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            let mut tx = Measured::new(tx, &mut sent);
+            let mut rx = Measured::new(rx, &mut received);
+
+            tx.send("foo").unwrap();
+            assert!(tx.send_timeout("bar", Duration::from_millis(1)).is_err());
+            assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "foo");
+            tx.try_send("baz").unwrap();
+            assert_eq!(rx.try_recv().unwrap(), "baz");
+        }
+
+        assert_eq!(sent.measurement().unwrap(), 2);
+        assert_eq!(received.measurement().unwrap(), 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn tokio_channel() {
+        use super::*;
+
+        let mut sent = window();
+        let mut received = window();
+
+        {
+            let (tx, rx) = tokio::sync::mpsc::channel(2);
+            let mut tx = Measured::with_weight(tx, &mut sent, |message: &&str| message.len() as u32);
+            let mut rx = Measured::new(rx, &mut received);
+
+            tx.send("foo").await.unwrap();
+            tx.try_send("quux").unwrap();
+            assert_eq!(rx.recv().await, Some("foo"));
+            assert_eq!(rx.try_recv().unwrap(), "quux");
+            drop(tx);
+            assert_eq!(rx.recv().await, None);
+        }
+
+        assert_eq!(sent.measurement().unwrap(), 7);
+        assert_eq!(received.measurement().unwrap(), 2);
+    }
+}
@@ -0,0 +1,296 @@
+//! Comparison of the current time window with the immediately preceding one or one delayed by given lag.
+//!
+//! `ComparingRunningAverage` retains one extra window of expired buckets so `compare()` can report rate measured over the current time window
+//! next to rate measured over the previous one and percentage change between them (e.g. "throughput dropped 40% vs the previous minute").
+//! ```
+//! use std::time::Duration;
+//! use running_average::compare::ComparingRunningAverage;
+//!
+//! let mut tw = ComparingRunningAverage::new(Duration::from_secs(60));
+//!
+//! tw.insert(2000);
+//!
+//! // Note: this may fail as it is based on real time
+//! let comparison = tw.compare();
+//! assert_eq!(*comparison.current().value(), 2000);
+//! assert_eq!(*comparison.previous().value(), 0);
+//! ```
+//!
+//! `ComparativeWindow` keeps buckets for given lag so that the current time window can be compared with the same time span delayed by the lag
+//! (e.g. same time yesterday) from single stream of inserts.
+//! ```
+//! use std::time::Duration;
+//! use running_average::compare::ComparativeWindow;
+//!
+//! // Compare last minute with the same minute an hour ago
+//! let mut tw = ComparativeWindow::new(Duration::from_secs(60), Duration::from_secs(3600));
+//!
+//! tw.insert(2000);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(*tw.compare().current().value(), 2000);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{bucket_capacity, from_nanos, slot_duration, Accumulate, Insert, Measurement, RealTimeSource, RunningAverage, TimeSource, ToRate};
+
+/// Measurements of the current and the previous time window.
+#[derive(Debug, Clone)]
+pub struct Comparison<V> {
+    current: Measurement<V>,
+    previous: Measurement<V>,
+}
+
+impl<V> Comparison<V> {
+    /// Returns measurement of the current time window.
+    pub fn current(&self) -> &Measurement<V> {
+        &self.current
+    }
+
+    /// Returns measurement of the time window the current one is compared with.
+    pub fn previous(&self) -> &Measurement<V> {
+        &self.previous
+    }
+
+    /// Returns percentage change of rate of the current time window relative to the compared one (e.g. -40.0 for drop to 60%).
+    /// Returns infinity or not a number if previous rate is 0.
+    pub fn change(&self) -> f64 where V: Clone + ToRate, <V as ToRate>::Output: Into<f64> {
+        let current: f64 = self.current.rate().into();
+        let previous: f64 = self.previous.rate().into();
+        (current - previous) / previous * 100.0
+    }
+}
+
+/// Running average retaining buckets of the previous time window using given time source to obtain value of `now` instant.
+pub struct ComparingRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RunningAverage<V, TS::Instant>,
+    capacity: usize,
+    duration: Duration,
+    time_source: TS,
+}
+
+impl<V: Accumulate + fmt::Debug, TS: TimeSource> fmt::Debug for ComparingRunningAverage<V, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ComparingRunningAverage")
+            .field("inner", &self.inner)
+            .field("capacity", &self.capacity)
+            .field("duration", &self.duration)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<V: Accumulate> Default for ComparingRunningAverage<V, RealTimeSource> {
+    /// Crate new ComparingRunningAverage instance with window of 8 seconds width and 16 buckets.
+    fn default() -> ComparingRunningAverage<V, RealTimeSource> {
+        ComparingRunningAverage::new(Duration::from_secs(8))
+    }
+}
+
+impl<V: Accumulate> ComparingRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and 16 buckets using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> ComparingRunningAverage<V, RealTimeSource> {
+        ComparingRunningAverage::with_time_source(duration, 16, RealTimeSource)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> ComparingRunningAverage<V, TS> {
+    /// Crate new instance with window of given width duration and number of buckets using given as time source for `now` instant.
+    /// Twice the number of buckets is allocated to retain the previous time window; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> ComparingRunningAverage<V, TS> {
+        let capacity = bucket_capacity(duration, capacity);
+        ComparingRunningAverage {
+            inner: RunningAverage::with_capacity(duration * 2, capacity * 2),
+            capacity,
+            duration,
+            time_source,
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        let now = self.time_source.now();
+        self.inner.insert(now, val)
+    }
+
+    /// Calculate running average using the current time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        self.compare().current
+    }
+
+    /// Calculate running averages of the current time window ending now and of the time window preceding it.
+    /// Panics if time source time goes backwards.
+    pub fn compare(&mut self) -> Comparison<V> {
+        let now = self.time_source.now();
+        self.inner.shift(now);
+
+        let zero = self.inner.zero;
+        let current = self.inner.recent_first().take(self.capacity).fold(zero(), |sum, val| sum.merge(val));
+        let previous = self.inner.recent_first().skip(self.capacity).fold(zero(), |sum, val| sum.merge(val));
+
+        Comparison {
+            current: Measurement { value: current, duration: self.duration },
+            previous: Measurement { value: previous, duration: self.duration },
+        }
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for ComparingRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        ComparingRunningAverage::insert(self, val)
+    }
+}
+
+/// Running average retaining buckets for given lag to compare the current time window with one delayed by the lag using given time source to obtain value of `now` instant.
+/// Note: buckets of the same width as of the current time window are kept for the whole lag so memory usage grows with the ratio of the lag to the bucket width.
+pub struct ComparativeWindow<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RunningAverage<V, TS::Instant>,
+    capacity: usize,
+    lag: usize,
+    duration: Duration,
+    time_source: TS,
+}
+
+impl<V: Accumulate + fmt::Debug, TS: TimeSource> fmt::Debug for ComparativeWindow<V, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ComparativeWindow")
+            .field("inner", &self.inner)
+            .field("capacity", &self.capacity)
+            .field("lag", &self.lag)
+            .field("duration", &self.duration)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<V: Accumulate> ComparativeWindow<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and 16 buckets compared with window delayed by given lag, using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration, lag: Duration) -> ComparativeWindow<V, RealTimeSource> {
+        ComparativeWindow::with_time_source(duration, 16, lag, RealTimeSource)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> ComparativeWindow<V, TS> {
+    /// Crate new instance with window of given width duration and number of buckets compared with window delayed by given lag, using given as time source for `now` instant.
+    /// Lag is rounded to whole number of bucket widths.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn with_time_source(duration: Duration, capacity: usize, lag: Duration, time_source: TS) -> ComparativeWindow<V, TS> {
+        assert!(capacity > 0, "ComparativeWindow capacity cannot be 0");
+        let capacity = bucket_capacity(duration, capacity);
+        let slot_nanos = slot_duration(duration, capacity).as_nanos();
+        let lag_slots = ((lag.as_nanos() + slot_nanos / 2) / slot_nanos) as usize;
+
+        ComparativeWindow {
+            inner: RunningAverage::with_capacity(from_nanos(slot_nanos * (capacity + lag_slots) as u128), capacity + lag_slots),
+            capacity,
+            lag: lag_slots,
+            duration,
+            time_source,
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        let now = self.time_source.now();
+        self.inner.insert(now, val)
+    }
+
+    /// Calculate running average using the current time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        self.compare().current
+    }
+
+    /// Calculate running averages of the current time window ending now and of the time window delayed by the lag.
+    /// Panics if time source time goes backwards.
+    pub fn compare(&mut self) -> Comparison<V> {
+        let now = self.time_source.now();
+        self.inner.shift(now);
+
+        let zero = self.inner.zero;
+        let current = self.inner.recent_first().take(self.capacity).fold(zero(), |sum, val| sum.merge(val));
+        let delayed = self.inner.recent_first().skip(self.lag).take(self.capacity).fold(zero(), |sum, val| sum.merge(val));
+
+        Comparison {
+            current: Measurement { value: current, duration: self.duration },
+            previous: Measurement { value: delayed, duration: self.duration },
+        }
+    }
+
+    /// Returns the lag rounded to whole number of bucket widths.
+    pub fn lag(&self) -> Duration {
+        from_nanos(self.inner.bucket_duration().as_nanos() * self.lag as u128)
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for ComparativeWindow<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        ComparativeWindow::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn compare_with_previous_window() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = ComparingRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        for second in 0..8 {
+            tw.time_source().set(second as f64 + 0.5);
+            tw.insert(if second < 4 { 10 } else { 6 });
+        }
+
+        let comparison = tw.compare();
+        assert_eq!(*comparison.current().value(), 24);
+        assert_eq!(*comparison.previous().value(), 40);
+        assert_eq!(comparison.change(), -40.0);
+        assert_eq!(tw.measurement().to_rate(), 6.0);
+
+        tw.time_source().time_shift(4.0);
+        let comparison = tw.compare();
+        assert_eq!(*comparison.current().value(), 0);
+        assert_eq!(*comparison.previous().value(), 24);
+        assert_eq!(comparison.change(), -100.0);
+    }
+
+    #[test]
+    fn compare_with_delayed_window() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = ComparativeWindow::with_time_source(Duration::from_secs(2), 2, Duration::from_secs(10), ManualTimeSource::new());
+        assert_eq!(tw.lag(), Duration::from_secs(10));
+
+        for second in 0..20 {
+            tw.time_source().set(second as f64 + 0.5);
+            tw.insert(second);
+        }
+
+        let comparison = tw.compare();
+        assert_eq!(*comparison.current().value(), 19 + 18);
+        assert_eq!(*comparison.previous().value(), 9 + 8);
+        assert_eq!(tw.measurement().to_rate(), 18.5);
+    }
+}
@@ -0,0 +1,293 @@
+//! Declarative configuration of time windows.
+//!
+//! `WindowConfig` holds window width, number (or width) of buckets and handling of time going backwards so that running averages can be created from application configuration.
+//! With `serde` feature it can be serialized and deserialized, e.g. from YAML or TOML, where durations are given in seconds
+//! and, with `humantime` feature, also as human readable durations like `"8s"` or `"1h30m"`, and backwards policy as `"panic"`, `"error"` or `"clamp"`; all fields are optional.
+//! Use `try_from_config()` to get an error rather than panic on invalid configuration.
+//!
+//! Note: there is no `interpolation` setting as measurements always sum whole buckets without interpolating the oldest one.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::config::WindowConfig;
+//!
+//! let config = WindowConfig {
+//!     duration: Duration::from_secs(60),
+//!     capacity: 60,
+//!     ..WindowConfig::default()
+//! };
+//!
+//! let mut tw = RealTimeRunningAverage::try_from_config(&config).unwrap();
+//! tw.record(120).unwrap();
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tw.measurement().rate(), 2.0);
+//! ```
+
+use std::time::Duration;
+
+use super::{resolution_capacity, validate_capacity, Accumulate, BackwardsPolicy, InvalidCapacity, RealTimeRunningAverage, RealTimeSource, RunningAverage, TimeInstant, TimeSource};
+
+/// Width of time window and its buckets and handling of time going backwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct WindowConfig {
+    /// Width of the time window.
+    #[cfg_attr(feature = "serde", serde(with = "seconds"))]
+    pub duration: Duration,
+    /// Number of buckets used; ignored if `bucket_duration` is set.
+    pub capacity: usize,
+    /// Width of each bucket; number of buckets is computed as with `RunningAverage::with_resolution()`.
+    #[cfg_attr(feature = "serde", serde(with = "optional_seconds", skip_serializing_if = "Option::is_none"))]
+    pub bucket_duration: Option<Duration>,
+    /// Handling of time going backwards by `record()`.
+    pub backwards_policy: BackwardsPolicy,
+}
+
+impl Default for WindowConfig {
+    /// Window of 8 seconds width and 16 buckets that panics when time goes backwards.
+    fn default() -> WindowConfig {
+        WindowConfig {
+            duration: Duration::from_secs(8),
+            capacity: 16,
+            bucket_duration: None,
+            backwards_policy: BackwardsPolicy::Panic,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Returns error if capacity is 0 or greater than number of nanoseconds in the window, e.g. to check configuration before constructing running averages.
+    pub fn validate(&self) -> Result<(), InvalidCapacity> {
+        validate_capacity(self.duration, self.buckets())
+    }
+
+    fn buckets(&self) -> usize {
+        match self.bucket_duration {
+            Some(bucket_duration) => resolution_capacity(self.duration, bucket_duration),
+            None => self.capacity,
+        }
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> RunningAverage<V, I> {
+    /// Crate new RunningAverage instance with window configured by given config.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn from_config(config: &WindowConfig) -> RunningAverage<V, I> {
+        RunningAverage::with_capacity(config.duration, config.buckets()).with_backwards_policy(config.backwards_policy)
+    }
+
+    /// Crate new RunningAverage instance with window configured by given config or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_from_config(config: &WindowConfig) -> Result<RunningAverage<V, I>, InvalidCapacity> {
+        config.validate()?;
+        Ok(RunningAverage::from_config(config))
+    }
+}
+
+impl<V: Accumulate> RealTimeRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window configured by given config using RealTimeSource as time source for `now` instant.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn from_config(config: &WindowConfig) -> RealTimeRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::from_config_with_time_source(config, RealTimeSource)
+    }
+
+    /// Crate new instance with window configured by given config using RealTimeSource as time source for `now` instant
+    /// or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_from_config(config: &WindowConfig) -> Result<RealTimeRunningAverage<V, RealTimeSource>, InvalidCapacity> {
+        RealTimeRunningAverage::try_from_config_with_time_source(config, RealTimeSource)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> RealTimeRunningAverage<V, TS> {
+    /// Crate new instance with window configured by given config using given time source for `now` instant.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn from_config_with_time_source(config: &WindowConfig, time_source: TS) -> RealTimeRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source(config.duration, config.buckets(), time_source).with_backwards_policy(config.backwards_policy)
+    }
+
+    /// Crate new instance with window configured by given config using given time source for `now` instant
+    /// or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_from_config_with_time_source(config: &WindowConfig, time_source: TS) -> Result<RealTimeRunningAverage<V, TS>, InvalidCapacity> {
+        config.validate()?;
+        Ok(RealTimeRunningAverage::from_config_with_time_source(config, time_source))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod seconds {
+    use std::convert::TryFrom;
+    use std::fmt;
+    use std::time::Duration;
+
+    use serde::{Deserializer, Serializer};
+    use serde::de::{Error, Visitor};
+
+    struct SecondsVisitor;
+
+    impl<'de> Visitor<'de> for SecondsVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            if cfg!(feature = "humantime") {
+                f.write_str("non-negative number of seconds or human readable duration")
+            } else {
+                f.write_str("non-negative number of seconds")
+            }
+        }
+
+        fn visit_u64<E: Error>(self, seconds: u64) -> Result<Duration, E> {
+            Ok(Duration::from_secs(seconds))
+        }
+
+        fn visit_i64<E: Error>(self, seconds: i64) -> Result<Duration, E> {
+            u64::try_from(seconds).map(Duration::from_secs).map_err(|_| E::custom(format!("negative duration of {} seconds", seconds)))
+        }
+
+        fn visit_f64<E: Error>(self, seconds: f64) -> Result<Duration, E> {
+            Duration::try_from_secs_f64(seconds).map_err(|err| E::custom(format!("invalid duration of {} seconds: {}", seconds, err)))
+        }
+
+        #[cfg(feature = "humantime")]
+        fn visit_str<E: Error>(self, duration: &str) -> Result<Duration, E> {
+            ::humantime::parse_duration(duration).map_err(|err| E::custom(format!("invalid duration {:?}: {}", duration, err)))
+        }
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "humantime")]
+        return serializer.collect_str(&::humantime::format_duration(*duration));
+        #[cfg(not(feature = "humantime"))]
+        return serializer.serialize_f64(duration.as_secs_f64());
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(SecondsVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod optional_seconds {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Duration deserialized with `seconds` module.
+    #[derive(Deserialize)]
+    struct Seconds(#[serde(with = "super::seconds")] Duration);
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::seconds::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<Seconds>::deserialize(deserializer)?.map(|Seconds(duration)| duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn from_config() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        assert!(WindowConfig::default().validate().is_ok());
+        assert!(WindowConfig { capacity: 0, ..WindowConfig::default() }.validate().is_err());
+        assert!(WindowConfig { capacity: 0, bucket_duration: Some(Duration::from_secs(1)), ..WindowConfig::default() }.validate().is_ok());
+
+        let mut tw = RunningAverage::<u32, f64>::from_config(&WindowConfig::default());
+        tw.insert(0.0, 80);
+        assert_eq!(tw.measurement(0.0).rate(), 10.0);
+        assert_eq!(tw.measurement(7.0).unwrap(), 80);
+        assert_eq!(tw.measurement(8.0).unwrap(), 0);
+
+        let config = WindowConfig {
+            duration: Duration::from_secs(4),
+            capacity: 16,
+            bucket_duration: Some(Duration::from_secs(2)),
+            ..WindowConfig::default()
+        };
+        let mut tw = RealTimeRunningAverage::<u32, _>::from_config_with_time_source(&config, ManualTimeSource::new());
+        tw.insert(40);
+        tw.time_source().time_shift(1.0);
+        tw.insert(40);
+        tw.time_source().time_shift(1.0);
+        // Both values are in the first of two buckets of 2 seconds width
+        assert_eq!(tw.measurement().unwrap(), 80);
+        tw.time_source().time_shift(2.0);
+        assert_eq!(tw.measurement().unwrap(), 0);
+    }
+
+    #[test]
+    fn try_from_config() {
+        use super::*;
+        use super::super::{ManualTimeSource, TimeWentBackwards};
+
+        let invalid = WindowConfig { capacity: 0, ..WindowConfig::default() };
+        assert_eq!(RunningAverage::<u32, f64>::try_from_config(&invalid).unwrap_err(), InvalidCapacity { duration: Duration::from_secs(8), capacity: 0 });
+        assert!(RealTimeRunningAverage::<u32>::try_from_config(&invalid).is_err());
+        let invalid = WindowConfig { duration: Duration::from_nanos(4), capacity: 8, ..WindowConfig::default() };
+        assert!(RealTimeRunningAverage::<u32, _>::try_from_config_with_time_source(&invalid, ManualTimeSource::new()).is_err());
+
+        let config = WindowConfig { duration: Duration::from_secs(4), capacity: 4, backwards_policy: BackwardsPolicy::Error, ..WindowConfig::default() };
+        let mut tw = RunningAverage::<u32, f64>::try_from_config(&config).unwrap();
+        assert_eq!(tw.record(2.0, 1), Ok(()));
+        assert_eq!(tw.record(1.0, 1), Err(TimeWentBackwards));
+        assert_eq!(tw.measurement(2.0).unwrap(), 1);
+
+        let config = WindowConfig { backwards_policy: BackwardsPolicy::Clamp, ..config };
+        let mut tw = RealTimeRunningAverage::<u32, _>::try_from_config_with_time_source(&config, ManualTimeSource::new()).unwrap();
+        tw.time_source().set(2.0);
+        assert_eq!(tw.record(1), Ok(()));
+        tw.time_source().set(1.0);
+        assert_eq!(tw.record(1), Ok(()));
+        tw.time_source().set(2.0);
+        // Value recorded backwards in time went to the current bucket
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![2, 0, 0, 0]);
+
+        let config = WindowConfig { backwards_policy: BackwardsPolicy::Panic, ..config };
+        let mut tw = RunningAverage::<u32, f64>::try_from_config(&config).unwrap();
+        tw.record(2.0, 1).unwrap();
+        assert!(std::panic::catch_unwind(move || tw.record(1.0, 1)).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_config() {
+        use super::*;
+
+        let config: WindowConfig = serde_json::from_str(r#"{"duration": 60, "capacity": 60}"#).unwrap();
+        assert_eq!(config, WindowConfig { duration: Duration::from_secs(60), capacity: 60, ..WindowConfig::default() });
+
+        let config: WindowConfig = serde_json::from_str(r#"{"bucket_duration": 0.5}"#).unwrap();
+        assert_eq!(config, WindowConfig { bucket_duration: Some(Duration::from_millis(500)), ..WindowConfig::default() });
+
+        let config: WindowConfig = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(config.bucket_duration, Some(Duration::from_millis(500)));
+
+        assert!(serde_json::from_str::<WindowConfig>(r#"{"duration": -1}"#).is_err());
+
+        let config: WindowConfig = serde_json::from_str(r#"{"backwards_policy": "clamp"}"#).unwrap();
+        assert_eq!(config.backwards_policy, BackwardsPolicy::Clamp);
+        assert!(serde_json::from_str::<WindowConfig>(r#"{"backwards_policy": "ignore"}"#).is_err());
+
+        let config: WindowConfig = serde_json::from_str(r#"{"capacity": 0}"#).unwrap();
+        assert!(RunningAverage::<u32, f64>::try_from_config(&config).is_err());
+    }
+
+    #[cfg(feature = "humantime")]
+    #[test]
+    fn deserialize_humantime_config() {
+        use super::*;
+
+        let config: WindowConfig = serde_json::from_str(r#"{"duration": "1h30m", "bucket_duration": "1min"}"#).unwrap();
+        assert_eq!(config.duration, Duration::from_secs(5400));
+        assert_eq!(config.bucket_duration, Some(Duration::from_secs(60)));
+        assert_eq!(serde_json::to_string(&config).unwrap(), r#"{"duration":"1h 30m","capacity":16,"bucket_duration":"1m","backwards_policy":"panic"}"#);
+        assert!(serde_json::from_str::<WindowConfig>(r#"{"duration": "8 parsecs"}"#).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Windowed relative-error quantiles with one DDSketch per bucket (requires `ddsketch` feature).
+//!
+//! `sketches_ddsketch::DDSketch` implements `Accumulate` so it can be used as running average value: values are added to sketch of the current bucket
+//! and sketches of all buckets are merged on measurement giving quantiles of values inserted within the time window with bounded relative error,
+//! ready to be exported to monitoring backends that natively understand DDSketch.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use sketches_ddsketch::DDSketch;
+//!
+//! let mut latency = RealTimeRunningAverage::<DDSketch>::new(Duration::from_secs(60));
+//!
+//! for ms in 1..=100 {
+//!     latency.accumulate(ms as f64);
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! let sketch = latency.measurement().unwrap();
+//! assert_eq!(sketch.count(), 100);
+//! assert!((sketch.quantile(0.99).unwrap().unwrap() - 99.0).abs() <= 99.0 * 0.01);
+//! ```
+
+use ::sketches_ddsketch::{Config, DDSketch};
+
+use super::Accumulate;
+
+/// Empty bucket is sketch with default configuration (1% relative error); use `RunningAverage::with_capacity_and_init()` for other configurations.
+/// Panics on measurement if sketches of buckets have different configurations.
+impl Accumulate<f64> for DDSketch {
+    fn zero() -> DDSketch {
+        DDSketch::new(Config::defaults())
+    }
+
+    fn add(&mut self, val: f64) {
+        DDSketch::add(self, val)
+    }
+
+    fn merge(&self, other: &DDSketch) -> DDSketch {
+        let mut sum = self.clone();
+        DDSketch::merge(&mut sum, other).expect("DDSketch buckets with different configurations");
+        sum
+    }
+}
+
+impl Accumulate for DDSketch {
+    fn zero() -> DDSketch {
+        DDSketch::new(Config::defaults())
+    }
+
+    fn add(&mut self, sketch: DDSketch) {
+        DDSketch::merge(self, &sketch).expect("DDSketch inserted with different configuration")
+    }
+
+    fn merge(&self, other: &DDSketch) -> DDSketch {
+        Accumulate::<f64>::merge(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn windowed_sketch() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{ManualTimeSource, RealTimeRunningAverage};
+
+        let mut tw = RealTimeRunningAverage::<DDSketch, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.measurement().unwrap().quantile(0.5).unwrap(), None);
+
+        for val in 1..=1000 {
+            tw.accumulate(val as f64);
+        }
+        tw.time_source().time_shift(2.0);
+        tw.insert({
+            let mut sketch = DDSketch::new(Config::defaults());
+            sketch.add(5000.0);
+            sketch
+        });
+
+        let sketch = tw.measurement().unwrap();
+        assert_eq!(sketch.count(), 1001);
+        assert!((sketch.quantile(0.5).unwrap().unwrap() - 501.0).abs() <= 501.0 * 0.01);
+        assert_eq!(sketch.max(), Some(5000.0));
+
+        tw.time_source().time_shift(2.0);
+        let sketch = tw.measurement().unwrap();
+        assert_eq!(sketch.count(), 1);
+        assert_eq!(sketch.min(), Some(5000.0));
+    }
+}
@@ -0,0 +1,213 @@
+//! Exact sliding time window storing individual timestamped samples.
+//!
+//! `ExactWindow` keeps every inserted value with its timestamp, up to given number of samples, and sums exactly these inserted within the time window
+//! so there is no bucket quantization error; this suits low rate, high accuracy measurements.
+//! When the sample cap is reached `try_insert()` returns an error while `insert()` falls back to accumulating the value into the most recent sample.
+//! ```
+//! use std::time::Duration;
+//! use running_average::exact::ExactWindow;
+//!
+//! let mut tw = ExactWindow::new(Duration::from_secs(8), 1024);
+//!
+//! tw.insert(2000);
+//! tw.insert(1000);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tw.measurement().unwrap(), 3000);
+//! ```
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeSource, TimeInstant, TimeSource};
+
+/// Error returned when value cannot be inserted as the window has reached its cap of samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityExceeded<V> {
+    capacity: usize,
+    value: V,
+}
+
+impl<V> CapacityExceeded<V> {
+    /// Returns maximum number of samples the window can store.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the value that was not inserted.
+    pub fn into_value(self) -> V {
+        self.value
+    }
+}
+
+impl<V> fmt::Display for CapacityExceeded<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "exact window cannot store more than {} samples", self.capacity)
+    }
+}
+
+impl<V: fmt::Debug> Error for CapacityExceeded<V> {}
+
+/// Time window summing exactly the values inserted within its width using given time source to obtain value of `now` instant.
+pub struct ExactWindow<V, TS: TimeSource = RealTimeSource> {
+    samples: VecDeque<(TS::Instant, V)>,
+    capacity: usize,
+    duration: Duration,
+    time_source: TS,
+}
+
+impl<V: fmt::Debug, TS: TimeSource> fmt::Debug for ExactWindow<V, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExactWindow")
+            .field("samples", &self.samples)
+            .field("capacity", &self.capacity)
+            .field("duration", &self.duration)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<V> ExactWindow<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration storing up to given number of samples and using RealTimeSource as time source for `now` instant.
+    /// Panics if capacity is 0.
+    pub fn new(duration: Duration, capacity: usize) -> ExactWindow<V, RealTimeSource> {
+        ExactWindow::with_time_source(duration, capacity, RealTimeSource)
+    }
+}
+
+impl<V, TS: TimeSource> ExactWindow<V, TS> {
+    /// Crate new instance with window of given width duration storing up to given number of samples and using given as time source for `now` instant.
+    /// Panics if capacity is 0.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> ExactWindow<V, TS> {
+        assert!(capacity > 0, "ExactWindow capacity cannot be 0");
+        ExactWindow {
+            samples: VecDeque::new(),
+            capacity,
+            duration,
+            time_source,
+        }
+    }
+
+    fn expire(&mut self, now: TS::Instant) {
+        while let Some((time, _)) = self.samples.front() {
+            if now.duration_since(*time) < self.duration {
+                break
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Insert value to be average over now or return it with error if cap of samples within the time window is reached.
+    /// Panics if time source time goes backwards.
+    pub fn try_insert(&mut self, val: V) -> Result<(), CapacityExceeded<V>> {
+        let now = self.time_source.now();
+        self.expire(now);
+
+        if self.samples.len() == self.capacity {
+            return Err(CapacityExceeded { capacity: self.capacity, value: val })
+        }
+        self.samples.push_back((now, val));
+        Ok(())
+    }
+
+    /// Insert value to be average over now; if cap of samples within the time window is reached
+    /// the value is accumulated into the most recent sample so it will expire together with it.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) where V: Accumulate {
+        if let Err(exceeded) = self.try_insert(val) {
+            let (_, recent) = self.samples.back_mut().expect("ExactWindow at capacity with no samples");
+            recent.add(exceeded.into_value());
+        }
+    }
+
+    /// Calculate sum of values inserted within time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> where V: Accumulate {
+        let now = self.time_source.now();
+        self.expire(now);
+
+        Measurement {
+            value: self.samples.iter().fold(V::zero(), |sum, (_, val)| sum.merge(val)),
+            duration: self.duration,
+        }
+    }
+
+    /// Returns number of samples stored.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if no samples are stored.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns maximum number of samples stored.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for ExactWindow<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        ExactWindow::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn exact_expiry() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = ExactWindow::with_time_source(Duration::from_secs(4), 8, ManualTimeSource::new());
+
+        tw.insert(10);
+        tw.time_source().time_shift(0.5);
+        tw.insert(20);
+        tw.time_source().time_shift(3.25);
+        assert_eq!(tw.measurement().unwrap(), 30);
+
+        tw.time_source().time_shift(0.25);
+        assert_eq!(tw.measurement().unwrap(), 20);
+
+        tw.time_source().time_shift(0.5);
+        assert_eq!(tw.measurement().unwrap(), 0);
+        assert!(tw.is_empty());
+    }
+
+    #[test]
+    fn capacity_exceeded() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = ExactWindow::with_time_source(Duration::from_secs(4), 2, ManualTimeSource::new());
+
+        assert!(tw.try_insert(1).is_ok());
+        tw.time_source().time_shift(1.0);
+        assert!(tw.try_insert(2).is_ok());
+
+        let exceeded = tw.try_insert(4).unwrap_err();
+        assert_eq!(exceeded.capacity(), 2);
+        assert_eq!(exceeded.into_value(), 4);
+
+        tw.insert(4);
+        assert_eq!(tw.len(), 2);
+        assert_eq!(tw.measurement().unwrap(), 7);
+
+        // Sample of the first value expired making room for another
+        tw.time_source().time_shift(3.0);
+        assert!(tw.try_insert(8).is_ok());
+        assert_eq!(tw.measurement().unwrap(), 14);
+    }
+}
@@ -0,0 +1,132 @@
+//! C ABI for embedding running average in C/C++ programs (requires `ffi` feature).
+//!
+//! Functions operate on opaque `RaWindow` running average of `f64` values using real time clock;
+//! C header generated with cbindgen is available in `include/running_average.h`.
+//! Build static or dynamic library with `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+//! ```c
+//! #include "running_average.h"
+//!
+//! RaWindow *window = ra_new(8.0, 16);
+//! ra_insert(window, 2000.0);
+//! double rate = ra_measure(window);
+//! ra_free(window);
+//! ```
+
+use std::ptr;
+use std::time::Duration;
+
+use super::{RealTimeRunningAverage, RealTimeSource};
+
+/// Running average of `f64` values over time window using real time clock.
+#[derive(Debug)]
+pub struct RaWindow {
+    inner: RealTimeRunningAverage<f64>,
+}
+
+/// Create new running average with window of given width in seconds consisting of given number of buckets.
+/// Returns NULL if window width is not positive or too large or capacity is 0 or greater than number of nanoseconds in the window.
+/// The returned pointer needs to be released with `ra_free()`.
+#[no_mangle]
+pub extern "C" fn ra_new(window_seconds: f64, capacity: usize) -> *mut RaWindow {
+    // Panicking here would abort the host process
+    let duration = match Duration::try_from_secs_f64(window_seconds) {
+        Ok(duration) if window_seconds > 0.0 => duration,
+        _ => return ptr::null_mut(),
+    };
+
+    match RealTimeRunningAverage::try_with_time_source(duration, capacity, RealTimeSource) {
+        Ok(inner) => Box::into_raw(Box::new(RaWindow { inner })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Insert value to be averaged over now.
+///
+/// # Safety
+/// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+#[no_mangle]
+pub unsafe extern "C" fn ra_insert(window: *mut RaWindow, value: f64) {
+    if let Some(window) = window.as_mut() {
+        window.inner.insert(value)
+    }
+}
+
+/// Insert integer value to be averaged over now.
+///
+/// # Safety
+/// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+#[no_mangle]
+pub unsafe extern "C" fn ra_insert_u64(window: *mut RaWindow, value: u64) {
+    ra_insert(window, value as f64)
+}
+
+/// Returns rate (sum of values within time window ending now per second).
+/// Returns NaN if window is NULL.
+///
+/// # Safety
+/// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn ra_measure(window: *mut RaWindow) -> f64 {
+    match window.as_mut() {
+        Some(window) => window.inner.measurement().rate(),
+        None => f64::NAN,
+    }
+}
+
+/// Returns sum of values within time window ending now.
+/// Returns NaN if window is NULL.
+///
+/// # Safety
+/// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn ra_value(window: *mut RaWindow) -> f64 {
+    match window.as_mut() {
+        Some(window) => window.inner.measurement().unwrap(),
+        None => f64::NAN,
+    }
+}
+
+/// Release running average.
+///
+/// # Safety
+/// Window needs to be a pointer returned by `ra_new()` that was not freed yet or NULL in which case nothing is done.
+#[no_mangle]
+pub unsafe extern "C" fn ra_free(window: *mut RaWindow) {
+    if !window.is_null() {
+        drop(Box::from_raw(window))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn insert_and_measure() {
+        use super::*;
+
+        assert!(ra_new(0.0, 16).is_null());
+        assert!(ra_new(8.0, 0).is_null());
+        assert!(ra_new(f64::NAN, 16).is_null());
+        assert!(ra_new(f64::INFINITY, 16).is_null());
+        assert!(ra_new(1e20, 16).is_null());
+        assert!(ra_new(1e-9, 2).is_null());
+        assert!(ra_new(1.0, usize::MAX).is_null());
+
+        let window = ra_new(8.0, 16);
+        assert!(!window.is_null());
+
+        unsafe {
+            ra_insert(window, 10.0);
+            ra_insert_u64(window, 10);
+
+            // Note: this may fail as it is based on real time
+            assert_eq!(ra_value(window), 20.0);
+            assert_eq!(ra_measure(window), 2.5);
+
+            ra_free(window);
+
+            ra_insert(ptr::null_mut(), 1.0);
+            assert!(ra_measure(ptr::null_mut()).is_nan());
+            ra_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Output-side filtering of measurements reducing churn of reported rates.
+//!
+//! `DebouncedMeasurement` only reports new measurement when its rate changed by more than given delta since the last reported one
+//! or when given minimum interval elapsed since then, so logs and UIs are not updated on tiny fluctuations.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::filter::DebouncedMeasurement;
+//!
+//! let mut tw = DebouncedMeasurement::new(RealTimeRunningAverage::new(Duration::from_secs(8)), 10.0, Duration::from_secs(60));
+//!
+//! tw.insert(800);
+//! // First measurement is always reported
+//! assert!(tw.measurement().is_some());
+//!
+//! tw.insert(1);
+//! // Note: this may fail as it is based on real time
+//! assert!(tw.measurement().is_none());
+//! assert_eq!(tw.last().unwrap().rate(), 100.0);
+//! ```
+//!
+//! `SmoothedReader` applies single-pole low-pass filter of given time constant to rates of successive measurements
+//! so that displayed rate is stable even with small number of buckets.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::filter::SmoothedReader;
+//!
+//! let mut tw = SmoothedReader::new(RealTimeRunningAverage::new(Duration::from_secs(8)), Duration::from_secs(2));
+//!
+//! tw.insert(800);
+//! // First rate is not smoothed
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tw.rate(), 100.0);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, RealTimeSource, TimeInstant, TimeSource};
+
+/// Running average reporting measurements only when rate changes by more than given delta or after given minimum interval.
+pub struct DebouncedMeasurement<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+    delta: f64,
+    min_interval: Duration,
+    last: Option<(TS::Instant, Measurement<V>)>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for DebouncedMeasurement<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug, TS::Instant: fmt::Debug, V: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DebouncedMeasurement")
+            .field("inner", &self.inner)
+            .field("delta", &self.delta)
+            .field("min_interval", &self.min_interval)
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> DebouncedMeasurement<V, TS> {
+    /// Crate new instance reporting measurements of given running average when rate changes by more than given delta (in units per second)
+    /// or when given minimum interval elapsed since the last reported measurement.
+    pub fn new(running_average: RealTimeRunningAverage<V, TS>, delta: f64, min_interval: Duration) -> DebouncedMeasurement<V, TS> {
+        DebouncedMeasurement {
+            inner: running_average,
+            delta,
+            min_interval,
+            last: None,
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        self.inner.insert(val)
+    }
+
+    /// Calculate running average using time window ending now and return it if it should be reported; None otherwise.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Option<Measurement<V>> where V: Clone + Into<f64> {
+        let now = self.inner.time_source().now();
+        let measurement = self.inner.measurement();
+
+        let report = match &self.last {
+            None => true,
+            Some((reported, last)) => (measurement.rate_f64() - last.rate_f64()).abs() > self.delta || now.duration_since(*reported) >= self.min_interval,
+        };
+
+        if report {
+            self.last = Some((now, measurement.clone()));
+            Some(measurement)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the last reported measurement.
+    pub fn last(&self) -> Option<&Measurement<V>> {
+        self.last.as_ref().map(|(_, measurement)| measurement)
+    }
+
+    /// Return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        &mut self.inner
+    }
+
+    /// Return the underlying running average consuming self.
+    pub fn into_inner(self) -> RealTimeRunningAverage<V, TS> {
+        self.inner
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for DebouncedMeasurement<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        DebouncedMeasurement::insert(self, val)
+    }
+}
+
+/// Running average smoothing rates of successive measurements with exponential low-pass filter.
+pub struct SmoothedReader<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+    time_constant: Duration,
+    smoothed: Option<(TS::Instant, f64)>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for SmoothedReader<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug, TS::Instant: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SmoothedReader")
+            .field("inner", &self.inner)
+            .field("time_constant", &self.time_constant)
+            .field("smoothed", &self.smoothed)
+            .finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> SmoothedReader<V, TS> {
+    /// Crate new instance smoothing rates measured with given running average using filter of given time constant;
+    /// after time constant elapsed smoothed rate moved 63% of the way towards the measured rate.
+    pub fn new(running_average: RealTimeRunningAverage<V, TS>, time_constant: Duration) -> SmoothedReader<V, TS> {
+        SmoothedReader {
+            inner: running_average,
+            time_constant,
+            smoothed: None,
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        self.inner.insert(val)
+    }
+
+    /// Calculate rate over time window ending now and return it smoothed with rates of previous calls.
+    /// First call returns measured rate.
+    /// Panics if time source time goes backwards.
+    pub fn rate(&mut self) -> f64 where V: Clone + Into<f64> {
+        let now = self.inner.time_source().now();
+        let rate = self.inner.measurement().rate_f64();
+
+        let smoothed = match self.smoothed {
+            None => rate,
+            Some((last, smoothed)) => {
+                let elapsed = now.duration_since(last).as_secs_f64();
+                let alpha = match self.time_constant.as_secs_f64() {
+                    tau if tau > 0.0 => 1.0 - (-elapsed / tau).exp(),
+                    _ => 1.0,
+                };
+                smoothed + alpha * (rate - smoothed)
+            }
+        };
+
+        self.smoothed = Some((now, smoothed));
+        smoothed
+    }
+
+    /// Returns smoothed rate returned by the last call to `rate()`.
+    pub fn last(&self) -> Option<f64> {
+        self.smoothed.map(|(_, smoothed)| smoothed)
+    }
+
+    /// Forget smoothed rate so that next call to `rate()` returns measured rate.
+    pub fn reset(&mut self) {
+        self.smoothed = None;
+    }
+
+    /// Return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        &mut self.inner
+    }
+
+    /// Return the underlying running average consuming self.
+    pub fn into_inner(self) -> RealTimeRunningAverage<V, TS> {
+        self.inner
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for SmoothedReader<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SmoothedReader::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn report_on_change_or_interval() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = DebouncedMeasurement::new(RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()), 1.0, Duration::from_secs(2));
+        assert!(tw.last().is_none());
+        assert_eq!(tw.measurement().unwrap().unwrap(), 0);
+
+        tw.insert(4);
+        assert!(tw.measurement().is_none());
+        tw.insert(1);
+        assert_eq!(tw.measurement().unwrap().unwrap(), 5);
+        assert_eq!(*tw.last().unwrap().value(), 5);
+
+        tw.get_mut().time_source().time_shift(1.0);
+        assert!(tw.measurement().is_none());
+        tw.get_mut().time_source().time_shift(1.0);
+        assert_eq!(tw.measurement().unwrap().unwrap(), 5);
+    }
+
+    #[test]
+    fn low_pass_filtered_rate() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = SmoothedReader::new(RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()), Duration::from_secs(1));
+        assert!(tw.last().is_none());
+
+        tw.insert(40.0);
+        assert_eq!(tw.rate(), 10.0);
+
+        // No time elapsed so measured rate has no effect
+        tw.insert(40.0);
+        assert_eq!(tw.rate(), 10.0);
+
+        tw.get_mut().time_source().time_shift(1.0);
+        let expected = 10.0 + (1.0 - (-1.0f64).exp()) * 10.0;
+        let smoothed = tw.rate();
+        assert!((smoothed - expected).abs() < 1e-9);
+        assert_eq!(tw.last(), Some(smoothed));
+
+        tw.get_mut().time_source().time_shift(100.0);
+        assert!(tw.rate().abs() < 1e-9);
+
+        tw.reset();
+        tw.insert(4.0);
+        assert_eq!(tw.rate(), 1.0);
+    }
+}
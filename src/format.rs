@@ -0,0 +1,99 @@
+//! Human readable formatting of rates with SI (or binary) prefixes.
+//!
+//! `SiRate` displays any numeric rate scaled with `k`, `M`, `G`, ... prefixes (or `Ki`, `Mi`, `Gi`, ... for base 1024)
+//! with configurable number of decimal places.
+//! ```
+//! use running_average::format::SiRate;
+//!
+//! assert_eq!(SiRate::new(3400.0).to_string(), "3.4 k/s");
+//! assert_eq!(SiRate::new(1_234_567.0).precision(2).to_string(), "1.23 M/s");
+//! assert_eq!(SiRate::new(2048.0).binary().to_string(), "2.0 Ki/s");
+//! assert_eq!(SiRate::new(12.0).to_string(), "12.0/s");
+//! ```
+
+use core::fmt;
+
+use super::Measurement;
+
+const SI: [&str; 7] = ["", "k", "M", "G", "T", "P", "E"];
+const BINARY: [&str; 7] = ["", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei"];
+
+/// Rate per second displayed with SI or binary prefix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SiRate {
+    rate: f64,
+    precision: usize,
+    base: f64,
+}
+
+impl SiRate {
+    /// Format given rate with SI prefixes (base 1000) and one decimal place.
+    pub fn new(rate: f64) -> SiRate {
+        SiRate {
+            rate,
+            precision: 1,
+            base: 1000.0,
+        }
+    }
+
+    /// Use given number of decimal places.
+    pub fn precision(mut self, precision: usize) -> SiRate {
+        self.precision = precision;
+        self
+    }
+
+    /// Use binary prefixes (base 1024).
+    pub fn binary(mut self) -> SiRate {
+        self.base = 1024.0;
+        self
+    }
+
+    /// Returns rate scaled to its prefix together with the prefix.
+    pub fn scaled(&self) -> (f64, &'static str) {
+        let prefixes = if self.base == 1024.0 { &BINARY } else { &SI };
+
+        let mut rate = self.rate;
+        let mut prefix = 0;
+        while rate.abs() >= self.base && prefix < prefixes.len() - 1 {
+            rate /= self.base;
+            prefix += 1;
+        }
+        (rate, prefixes[prefix])
+    }
+}
+
+impl fmt::Display for SiRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.scaled() {
+            (rate, "") => write!(f, "{:.*}/s", self.precision, rate),
+            (rate, prefix) => write!(f, "{:.*} {}/s", self.precision, rate, prefix),
+        }
+    }
+}
+
+impl<T: Clone + Into<f64>> Measurement<T> {
+    /// Returns rate of this measurement that displays with SI prefix.
+    pub fn si_rate(&self) -> SiRate {
+        SiRate::new(self.rate_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn si_prefixes() {
+        use super::*;
+        use std::time::Duration;
+
+        assert_eq!(SiRate::new(0.0).to_string(), "0.0/s");
+        assert_eq!(SiRate::new(999.0).precision(0).to_string(), "999/s");
+        assert_eq!(SiRate::new(1000.0).to_string(), "1.0 k/s");
+        assert_eq!(SiRate::new(-2_500_000.0).to_string(), "-2.5 M/s");
+        assert_eq!(SiRate::new(3.2e21).to_string(), "3200.0 E/s");
+        assert_eq!(SiRate::new(1000.0).binary().to_string(), "1000.0/s");
+        assert_eq!(SiRate::new(1536.0 * 1024.0).binary().precision(3).to_string(), "1.500 Mi/s");
+
+        let measurement = Measurement { value: 8000, duration: Duration::from_secs(4) };
+        assert_eq!(measurement.si_rate().to_string(), "2.0 k/s");
+    }
+}
@@ -0,0 +1,93 @@
+//! Windowed latency distributions with one HDR histogram per bucket (requires `hdrhistogram` feature).
+//!
+//! `hdrhistogram::Histogram` implements `Accumulate` so it can be used as running average value: values are recorded into histogram of the current bucket
+//! and histograms of all buckets are added together on measurement giving distribution of values inserted within the time window with precision guarantees of HDR histogram.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use hdrhistogram::Histogram;
+//!
+//! let mut latency = RealTimeRunningAverage::<Histogram<u64>>::new(Duration::from_secs(60));
+//!
+//! // Record latencies in microseconds
+//! for us in 1..=1000 {
+//!     latency.accumulate(us);
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! let histogram = latency.measurement().unwrap();
+//! assert_eq!(histogram.len(), 1000);
+//! assert_eq!(histogram.value_at_quantile(0.99), 990);
+//! ```
+
+use ::hdrhistogram::{Counter, Histogram};
+
+use super::Accumulate;
+
+fn empty<T: Counter>() -> Histogram<T> {
+    Histogram::new(3).expect("hdrhistogram with 3 significant figures")
+}
+
+/// Empty bucket is auto-resizing histogram with 3 significant figures; use `RunningAverage::with_capacity_and_init()` for other configurations.
+/// Values that cannot be recorded are clamped to the range of the histogram.
+/// Panics on measurement if histograms of buckets cannot be added together (e.g. histogram of other bucket holds values outside of range of histograms not auto-resizing).
+impl<T: Counter> Accumulate<u64> for Histogram<T> {
+    fn zero() -> Histogram<T> {
+        empty()
+    }
+
+    fn add(&mut self, val: u64) {
+        if self.record(val).is_err() {
+            self.saturating_record(val)
+        }
+    }
+
+    fn merge(&self, other: &Histogram<T>) -> Histogram<T> {
+        let mut sum = self.clone();
+        sum.add(other).expect("hdrhistogram bucket cannot hold values of other bucket");
+        sum
+    }
+}
+
+impl<T: Counter> Accumulate for Histogram<T> {
+    fn zero() -> Histogram<T> {
+        empty()
+    }
+
+    fn add(&mut self, histogram: Histogram<T>) {
+        Histogram::add(self, histogram).expect("hdrhistogram bucket cannot hold values of inserted histogram")
+    }
+
+    fn merge(&self, other: &Histogram<T>) -> Histogram<T> {
+        Accumulate::<u64>::merge(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn windowed_distribution() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{ManualTimeSource, RealTimeRunningAverage};
+
+        let mut tw = RealTimeRunningAverage::<Histogram<u64>, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert!(tw.measurement().unwrap().is_empty());
+
+        for val in 1..=1000 {
+            tw.accumulate(val);
+        }
+        tw.time_source().time_shift(2.0);
+        tw.accumulate(1_000_000);
+
+        let histogram = tw.measurement().unwrap();
+        assert_eq!(histogram.len(), 1001);
+        assert_eq!(histogram.value_at_quantile(0.5), 501);
+        assert!(histogram.equivalent(histogram.max(), 1_000_000));
+
+        tw.time_source().time_shift(2.0);
+        let histogram = tw.measurement().unwrap();
+        assert_eq!(histogram.len(), 1);
+        assert_eq!(histogram.min(), histogram.lowest_equivalent(1_000_000));
+    }
+}
@@ -0,0 +1,154 @@
+//! Approximate windowed distinct count with one HyperLogLog sketch per bucket (requires `hll` feature).
+//!
+//! `HyperLogLog` implements `Accumulate` for any hashable value so it can be used as running average value: elements are added to sketch of the current bucket
+//! and sketches of all buckets are merged on measurement giving approximate number of distinct elements seen within the time window (e.g. unique clients per minute).
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::hll::HyperLogLog;
+//!
+//! let mut clients = RealTimeRunningAverage::<HyperLogLog>::new(Duration::from_secs(60));
+//!
+//! for request in 0..10_000 {
+//!     clients.accumulate(format!("10.0.{}.{}", request % 100 / 10, request % 10));
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! assert!((clients.measurement().unwrap().count() - 100.0).abs() < 3.0);
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::Accumulate;
+
+/// HyperLogLog sketch with 2^P registers; relative standard error of the count is about `1.04 / sqrt(2^P)` (1.6% for default P of 12).
+/// Registers are allocated on first element added so that empty sketch takes no memory.
+/// P has to be between 4 and 16.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HyperLogLog<const P: u8 = 12> {
+    registers: Vec<u8>,
+}
+
+impl<const P: u8> HyperLogLog<P> {
+    /// Crate new empty sketch.
+    pub fn new() -> HyperLogLog<P> {
+        HyperLogLog {
+            registers: Vec::new(),
+        }
+    }
+
+    /// Add element to the sketch.
+    /// Panics if P is not between 4 and 16.
+    pub fn insert<T: Hash + ?Sized>(&mut self, element: &T) {
+        assert!((4..=16).contains(&P), "HyperLogLog precision has to be between 4 and 16");
+        if self.registers.is_empty() {
+            self.registers = vec![0; 1 << P];
+        }
+
+        let mut hasher = DefaultHasher::new();
+        element.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let index = (hash >> (64 - P)) as usize;
+        let rank = ((hash << P) | (1 << (P - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Returns estimated number of distinct elements added.
+    pub fn count(&self) -> f64 {
+        if self.registers.is_empty() {
+            return 0.0
+        }
+
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let estimate = alpha * m * m / self.registers.iter().map(|&rank| 2f64.powi(-(rank as i32))).sum::<f64>();
+
+        // Use linear counting for small cardinalities
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+
+    /// Returns true if no elements were added.
+    pub fn is_empty(&self) -> bool {
+        self.registers.iter().all(|&rank| rank == 0)
+    }
+}
+
+impl<T: Hash, const P: u8> Accumulate<T> for HyperLogLog<P> {
+    fn zero() -> HyperLogLog<P> {
+        HyperLogLog::new()
+    }
+
+    fn add(&mut self, element: T) {
+        self.insert(&element)
+    }
+
+    fn merge(&self, other: &HyperLogLog<P>) -> HyperLogLog<P> {
+        if self.registers.is_empty() {
+            return other.clone()
+        }
+        let mut union = self.clone();
+        union.registers.iter_mut().zip(other.registers.iter()).for_each(|(union, &rank)| *union = (*union).max(rank));
+        union
+    }
+}
+
+impl<const P: u8> Accumulate for HyperLogLog<P> {
+    fn zero() -> HyperLogLog<P> {
+        HyperLogLog::new()
+    }
+
+    fn add(&mut self, other: HyperLogLog<P>) {
+        *self = Accumulate::<HyperLogLog<P>>::merge(self, &other)
+    }
+
+    fn merge(&self, other: &HyperLogLog<P>) -> HyperLogLog<P> {
+        Accumulate::<u8>::merge(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn windowed_distinct_count() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{ManualTimeSource, RealTimeRunningAverage};
+
+        let mut tw = RealTimeRunningAverage::<HyperLogLog, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.measurement().unwrap().count(), 0.0);
+
+        for element in 0..50_000u32 {
+            tw.accumulate(element);
+        }
+        tw.time_source().time_shift(2.0);
+        for element in 25_000..60_000u32 {
+            tw.accumulate(element);
+        }
+
+        let count = tw.measurement().unwrap().count();
+        assert!((count - 60_000.0).abs() < 60_000.0 * 0.05, "{}", count);
+
+        tw.time_source().time_shift(2.0);
+        let count = tw.measurement().unwrap().count();
+        assert!((count - 35_000.0).abs() < 35_000.0 * 0.05, "{}", count);
+
+        let mut small = HyperLogLog::<4>::new();
+        assert!(small.is_empty());
+        small.insert("a");
+        small.insert("b");
+        small.insert("a");
+        assert_eq!(small.count().round(), 2.0);
+    }
+}
@@ -0,0 +1,131 @@
+//! Construction of running averages from human readable window widths like `"8s"`, `"5min"` or `"1h30m"` (requires `humantime` feature).
+//!
+//! Builder can also be deserialized with `serde` from configuration where `duration` is human readable window width,
+//! `capacity` number of buckets, `name` name and `labels` table of key/value labels; all fields are optional.
+//! ```
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::labels::Builder;
+//!
+//! let mut tw = RealTimeRunningAverage::<u32>::from_humantime("1h30m").unwrap();
+//! tw.insert(5400);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tw.measurement().rate(), 1.0);
+//!
+//! let builder: Builder<u32> = serde_json::from_str(r#"{"duration": "5min", "capacity": 10, "name": "egress"}"#).unwrap();
+//! assert_eq!(builder.build().labels().name(), Some("egress"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ::humantime::{parse_duration, DurationError};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+
+use super::{Accumulate, RealTimeRunningAverage, RealTimeSource, RunningAverage, TimeInstant, TimeSource};
+use super::labels::Builder;
+
+impl<V: Accumulate, I: TimeInstant + Copy> RunningAverage<V, I> {
+    /// Crate new instance with window of width given as human readable duration (e.g. `"8s"`) and 16 buckets.
+    pub fn from_humantime(duration: &str) -> Result<RunningAverage<V, I>, DurationError> {
+        Ok(RunningAverage::new(parse_duration(duration)?))
+    }
+}
+
+impl<V: Accumulate> RealTimeRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of width given as human readable duration (e.g. `"8s"`) and 16 buckets using RealTimeSource as time source for `now` instant.
+    pub fn from_humantime(duration: &str) -> Result<RealTimeRunningAverage<V, RealTimeSource>, DurationError> {
+        Ok(RealTimeRunningAverage::new(parse_duration(duration)?))
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Builder<V, TS> {
+    /// Set width of the time window given as human readable duration (e.g. `"8s"`).
+    pub fn humantime_duration(self, duration: &str) -> Result<Builder<V, TS>, DurationError> {
+        Ok(self.duration(parse_duration(duration)?))
+    }
+}
+
+/// Builder configuration as read from config files.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BuilderConfig {
+    duration: Option<String>,
+    capacity: Option<usize>,
+    name: Option<String>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+impl<'de, V: Accumulate> Deserialize<'de> for Builder<V, RealTimeSource> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Builder<V, RealTimeSource>, D::Error> {
+        let config = BuilderConfig::deserialize(deserializer)?;
+        let mut builder = RealTimeRunningAverage::builder();
+
+        if let Some(duration) = config.duration {
+            builder = builder.humantime_duration(&duration).map_err(|err| D::Error::custom(InvalidDuration(duration, err)))?;
+        }
+        if let Some(capacity) = config.capacity {
+            builder = builder.capacity(capacity);
+        }
+        if let Some(name) = config.name {
+            builder = builder.name(&name);
+        }
+        for (key, value) in config.labels {
+            builder = builder.label(&key, &value);
+        }
+
+        Ok(builder)
+    }
+}
+
+struct InvalidDuration(String, DurationError);
+
+impl fmt::Display for InvalidDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid duration {:?}: {}", self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_window_width() {
+        use super::*;
+        use std::time::Duration;
+
+        let mut tw = RunningAverage::<u32, f64>::from_humantime("8s").unwrap();
+        tw.insert(0.0, 80);
+        assert_eq!(tw.measurement(0.0).rate(), 10.0);
+        assert_eq!(tw.measurement(0.0).duration(), Duration::from_secs(8));
+
+        let mut tw = RealTimeRunningAverage::<u32>::from_humantime("5min").unwrap();
+        assert_eq!(tw.measurement().duration(), Duration::from_secs(300));
+
+        assert!(RunningAverage::<u32, f64>::from_humantime("8 parsecs").is_err());
+        assert!(RealTimeRunningAverage::<u32>::builder().humantime_duration("").is_err());
+    }
+
+    #[test]
+    fn deserialize_builder() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::ManualTimeSource;
+
+        let builder: Builder<u32> = serde_json::from_str(r#"{"duration": "1h30m", "capacity": 90, "name": "egress", "labels": {"zone": "a", "iface": "eth0"}}"#).unwrap();
+        let mut tw = builder.time_source(ManualTimeSource::new()).build();
+        tw.insert(5400);
+        assert_eq!(tw.measurement().rate(), 1.0);
+        assert_eq!(tw.measurement().duration(), Duration::from_secs(5400));
+        assert_eq!(tw.labels().name(), Some("egress"));
+        assert_eq!(tw.labels().pairs(), vec![("iface", "eth0"), ("zone", "a")]);
+
+        let builder: Builder<u32> = serde_json::from_str("{}").unwrap();
+        assert_eq!(builder.build().measurement().duration(), Duration::from_secs(8));
+
+        let err = serde_json::from_str::<Builder<u32>>(r#"{"duration": "8 parsecs"}"#).unwrap_err();
+        assert!(err.to_string().starts_with("invalid duration \"8 parsecs\""));
+        assert!(serde_json::from_str::<Builder<u32>>(r#"{"width": "8s"}"#).is_err());
+    }
+}
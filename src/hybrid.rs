@@ -0,0 +1,168 @@
+//! Time window keeping exact samples for the most recent bucket span and bucketed sums for older values.
+//!
+//! `HybridWindow` stores values inserted within the last bucket span individually with their timestamps and accumulates them into buckets only once they get older,
+//! so short term rates over up to one bucket span are exact while memory usage for the bulk of the window stays constant.
+//! ```
+//! use std::time::Duration;
+//! use running_average::hybrid::HybridWindow;
+//!
+//! let mut tw = HybridWindow::new(Duration::from_secs(8));
+//!
+//! tw.insert(2000);
+//! tw.insert(1000);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tw.measurement().unwrap(), 3000);
+//! assert_eq!(tw.recent_measurement(Duration::from_millis(100)).unwrap(), 3000);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeSource, RunningAverage, TimeInstant, TimeSource};
+
+/// Time window with exact samples for the most recent bucket span using given time source to obtain value of `now` instant.
+pub struct HybridWindow<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    buckets: RunningAverage<V, TS::Instant>,
+    recent: VecDeque<(TS::Instant, V)>,
+    time_source: TS,
+}
+
+impl<V: Accumulate + fmt::Debug, TS: TimeSource> fmt::Debug for HybridWindow<V, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HybridWindow")
+            .field("buckets", &self.buckets)
+            .field("recent", &self.recent)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<V: Accumulate> Default for HybridWindow<V, RealTimeSource> {
+    /// Crate new HybridWindow instance with window of 8 seconds width and 16 buckets.
+    fn default() -> HybridWindow<V, RealTimeSource> {
+        HybridWindow::new(Duration::from_secs(8))
+    }
+}
+
+impl<V: Accumulate> HybridWindow<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and 16 buckets using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> HybridWindow<V, RealTimeSource> {
+        HybridWindow::with_time_source(duration, 16, RealTimeSource)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> HybridWindow<V, TS> {
+    /// Crate new instance with window of given width duration and number of buckets using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> HybridWindow<V, TS> {
+        HybridWindow {
+            buckets: RunningAverage::with_capacity(duration, capacity),
+            recent: VecDeque::new(),
+            time_source,
+        }
+    }
+
+    /// Accumulate samples older than one bucket span into buckets.
+    fn settle(&mut self, now: TS::Instant) {
+        let slot_duration = self.buckets.bucket_duration();
+
+        while let Some((time, _)) = self.recent.front() {
+            if now.duration_since(*time) < slot_duration {
+                break
+            }
+            let (time, val) = self.recent.pop_front().unwrap();
+            self.buckets.insert(time, val);
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        let now = self.time_source.now();
+        self.settle(now);
+        self.recent.push_back((now, val));
+    }
+
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        let now = self.time_source.now();
+        self.settle(now);
+
+        let buckets = self.buckets.peek(now);
+        Measurement {
+            value: self.recent.iter().fold(buckets.value, |sum, (_, val)| sum.merge(val)),
+            duration: buckets.duration,
+        }
+    }
+
+    /// Calculate exact running average using time window of given width, up to one bucket span, ending now.
+    /// Panics if time source time goes backwards or if given duration is longer than bucket span.
+    pub fn recent_measurement(&mut self, duration: Duration) -> Measurement<V> {
+        assert!(duration <= self.buckets.bucket_duration(), "HybridWindow recent measurement cannot be longer than bucket span");
+        let now = self.time_source.now();
+        self.settle(now);
+
+        Measurement {
+            value: self.recent.iter()
+                .filter(|(time, _)| now.duration_since(*time) < duration)
+                .fold(V::zero(), |sum, (_, val)| sum.merge(val)),
+            duration,
+        }
+    }
+
+    /// Returns number of samples stored individually.
+    pub fn recent_samples(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Returns width of the time span covered by single bucket which is also the time span of samples stored individually.
+    pub fn bucket_duration(&self) -> Duration {
+        self.buckets.bucket_duration()
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for HybridWindow<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        HybridWindow::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn exact_recent_bucketed_old() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = HybridWindow::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.insert(10);
+        tw.time_source().time_shift(0.5);
+        tw.insert(20);
+        tw.time_source().time_shift(0.25);
+        tw.insert(40);
+        assert_eq!(tw.recent_samples(), 3);
+        assert_eq!(tw.measurement().unwrap(), 70);
+
+        assert_eq!(tw.recent_measurement(Duration::from_millis(100)).unwrap(), 40);
+        assert_eq!(tw.recent_measurement(Duration::from_millis(500)).unwrap(), 60);
+        assert_eq!(tw.recent_measurement(Duration::from_secs(1)).to_rate(), 70.0);
+
+        tw.time_source().time_shift(0.5);
+        assert_eq!(tw.recent_measurement(Duration::from_secs(1)).unwrap(), 60);
+        assert_eq!(tw.recent_samples(), 2);
+        assert_eq!(tw.measurement().unwrap(), 70);
+
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.measurement().unwrap(), 0);
+    }
+}
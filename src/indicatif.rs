@@ -0,0 +1,154 @@
+//! Progress bar rate and ETA estimated with running average for `indicatif` (requires `indicatif` feature).
+//!
+//! `with_windowed_rate()` adds `{windowed_bytes_per_sec}`, `{windowed_per_sec}` and `{windowed_eta}` template keys to progress style
+//! that are calculated from position changes averaged over time window of given width instead of indicatif's internal estimator.
+//! ```
+//! use std::time::Duration;
+//! use indicatif::{ProgressBar, ProgressStyle};
+//! use running_average::indicatif::with_windowed_rate;
+//!
+//! let style = ProgressStyle::with_template("{bar} {bytes}/{total_bytes} {windowed_bytes_per_sec} {windowed_eta}").unwrap();
+//! let bar = ProgressBar::new(10_000_000).with_style(with_windowed_rate(style, Duration::from_secs(4)));
+//!
+//! // Got 2KB of data
+//! bar.inc(2000);
+//! # bar.finish_and_clear();
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use ::indicatif::{HumanBytes, HumanDuration, ProgressState, ProgressStyle};
+use ::indicatif::style::ProgressTracker;
+
+use super::RunningAverage;
+
+/// What `WindowedRate` progress tracker displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateDisplay {
+    /// Rate as human readable bytes per second.
+    BytesPerSec,
+    /// Rate as number of steps per second.
+    PerSec,
+    /// Estimated time remaining as human readable duration.
+    Eta,
+}
+
+/// Progress tracker that measures rate of change of progress bar position with running average.
+#[derive(Debug)]
+pub struct WindowedRate {
+    window: RunningAverage<f64, Instant>,
+    capacity: usize,
+    display: RateDisplay,
+    pos: Option<u64>,
+    rate: f64,
+}
+
+impl WindowedRate {
+    /// Create new tracker averaging over window of width of given duration using 16 buckets.
+    pub fn new(duration: Duration, display: RateDisplay) -> WindowedRate {
+        WindowedRate::with_capacity(duration, 16, display)
+    }
+
+    /// Create new tracker averaging over window of width of given duration with specific number of buckets to use.
+    pub fn with_capacity(duration: Duration, capacity: usize, display: RateDisplay) -> WindowedRate {
+        WindowedRate {
+            window: RunningAverage::with_capacity(duration, capacity),
+            capacity,
+            display,
+            pos: None,
+            rate: 0.0,
+        }
+    }
+
+    /// Returns rate of position change measured on last tick.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    fn observe(&mut self, pos: u64, now: Instant) {
+        if let Some(last) = self.pos {
+            if pos > last {
+                self.window.insert(now, (pos - last) as f64);
+            }
+        }
+        self.pos = Some(pos);
+        self.rate = self.window.measurement(now).rate();
+    }
+
+    fn eta(&self, pos: u64, len: Option<u64>) -> Option<Duration> {
+        let left = len?.saturating_sub(pos);
+        if left == 0 {
+            Some(Duration::from_secs(0))
+        } else if self.rate > 0.0 {
+            // Saturate if rate is too low for remaining time to be represented
+            Some(Duration::try_from_secs_f64(left as f64 / self.rate).unwrap_or(Duration::MAX))
+        } else {
+            None
+        }
+    }
+}
+
+impl ProgressTracker for WindowedRate {
+    fn clone_box(&self) -> Box<dyn ProgressTracker> {
+        Box::new(WindowedRate::with_capacity(self.window.duration, self.capacity, self.display))
+    }
+
+    fn tick(&mut self, state: &ProgressState, now: Instant) {
+        self.observe(state.pos(), now)
+    }
+
+    fn reset(&mut self, state: &ProgressState, _now: Instant) {
+        *self = WindowedRate::with_capacity(self.window.duration, self.capacity, self.display);
+        self.pos = Some(state.pos());
+    }
+
+    fn write(&self, state: &ProgressState, w: &mut dyn fmt::Write) {
+        let _ = match self.display {
+            RateDisplay::BytesPerSec => write!(w, "{}/s", HumanBytes(self.rate as u64)),
+            RateDisplay::PerSec => write!(w, "{:.2}/s", self.rate),
+            RateDisplay::Eta => match self.eta(state.pos(), state.len()) {
+                Some(eta) => write!(w, "{:#}", HumanDuration(eta)),
+                None => w.write_str("?"),
+            },
+        };
+    }
+}
+
+/// Add `{windowed_bytes_per_sec}`, `{windowed_per_sec}` and `{windowed_eta}` template keys to given progress style
+/// with values calculated from running average over time window of given width.
+pub fn with_windowed_rate(style: ProgressStyle, duration: Duration) -> ProgressStyle {
+    style
+        .with_key("windowed_bytes_per_sec", WindowedRate::new(duration, RateDisplay::BytesPerSec))
+        .with_key("windowed_per_sec", WindowedRate::new(duration, RateDisplay::PerSec))
+        .with_key("windowed_eta", WindowedRate::new(duration, RateDisplay::Eta))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn observe_position_changes() {
+        use super::*;
+
+        let start = Instant::now();
+        let mut tracker = WindowedRate::with_capacity(Duration::from_secs(4), 4, RateDisplay::BytesPerSec);
+
+        tracker.observe(1000, start);
+        assert_eq!(tracker.rate(), 0.0);
+        assert_eq!(tracker.eta(1000, Some(2000)), None);
+
+        tracker.observe(1100, start + Duration::from_secs(1));
+        tracker.observe(1400, start + Duration::from_secs(2));
+        assert_eq!(tracker.rate(), 100.0);
+        assert_eq!(tracker.eta(1400, Some(2000)), Some(Duration::from_secs(6)));
+        assert_eq!(tracker.eta(1400, None), None);
+
+        tracker.observe(1400, start + Duration::from_secs(6));
+        assert_eq!(tracker.rate(), 0.0);
+
+        // Slow transfer of a very large total
+        tracker.observe(1401, start + Duration::from_secs(7));
+        assert_eq!(tracker.eta(1401, Some(u64::MAX)), Some(Duration::MAX));
+        assert!(!format!("{:#}", HumanDuration(Duration::MAX)).is_empty());
+    }
+}
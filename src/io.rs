@@ -0,0 +1,163 @@
+//! I/O wrappers inserting number of transferred bytes into running average.
+//!
+//! ```
+//! use std::io::Write;
+//! use running_average::io::MeasuredWriter;
+//! use running_average::shared::SharedRunningAverage;
+//!
+//! let egress = SharedRunningAverage::<u64>::default();
+//!
+//! let mut writer = MeasuredWriter::new(Vec::new(), egress.clone());
+//! writer.write_all(b"hello world").unwrap();
+//!
+//! assert_eq!(egress.measurement().unwrap(), 11);
+//! ```
+
+use std::io::{self, IoSlice, Write};
+
+use super::Insert;
+
+/// Insert count that may not fit in u32 breaking it into multiple inserts if needed.
+pub(crate) fn insert_count<R: Insert + ?Sized>(recorder: &mut R, mut count: u64) where R::Value: From<u32> {
+    while count > u64::from(u32::MAX) {
+        recorder.insert(From::from(u32::MAX));
+        count -= u64::from(u32::MAX);
+    }
+    recorder.insert(From::from(count as u32));
+}
+
+/// Writer wrapper that inserts number of bytes written into running average and optionally inserts 1 on each successful flush into another.
+#[derive(Debug)]
+pub struct MeasuredWriter<W, R, FR = R> {
+    writer: W,
+    bytes: R,
+    flushes: Option<FR>,
+}
+
+impl<W: Write, R> MeasuredWriter<W, R, R> {
+    /// Wrap given writer inserting number of bytes written into given running average.
+    pub fn new(writer: W, bytes: R) -> MeasuredWriter<W, R, R> {
+        MeasuredWriter {
+            writer,
+            bytes,
+            flushes: None,
+        }
+    }
+}
+
+impl<W: Write, R, FR> MeasuredWriter<W, R, FR> {
+    /// Wrap given writer inserting number of bytes written into given running average and 1 for each successful flush into another.
+    pub fn with_flushes(writer: W, bytes: R, flushes: FR) -> MeasuredWriter<W, R, FR> {
+        MeasuredWriter {
+            writer,
+            bytes,
+            flushes: Some(flushes),
+        }
+    }
+
+    /// Return reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Return mutable reference to the underlying writer.
+    /// Note: Bytes written directly to the underlying writer are not measured.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Return reference to running average bytes written are inserted into.
+    pub fn bytes(&self) -> &R {
+        &self.bytes
+    }
+
+    /// Return reference to running average flushes are inserted into if any.
+    pub fn flushes(&self) -> Option<&FR> {
+        self.flushes.as_ref()
+    }
+
+    /// Return the underlying writer consuming self.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W, R, FR> Write for MeasuredWriter<W, R, FR> where W: Write, R: Insert, R::Value: From<u32>, FR: Insert, FR::Value: From<u8> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        if written > 0 {
+            insert_count(&mut self.bytes, written as u64);
+        }
+        Ok(written)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let written = self.writer.write_vectored(bufs)?;
+        if written > 0 {
+            insert_count(&mut self.bytes, written as u64);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        if let Some(flushes) = self.flushes.as_mut() {
+            flushes.insert(From::from(1));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn measure_written_bytes_and_flushes() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{RealTimeRunningAverage, ManualTimeSource};
+
+        let mut bytes = RealTimeRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let mut flushes = RealTimeRunningAverage::<u8, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        {
+            let mut writer = MeasuredWriter::with_flushes(Vec::new(), &mut bytes, &mut flushes);
+            writer.write_all(b"hello").unwrap();
+            writer.flush().unwrap();
+            write!(writer, "{} world", 42).unwrap();
+            writer.flush().unwrap();
+            assert_eq!(writer.get_ref(), b"hello42 world");
+        }
+
+        assert_eq!(bytes.measurement().unwrap(), 13);
+        assert_eq!(flushes.measurement().unwrap(), 2);
+    }
+
+    #[test]
+    fn failed_writes_are_not_measured() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{RealTimeRunningAverage, ManualTimeSource};
+
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken pipe"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::Error::other("broken pipe"))
+            }
+        }
+
+        let mut bytes = RealTimeRunningAverage::<f64, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        {
+            let mut writer = MeasuredWriter::new(Failing, &mut bytes);
+            assert!(writer.write(b"hello").is_err());
+            assert!(writer.flush().is_err());
+        }
+
+        assert_eq!(bytes.measurement().unwrap(), 0.0);
+    }
+}
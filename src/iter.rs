@@ -0,0 +1,98 @@
+//! Iterator adapter inserting weight of each yielded item into running average.
+//!
+//! ```
+//! use running_average::iter::MeasureRateExt;
+//! use running_average::shared::SharedRunningAverage;
+//!
+//! let items_rate = SharedRunningAverage::<u32>::default();
+//! let bytes_rate = SharedRunningAverage::<u32>::default();
+//!
+//! let lines = vec!["foo", "bar", "baz"];
+//! let processed = lines.into_iter()
+//!     .measure_rate(&items_rate)
+//!     .measure_rate_by(&bytes_rate, |line| line.len() as u32)
+//!     .count();
+//!
+//! assert_eq!(processed, 3);
+//! assert_eq!(items_rate.measurement().unwrap(), 3);
+//! assert_eq!(bytes_rate.measurement().unwrap(), 9);
+//! ```
+
+use super::Insert;
+
+/// Weight function used by `MeasureRateExt::measure_rate()` counting each item as 1.
+pub type CountItem<T, V> = fn(&T) -> V;
+
+/// Extension trait adding running average instrumentation to all iterators.
+pub trait MeasureRateExt: Iterator + Sized {
+    /// Insert 1 into given running average for each item yielded.
+    fn measure_rate<R>(self, recorder: R) -> MeasureRate<Self, R, CountItem<Self::Item, R::Value>> where R: Insert, R::Value: From<u8> {
+        MeasureRate {
+            iter: self,
+            recorder,
+            weight: |_| From::from(1),
+        }
+    }
+
+    /// Insert weight calculated by given function from each yielded item into given running average.
+    fn measure_rate_by<R, F>(self, recorder: R, weight: F) -> MeasureRate<Self, R, F> where R: Insert, F: FnMut(&Self::Item) -> R::Value {
+        MeasureRate {
+            iter: self,
+            recorder,
+            weight,
+        }
+    }
+}
+
+impl<I: Iterator> MeasureRateExt for I {}
+
+/// Iterator inserting weight of each yielded item into running average; created with `MeasureRateExt` methods.
+#[derive(Debug, Clone)]
+pub struct MeasureRate<I, R, F> {
+    iter: I,
+    recorder: R,
+    weight: F,
+}
+
+impl<I, R, F> MeasureRate<I, R, F> {
+    /// Return the underlying iterator and running average consuming self.
+    pub fn into_inner(self) -> (I, R) {
+        (self.iter, self.recorder)
+    }
+}
+
+impl<I, R, F> Iterator for MeasureRate<I, R, F> where I: Iterator, R: Insert, F: FnMut(&I::Item) -> R::Value {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.iter.next()?;
+        self.recorder.insert((self.weight)(&item));
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn inserts_as_items_are_yielded() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{RealTimeRunningAverage, ManualTimeSource};
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        {
+            let mut items = (1..5).measure_rate_by(&mut tw, |item| *item * 10);
+            assert_eq!(items.next(), Some(1));
+            assert_eq!(items.next(), Some(2));
+        }
+        assert_eq!(tw.measurement().unwrap(), 30);
+
+        assert_eq!((0..3).measure_rate(&mut tw).last(), Some(2));
+        assert_eq!(tw.measurement().unwrap(), 33);
+    }
+}
@@ -0,0 +1,360 @@
+//! Exact per-key windowed rates with eviction of idle keys.
+//!
+//! `KeyedRunningAverage` keeps one running average per key (e.g. tenant or client identifier) and evicts keys that saw no inserts for given idle timeout,
+//! counting keys added and evicted over the time window and optionally notifying about evicted keys so that key-space churn can be monitored.
+//! Number of keys can also be capped evicting least recently inserted key first so that memory stays bounded when keys come from untrusted clients.
+//! ```
+//! use std::time::Duration;
+//! use running_average::keyed::KeyedRunningAverage;
+//!
+//! let mut tenants = KeyedRunningAverage::new(Duration::from_secs(8))
+//!     .max_keys(10_000)
+//!     .on_evict(|tenant: &&str, last| println!("{} went idle after {}", tenant, last));
+//!
+//! tenants.insert("acme", 2000);
+//! tenants.insert("globex", 1000);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(tenants.measurement(&"acme").unwrap().unwrap(), 2000);
+//! assert_eq!(*tenants.churn().added().value(), 2.0);
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::time::Duration;
+
+use super::{bucket_capacity, slot_duration, Accumulate, Measurement, RealTimeSource, RunningAverage, TimeInstant, TimeSource};
+
+struct Entry<V: Accumulate, I: TimeInstant + Copy> {
+    window: RunningAverage<V, I>,
+    last_insert: I,
+    /// Position of the key in order of inserts.
+    seq: u64,
+}
+
+type OnEvict<K, V> = Box<dyn FnMut(&K, Measurement<V>) + Send>;
+
+/// Keys added and evicted over the time window and since creation.
+#[derive(Debug, Clone)]
+pub struct Churn {
+    added: Measurement<f64>,
+    evicted: Measurement<f64>,
+    added_total: u64,
+    evicted_total: u64,
+    lru_evicted_total: u64,
+}
+
+impl Churn {
+    /// Returns measurement of number of keys added within the time window.
+    pub fn added(&self) -> &Measurement<f64> {
+        &self.added
+    }
+
+    /// Returns measurement of number of keys evicted within the time window.
+    pub fn evicted(&self) -> &Measurement<f64> {
+        &self.evicted
+    }
+
+    /// Returns number of keys added since creation.
+    pub fn added_total(&self) -> u64 {
+        self.added_total
+    }
+
+    /// Returns number of keys evicted since creation.
+    pub fn evicted_total(&self) -> u64 {
+        self.evicted_total
+    }
+
+    /// Returns number of keys evicted since creation to keep number of keys within the limit; included in `evicted_total()`.
+    pub fn lru_evicted_total(&self) -> u64 {
+        self.lru_evicted_total
+    }
+}
+
+/// Running averages per key evicted after given idle timeout using given time source to obtain value of `now` instant.
+pub struct KeyedRunningAverage<K: Hash + Eq, V: Accumulate, TS: TimeSource = RealTimeSource> {
+    windows: HashMap<K, Entry<V, TS::Instant>>,
+    /// Keys by position in order of inserts, least recently inserted first.
+    order: BTreeMap<u64, K>,
+    seq: u64,
+    max_keys: Option<usize>,
+    duration: Duration,
+    capacity: usize,
+    idle_timeout: Duration,
+    last_sweep: Option<TS::Instant>,
+    added: RunningAverage<f64, TS::Instant>,
+    evicted: RunningAverage<f64, TS::Instant>,
+    added_total: u64,
+    evicted_total: u64,
+    lru_evicted_total: u64,
+    on_evict: Option<OnEvict<K, V>>,
+    time_source: TS,
+}
+
+impl<K: Hash + Eq, V: Accumulate, TS: TimeSource> fmt::Debug for KeyedRunningAverage<K, V, TS> where K: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyedRunningAverage")
+            .field("keys", &self.windows.keys().collect::<Vec<_>>())
+            .field("duration", &self.duration)
+            .field("capacity", &self.capacity)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_keys", &self.max_keys)
+            .field("added_total", &self.added_total)
+            .field("evicted_total", &self.evicted_total)
+            .field("lru_evicted_total", &self.lru_evicted_total)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq, V: Accumulate> Default for KeyedRunningAverage<K, V, RealTimeSource> {
+    /// Crate new KeyedRunningAverage instance with windows of 8 seconds width and 16 buckets.
+    fn default() -> KeyedRunningAverage<K, V, RealTimeSource> {
+        KeyedRunningAverage::new(Duration::from_secs(8))
+    }
+}
+
+impl<K: Hash + Eq, V: Accumulate> KeyedRunningAverage<K, V, RealTimeSource> {
+    /// Crate new instance with windows of given width duration and 16 buckets evicting keys idle for the window width, using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> KeyedRunningAverage<K, V, RealTimeSource> {
+        KeyedRunningAverage::with_time_source(duration, 16, duration, RealTimeSource)
+    }
+}
+
+impl<K: Hash + Eq, V: Accumulate, TS: TimeSource> KeyedRunningAverage<K, V, TS> {
+    /// Crate new instance with windows of given width duration and number of buckets evicting keys with no inserts for given idle timeout, using given as time source for `now` instant.
+    /// Note: key idle for the window width has measurement of zero value.
+    pub fn with_time_source(duration: Duration, capacity: usize, idle_timeout: Duration, time_source: TS) -> KeyedRunningAverage<K, V, TS> {
+        KeyedRunningAverage {
+            windows: HashMap::new(),
+            order: BTreeMap::new(),
+            seq: 0,
+            max_keys: None,
+            duration,
+            capacity: bucket_capacity(duration, capacity),
+            idle_timeout,
+            last_sweep: None,
+            added: RunningAverage::with_capacity(duration, capacity),
+            evicted: RunningAverage::with_capacity(duration, capacity),
+            added_total: 0,
+            evicted_total: 0,
+            lru_evicted_total: 0,
+            on_evict: None,
+            time_source,
+        }
+    }
+
+    /// Keep at most given number of keys evicting least recently inserted key when new key is inserted.
+    /// Panics if max keys is 0.
+    pub fn max_keys(mut self, max_keys: usize) -> KeyedRunningAverage<K, V, TS> {
+        assert!(max_keys > 0, "KeyedRunningAverage max keys cannot be 0");
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    /// Call given closure with key and last measurement of its window when it is evicted.
+    pub fn on_evict(mut self, on_evict: impl FnMut(&K, Measurement<V>) + Send + 'static) -> KeyedRunningAverage<K, V, TS> {
+        self.on_evict = Some(Box::new(on_evict));
+        self
+    }
+
+    /// Evict keys that had no inserts for the idle timeout.
+    /// Panics if time source time goes backwards.
+    pub fn evict_idle(&mut self) {
+        let now = self.time_source.now();
+        self.sweep(now);
+    }
+
+    fn sweep(&mut self, now: TS::Instant) {
+        self.last_sweep = Some(now);
+
+        // Least recently inserted keys are idle the longest
+        while let Some(seq) = self.order.keys().next().copied() {
+            let idle = self.order.get(&seq).and_then(|key| self.windows.get(key))
+                .map(|entry| now.duration_since(entry.last_insert) >= self.idle_timeout)
+                .unwrap_or(false);
+            if !idle {
+                break
+            }
+            self.evict(now, seq);
+        }
+    }
+
+    fn evict(&mut self, now: TS::Instant, seq: u64) {
+        let key = self.order.remove(&seq).expect("KeyedRunningAverage evicted key");
+        let mut entry = self.windows.remove(&key).expect("KeyedRunningAverage evicted key window");
+
+        self.evicted.insert(now, 1.0);
+        self.evicted_total += 1;
+        if let Some(on_evict) = self.on_evict.as_mut() {
+            on_evict(&key, entry.window.measurement(now));
+        }
+    }
+
+    /// Insert value to be average over now for given key; keys idle for the idle timeout are evicted at most once per bucket width
+    /// and least recently inserted key is evicted when new key would exceed max keys.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, key: K, val: V) where K: Clone {
+        let now = self.time_source.now();
+
+        let sweep = self.last_sweep.map(|last_sweep| now.duration_since(last_sweep) >= slot_duration(self.duration, self.capacity)).unwrap_or(true);
+        if sweep {
+            self.sweep(now);
+        }
+
+        let seq = self.seq;
+        self.seq += 1;
+
+        if let Some(entry) = self.windows.get_mut(&key) {
+            self.order.remove(&entry.seq);
+            self.order.insert(seq, key);
+            entry.seq = seq;
+            entry.last_insert = now;
+            entry.window.insert(now, val);
+            return
+        }
+
+        if let Some(max_keys) = self.max_keys {
+            while self.windows.len() >= max_keys {
+                let lru = *self.order.keys().next().expect("KeyedRunningAverage least recently inserted key");
+                self.evict(now, lru);
+                self.lru_evicted_total += 1;
+            }
+        }
+
+        self.added.insert(now, 1.0);
+        self.added_total += 1;
+
+        let mut window = RunningAverage::with_capacity(self.duration, self.capacity);
+        window.insert(now, val);
+        self.order.insert(seq, key.clone());
+        self.windows.insert(key, Entry {
+            window,
+            last_insert: now,
+            seq,
+        });
+    }
+
+    /// Calculate running average of given key using time window ending now; None if key is not tracked.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self, key: &K) -> Option<Measurement<V>> {
+        let now = self.time_source.now();
+        self.windows.get_mut(key).map(|entry| entry.window.measurement(now))
+    }
+
+    /// Evict idle keys and calculate running averages of all remaining keys using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurements(&mut self) -> Vec<(&K, Measurement<V>)> {
+        let now = self.time_source.now();
+        self.sweep(now);
+        self.windows.iter_mut().map(|(key, entry)| (key, entry.window.measurement(now))).collect()
+    }
+
+    /// Returns keys added and evicted within time window ending now and since creation.
+    /// Panics if time source time goes backwards.
+    pub fn churn(&mut self) -> Churn {
+        let now = self.time_source.now();
+        Churn {
+            added: self.added.measurement(now),
+            evicted: self.evicted.measurement(now),
+            added_total: self.added_total,
+            evicted_total: self.evicted_total,
+            lru_evicted_total: self.lru_evicted_total,
+        }
+    }
+
+    /// Returns number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Returns true if no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn evict_idle_keys() {
+        use super::*;
+        use std::sync::{Arc, Mutex};
+        use super::super::ManualTimeSource;
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let notified = evicted.clone();
+        let mut tw = KeyedRunningAverage::with_time_source(Duration::from_secs(4), 4, Duration::from_secs(2), ManualTimeSource::new())
+            .on_evict(move |key: &&str, last| notified.lock().unwrap().push((*key, *last.value())));
+
+        tw.insert("a", 10);
+        tw.insert("b", 20);
+        tw.insert("a", 10);
+        assert_eq!(tw.len(), 2);
+        assert_eq!(tw.measurement(&"a").unwrap().unwrap(), 20);
+        assert!(tw.measurement(&"c").is_none());
+
+        tw.time_source().time_shift(1.0);
+        tw.insert("b", 5);
+        tw.time_source().time_shift(1.0);
+        tw.insert("c", 1);
+
+        // Key a was idle for 2 seconds
+        assert_eq!(tw.len(), 2);
+        assert_eq!(*evicted.lock().unwrap(), vec![("a", 20)]);
+
+        tw.time_source().time_shift(2.0);
+        assert!(tw.measurements().is_empty());
+        assert!(tw.is_empty());
+
+        let churn = tw.churn();
+        assert_eq!(churn.added_total(), 3);
+        assert_eq!(churn.evicted_total(), 3);
+        assert_eq!(*churn.added().value(), 1.0);
+        assert_eq!(*churn.evicted().value(), 3.0);
+        evicted.lock().unwrap().sort();
+        assert_eq!(*evicted.lock().unwrap(), vec![("a", 20), ("b", 5), ("c", 1)]);
+    }
+
+    #[test]
+    fn evict_least_recently_inserted_keys() {
+        use super::*;
+        use std::sync::{Arc, Mutex};
+        use super::super::ManualTimeSource;
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let notified = evicted.clone();
+        let mut tw = KeyedRunningAverage::with_time_source(Duration::from_secs(4), 4, Duration::from_secs(60), ManualTimeSource::new())
+            .max_keys(2)
+            .on_evict(move |key: &u32, _| notified.lock().unwrap().push(*key));
+
+        tw.insert(1, 10);
+        tw.insert(2, 20);
+        tw.insert(1, 10);
+        assert_eq!(tw.len(), 2);
+
+        // Key 2 was inserted least recently
+        tw.insert(3, 30);
+        assert_eq!(tw.len(), 2);
+        assert!(tw.measurement(&2).is_none());
+        assert_eq!(tw.measurement(&1).unwrap().unwrap(), 20);
+
+        for key in 4..10 {
+            tw.insert(key, key);
+        }
+        assert_eq!(tw.len(), 2);
+        assert_eq!(tw.measurement(&9).unwrap().unwrap(), 9);
+        assert_eq!(*evicted.lock().unwrap(), vec![2, 1, 3, 4, 5, 6, 7]);
+
+        let churn = tw.churn();
+        assert_eq!(churn.added_total(), 9);
+        assert_eq!(churn.evicted_total(), 7);
+        assert_eq!(churn.lru_evicted_total(), 7);
+    }
+}
@@ -0,0 +1,250 @@
+//! Name and key/value labels identifying running averages in sinks and exporters.
+//!
+//! `RealTimeRunningAverage::builder()` configures window and time source together with name and labels
+//! that are carried by the running average and its `LabeledMeasurement` snapshots and used by labelled sinks and exporters.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//!
+//! let mut tw = RealTimeRunningAverage::<u32>::builder()
+//!     .duration(Duration::from_secs(4))
+//!     .name("egress")
+//!     .label("iface", "eth0")
+//!     .build();
+//!
+//! tw.insert(2000);
+//!
+//! assert_eq!(tw.labels().name(), Some("egress"));
+//! assert_eq!(tw.labels().pairs(), vec![("iface", "eth0")]);
+//!
+//! let measurement = tw.labeled_measurement();
+//! assert_eq!(measurement.labels().name(), Some("egress"));
+//! ```
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Accumulate, Measurement, RealTimeRunningAverage, RealTimeSource, RunningAverage, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Optional name and ordered key/value labels of a running average.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Labels {
+    name: Option<String>,
+    labels: Vec<(String, String)>,
+}
+
+impl Labels {
+    /// Create new Labels with no name and no labels.
+    pub fn new() -> Labels {
+        Labels::default()
+    }
+
+    /// Returns name if set.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Set name.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_owned());
+    }
+
+    /// Returns value of label with given key if set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.labels.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Set label with given key to given value replacing previous value while keeping its position.
+    pub fn insert(&mut self, key: &str, value: &str) {
+        match self.labels.iter_mut().find(|(k, _)| k == key) {
+            Some((_, old)) => *old = value.to_owned(),
+            None => self.labels.push((key.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Returns key/value pairs of labels in order they were first set.
+    pub fn pairs(&self) -> Vec<(&str, &str)> {
+        self.labels.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect()
+    }
+
+    /// Returns true if there is no name and no labels set.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.labels.is_empty()
+    }
+
+    /// Returns number of bytes of heap memory allocated for name and labels.
+    pub(crate) fn heap_footprint(&self) -> usize {
+        self.name.as_ref().map(String::capacity).unwrap_or_default()
+            + self.labels.capacity() * std::mem::size_of::<(String, String)>()
+            + self.labels.iter().map(|(key, value)| key.capacity() + value.capacity()).sum::<usize>()
+    }
+}
+
+/// Measurement together with name and labels of the running average it was taken from, so that it can be identified by sinks.
+/// Dereferences to the measurement.
+#[derive(Debug, Clone)]
+pub struct LabeledMeasurement<V> {
+    measurement: Measurement<V>,
+    labels: Arc<Labels>,
+}
+
+impl<V> LabeledMeasurement<V> {
+    /// Create new instance of given measurement with given name and labels.
+    pub fn new(measurement: Measurement<V>, labels: Arc<Labels>) -> LabeledMeasurement<V> {
+        LabeledMeasurement {
+            measurement,
+            labels,
+        }
+    }
+
+    /// Returns name and labels of the running average the measurement was taken from.
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    /// Returns the measurement.
+    pub fn measurement(&self) -> &Measurement<V> {
+        &self.measurement
+    }
+
+    /// Returns the measurement consuming self.
+    pub fn into_measurement(self) -> Measurement<V> {
+        self.measurement
+    }
+}
+
+impl<V> Deref for LabeledMeasurement<V> {
+    type Target = Measurement<V>;
+
+    fn deref(&self) -> &Measurement<V> {
+        &self.measurement
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> RealTimeRunningAverage<V, TS> {
+    /// Calculate running average using time window ending now together with name and labels of this running average.
+    /// Panics if time source time goes backwards.
+    pub fn labeled_measurement(&mut self) -> LabeledMeasurement<V> {
+        LabeledMeasurement::new(self.measurement(), self.labels.clone())
+    }
+}
+
+/// Builder of `RealTimeRunningAverage` and `SharedRunningAverage` with name and labels.
+#[derive(Debug)]
+pub struct Builder<V, TS: TimeSource = RealTimeSource> {
+    duration: Duration,
+    capacity: usize,
+    time_source: TS,
+    labels: Labels,
+    value: PhantomData<fn() -> V>,
+}
+
+impl<V: Accumulate> RealTimeRunningAverage<V, RealTimeSource> {
+    /// Create builder of running average with window of 8 seconds width, 16 buckets, RealTimeSource as time source and no labels.
+    pub fn builder() -> Builder<V, RealTimeSource> {
+        Builder {
+            duration: Duration::from_secs(8),
+            capacity: 16,
+            time_source: RealTimeSource,
+            labels: Labels::new(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Builder<V, TS> {
+    /// Set width of the time window.
+    pub fn duration(mut self, duration: Duration) -> Builder<V, TS> {
+        self.duration = duration;
+        self
+    }
+
+    /// Set number of buckets used.
+    pub fn capacity(mut self, capacity: usize) -> Builder<V, TS> {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set time source to be used for `now` instant.
+    pub fn time_source<NTS: TimeSource>(self, time_source: NTS) -> Builder<V, NTS> {
+        Builder {
+            duration: self.duration,
+            capacity: self.capacity,
+            time_source,
+            labels: self.labels,
+            value: PhantomData,
+        }
+    }
+
+    /// Set name.
+    pub fn name(mut self, name: &str) -> Builder<V, TS> {
+        self.labels.set_name(name);
+        self
+    }
+
+    /// Set label with given key to given value.
+    pub fn label(mut self, key: &str, value: &str) -> Builder<V, TS> {
+        self.labels.insert(key, value);
+        self
+    }
+
+    /// Build running average.
+    /// Panics if capacity is 0.
+    pub fn build(self) -> RealTimeRunningAverage<V, TS> {
+        RealTimeRunningAverage {
+            inner: RunningAverage::with_capacity(self.duration, self.capacity),
+            time_source: self.time_source,
+            labels: Arc::new(self.labels),
+        }
+    }
+
+    /// Build shared running average.
+    /// Panics if capacity is 0.
+    pub fn build_shared(self) -> SharedRunningAverage<V, TS> {
+        self.build().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn build_with_labels() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let shared = RealTimeRunningAverage::<u32, _>::builder()
+            .duration(Duration::from_secs(4))
+            .capacity(4)
+            .time_source(ManualTimeSource::new())
+            .name("egress")
+            .label("iface", "eth0")
+            .label("zone", "a")
+            .label("iface", "eth1")
+            .build_shared();
+
+        shared.insert(40);
+        assert_eq!(shared.measurement().rate(), 10.0);
+
+        let labels = shared.labels();
+        assert_eq!(labels.name(), Some("egress"));
+        assert_eq!(labels.get("iface"), Some("eth1"));
+        assert_eq!(labels.pairs(), vec![("iface", "eth1"), ("zone", "a")]);
+        assert!(!labels.is_empty());
+        assert!(Labels::new().is_empty());
+
+        let measurement = shared.labeled_measurement();
+        assert_eq!(measurement.rate(), 10.0);
+        assert_eq!(measurement.labels(), &labels);
+        assert_eq!(measurement.into_measurement().unwrap(), 40);
+
+        // Snapshot keeps labels it was taken with
+        let mut tw = RealTimeRunningAverage::<u32, _>::builder().time_source(ManualTimeSource::new()).name("ingress").build();
+        let measurement = tw.labeled_measurement();
+        tw.labels_mut().set_name("egress");
+        assert_eq!(measurement.labels().name(), Some("ingress"));
+        assert_eq!(tw.labeled_measurement().labels().name(), Some("egress"));
+    }
+}
@@ -0,0 +1,391 @@
+//! Combined latency and throughput tracking of completed operations using single time window.
+//!
+//! `LatencyThroughput` records latency and weight (e.g. number of bytes) of each completed operation into the same buckets
+//! so average latency, operations per second and weight per second are always measured over the same time span.
+//! ```
+//! use std::time::Duration;
+//! use running_average::latency::LatencyThroughput;
+//!
+//! let requests = LatencyThroughput::default();
+//!
+//! // Served 2KB in 20ms
+//! requests.record(Duration::from_millis(20), 2000.0);
+//!
+//! // Note: this may fail as it is based on real time
+//! let measurement = requests.measurement();
+//! assert_eq!(measurement.count(), 1);
+//! assert_eq!(measurement.average_latency(), Some(Duration::from_millis(20)));
+//! assert_eq!(measurement.weight_rate(), 250.0);
+//! ```
+//!
+//! `ConcurrencyEstimator` applies Little's law to the measured throughput and latency to estimate average number of operations in flight
+//! and number of workers needed to keep up with the measured throughput at target latency (e.g. for autoscaling of worker pools).
+//! ```
+//! use std::time::Duration;
+//! use running_average::latency::ConcurrencyEstimator;
+//!
+//! let workers = ConcurrencyEstimator::new(Duration::from_secs(1));
+//!
+//! // 100 requests each taking 50ms completed within last second
+//! for _ in 0..100 {
+//!     workers.record(Duration::from_millis(50));
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! assert!((workers.estimated_in_flight() - 5.0).abs() < 1e-9);
+//! assert_eq!(workers.suggested_concurrency(Duration::from_millis(20)), 2);
+//! ```
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+use super::{dts, Accumulate, Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Number, total latency and total weight of completed operations.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Operations {
+    count: u64,
+    latency: f64,
+    weight: f64,
+}
+
+impl Operations {
+    /// Create value of single operation of given latency and weight.
+    pub fn new(latency: Duration, weight: f64) -> Operations {
+        Operations {
+            count: 1,
+            latency: dts(latency),
+            weight,
+        }
+    }
+
+    /// Returns number of operations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns sum of latencies of operations in seconds.
+    pub fn latency(&self) -> f64 {
+        self.latency
+    }
+
+    /// Returns sum of weights of operations.
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+}
+
+impl AddAssign for Operations {
+    fn add_assign(&mut self, other: Operations) {
+        self.count += other.count;
+        self.latency += other.latency;
+        self.weight += other.weight;
+    }
+}
+
+impl<'i> Sum<&'i Operations> for Operations {
+    fn sum<I: Iterator<Item = &'i Operations>>(iter: I) -> Operations {
+        iter.fold(Operations::default(), |mut sum, operations| {
+            sum += *operations;
+            sum
+        })
+    }
+}
+
+impl Accumulate for Operations {
+    fn zero() -> Operations {
+        Operations::default()
+    }
+
+    fn add(&mut self, operations: Operations) {
+        *self += operations
+    }
+
+    fn merge(&self, other: &Operations) -> Operations {
+        let mut operations = *self;
+        operations += *other;
+        operations
+    }
+}
+
+/// Measurement of operations completed in the time window.
+#[derive(Debug, Clone)]
+pub struct LatencyThroughputMeasurement {
+    operations: Measurement<Operations>,
+}
+
+impl LatencyThroughputMeasurement {
+    /// Returns number of operations completed in the time window.
+    pub fn count(&self) -> u64 {
+        self.operations.value.count
+    }
+
+    /// Returns summary of operations completed in the time window.
+    pub fn operations(&self) -> &Operations {
+        &self.operations.value
+    }
+
+    /// Returns width of the time window.
+    pub fn duration(&self) -> Duration {
+        self.operations.duration
+    }
+
+    /// Returns number of operations completed per second.
+    pub fn rate(&self) -> f64 {
+        self.operations.value.count as f64 / dts(self.operations.duration)
+    }
+
+    /// Returns weight of operations completed per second.
+    pub fn weight_rate(&self) -> f64 {
+        self.operations.value.weight / dts(self.operations.duration)
+    }
+
+    /// Returns average latency of operations completed in the time window; None if no operations were completed.
+    pub fn average_latency(&self) -> Option<Duration> {
+        match self.operations.value.count {
+            0 => None,
+            count => Some(Duration::from_secs_f64(self.operations.value.latency / count as f64)),
+        }
+    }
+
+    /// Returns average number of operations in flight over the time window according to Little's law (rate times average latency).
+    pub fn estimated_in_flight(&self) -> f64 {
+        self.operations.value.latency / dts(self.operations.duration)
+    }
+
+    /// Returns number of operations that need to be in flight to sustain the measured rate if each took given target latency (at least 1).
+    pub fn suggested_concurrency(&self, target_latency: Duration) -> usize {
+        // Float to integer casts saturate and map NaN to 0
+        ((self.rate() * dts(target_latency)).ceil() as usize).max(1)
+    }
+}
+
+/// Cloneable handle to time window of completed operations shared between threads.
+pub struct LatencyThroughput<TS: TimeSource = RealTimeSource> {
+    shared: SharedRunningAverage<Operations, TS>,
+}
+
+impl<TS: TimeSource> fmt::Debug for LatencyThroughput<TS> where SharedRunningAverage<Operations, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LatencyThroughput").field("shared", &self.shared).finish()
+    }
+}
+
+impl<TS: TimeSource> Clone for LatencyThroughput<TS> {
+    fn clone(&self) -> LatencyThroughput<TS> {
+        LatencyThroughput {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Default for LatencyThroughput<RealTimeSource> {
+    /// Crate new LatencyThroughput instance with window of 8 seconds width and 16 buckets.
+    fn default() -> LatencyThroughput<RealTimeSource> {
+        LatencyThroughput::new(Duration::from_secs(8))
+    }
+}
+
+impl LatencyThroughput<RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> LatencyThroughput<RealTimeSource> {
+        LatencyThroughput {
+            shared: SharedRunningAverage::new(duration),
+        }
+    }
+}
+
+impl<TS: TimeSource> From<RealTimeRunningAverage<Operations, TS>> for LatencyThroughput<TS> {
+    fn from(running_average: RealTimeRunningAverage<Operations, TS>) -> LatencyThroughput<TS> {
+        LatencyThroughput {
+            shared: running_average.into(),
+        }
+    }
+}
+
+impl<TS: TimeSource> LatencyThroughput<TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> LatencyThroughput<TS> {
+        LatencyThroughput {
+            shared: SharedRunningAverage::with_time_source(duration, capacity, time_source),
+        }
+    }
+
+    /// Record operation of given latency and weight completed now.
+    /// Panics if time source time goes backwards.
+    pub fn record(&self, latency: Duration, weight: f64) {
+        self.shared.insert(Operations::new(latency, weight))
+    }
+
+    /// Call given closure recording it as operation of weight 0 with latency of time it took to complete and return its result.
+    /// Note: latency is measured with real time clock regardless of time source used.
+    /// Panics if time source time goes backwards.
+    pub fn measure_call<R>(&self, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(start.elapsed(), 0.0);
+        result
+    }
+
+    /// Calculate measurement of operations completed in time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&self) -> LatencyThroughputMeasurement {
+        LatencyThroughputMeasurement {
+            operations: self.shared.measurement(),
+        }
+    }
+
+    /// Returns the underlying shared running average.
+    pub fn shared(&self) -> &SharedRunningAverage<Operations, TS> {
+        &self.shared
+    }
+}
+
+/// Cloneable handle estimating concurrency of operations from their throughput and latency measured over the same time window.
+pub struct ConcurrencyEstimator<TS: TimeSource = RealTimeSource> {
+    operations: LatencyThroughput<TS>,
+}
+
+impl<TS: TimeSource> fmt::Debug for ConcurrencyEstimator<TS> where LatencyThroughput<TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConcurrencyEstimator").field("operations", &self.operations).finish()
+    }
+}
+
+impl<TS: TimeSource> Clone for ConcurrencyEstimator<TS> {
+    fn clone(&self) -> ConcurrencyEstimator<TS> {
+        ConcurrencyEstimator {
+            operations: self.operations.clone(),
+        }
+    }
+}
+
+impl Default for ConcurrencyEstimator<RealTimeSource> {
+    /// Crate new ConcurrencyEstimator instance with window of 8 seconds width and 16 buckets.
+    fn default() -> ConcurrencyEstimator<RealTimeSource> {
+        ConcurrencyEstimator::new(Duration::from_secs(8))
+    }
+}
+
+impl ConcurrencyEstimator<RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> ConcurrencyEstimator<RealTimeSource> {
+        LatencyThroughput::new(duration).into()
+    }
+}
+
+impl<TS: TimeSource> From<LatencyThroughput<TS>> for ConcurrencyEstimator<TS> {
+    fn from(operations: LatencyThroughput<TS>) -> ConcurrencyEstimator<TS> {
+        ConcurrencyEstimator {
+            operations,
+        }
+    }
+}
+
+impl<TS: TimeSource> ConcurrencyEstimator<TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> ConcurrencyEstimator<TS> {
+        LatencyThroughput::with_time_source(duration, capacity, time_source).into()
+    }
+
+    /// Record operation of given latency completed now.
+    /// Panics if time source time goes backwards.
+    pub fn record(&self, latency: Duration) {
+        self.operations.record(latency, 0.0)
+    }
+
+    /// Call given closure recording it as operation with latency of time it took to complete and return its result.
+    /// Note: latency is measured with real time clock regardless of time source used.
+    /// Panics if time source time goes backwards.
+    pub fn measure_call<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.operations.measure_call(f)
+    }
+
+    /// Returns average number of operations in flight over time window ending now.
+    /// See `LatencyThroughputMeasurement::estimated_in_flight()`.
+    /// Panics if time source time goes backwards.
+    pub fn estimated_in_flight(&self) -> f64 {
+        self.operations.measurement().estimated_in_flight()
+    }
+
+    /// Returns number of operations that need to be in flight to sustain rate measured over time window ending now at given target latency.
+    /// See `LatencyThroughputMeasurement::suggested_concurrency()`.
+    /// Panics if time source time goes backwards.
+    pub fn suggested_concurrency(&self, target_latency: Duration) -> usize {
+        self.operations.measurement().suggested_concurrency(target_latency)
+    }
+
+    /// Returns the underlying latency and throughput time window.
+    pub fn operations(&self) -> &LatencyThroughput<TS> {
+        &self.operations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn measure_latency_and_throughput() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let requests = LatencyThroughput::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let measurement = requests.measurement();
+        assert_eq!(measurement.count(), 0);
+        assert_eq!(measurement.average_latency(), None);
+
+        requests.record(Duration::from_millis(10), 1000.0);
+        requests.shared().lock().time_source().time_shift(1.0);
+        requests.record(Duration::from_millis(30), 3000.0);
+
+        let measurement = requests.measurement();
+        assert_eq!(measurement.count(), 2);
+        assert_eq!(measurement.rate(), 0.5);
+        assert_eq!(measurement.weight_rate(), 1000.0);
+        assert_eq!(measurement.average_latency(), Some(Duration::from_millis(20)));
+
+        requests.shared().lock().time_source().time_shift(3.0);
+        let measurement = requests.measurement();
+        assert_eq!(measurement.count(), 1);
+        assert_eq!(measurement.operations().weight(), 3000.0);
+    }
+
+    #[test]
+    fn measure_calls() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let calls = LatencyThroughput::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        assert_eq!(calls.measure_call(|| 2 + 2), 4);
+        assert_eq!(calls.measure_call(|| "done"), "done");
+
+        let measurement = calls.measurement();
+        assert_eq!(measurement.count(), 2);
+        assert_eq!(measurement.operations().weight(), 0.0);
+        assert!(measurement.average_latency().is_some());
+    }
+
+    #[test]
+    fn estimate_concurrency() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let workers = ConcurrencyEstimator::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(workers.estimated_in_flight(), 0.0);
+        assert_eq!(workers.suggested_concurrency(Duration::from_secs(1)), 1);
+
+        // 16 operations of 500ms and 16 of 250ms in 4 seconds
+        for _ in 0..16 {
+            workers.record(Duration::from_millis(500));
+            workers.record(Duration::from_millis(250));
+        }
+
+        assert_eq!(workers.estimated_in_flight(), 3.0);
+        assert_eq!(workers.suggested_concurrency(Duration::from_millis(250)), 2);
+        assert_eq!(workers.suggested_concurrency(Duration::from_millis(300)), 3);
+        assert_eq!(workers.operations().measurement().count(), 32);
+    }
+}
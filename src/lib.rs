@@ -20,17 +20,177 @@
 //! // Print average transfer for last 8 seconds
 //! println!("{}", tw.measurement());
 //! ```
-
-use std::collections::VecDeque;
-use std::time::{Instant, Duration};
-use std::ops::AddAssign;
-use std::iter::Sum;
-use std::default::Default;
+//!
+//! Without default `std` feature the crate is `no_std` and does not need `alloc`: `RunningAverage` can store its buckets in an array
+//! (see `RunningAverage::new_const()`) or in `heapless::Vec` (with `heapless` feature) using custom `TimeInstant` implementation (e.g. hardware timer ticks)
+//! while `RealTimeRunningAverage`, statistics of bucket rates and all other modules require `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+use core::time::Duration;
+use core::ops::{Add, Sub};
+use core::default::Default;
+use core::marker::PhantomData;
+use core::convert::TryFrom;
+
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "futures")]
+extern crate futures_timer;
+#[cfg(feature = "futures")]
+extern crate futures_io;
+#[cfg(feature = "crossbeam")]
+extern crate crossbeam_channel;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "tracing")]
+extern crate tracing_core;
+#[cfg(feature = "tracing")]
+extern crate tracing_subscriber;
+#[cfg(feature = "macros")]
+extern crate running_average_macros;
+#[cfg(feature = "heapless")]
+extern crate heapless;
+#[cfg(feature = "testing")]
+extern crate proptest;
+
+/// Attribute recording calls of the function and time spent in them into the global registry (requires `macros` feature).
+/// See `registry::CallSite` for names of the registry rates used.
+/// ```
+/// use running_average::{measure_rate, registry};
+///
+/// #[measure_rate("parse")]
+/// fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+///     input.parse()
+/// }
+///
+/// assert_eq!(parse("42"), Ok(42));
+/// assert!(parse("x").is_err());
+///
+/// // Note: this may fail as it is based on real time
+/// assert_eq!(registry::rate("parse.calls").measurement().unwrap(), 2.0);
+/// ```
+#[cfg(feature = "macros")]
+pub use running_average_macros::measure_rate;
+
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod reporter;
+#[cfg(feature = "std")]
+pub mod iter;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod channel;
+#[cfg(feature = "std")]
+pub mod limiter;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "std")]
+pub mod staged;
+#[cfg(feature = "std")]
+pub mod seqlock;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "std")]
+pub mod labels;
+#[cfg(feature = "std")]
+pub mod thread_local;
+#[cfg(feature = "std")]
+pub mod timer;
+#[cfg(feature = "std")]
+pub mod latency;
+pub mod format;
+#[cfg(feature = "std")]
+pub mod exact;
+#[cfg(feature = "std")]
+pub mod hybrid;
+#[cfg(feature = "std")]
+pub mod sketch;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod utilization;
+#[cfg(feature = "std")]
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod segmented;
+#[cfg(feature = "std")]
+pub mod sparse;
+#[cfg(feature = "std")]
+pub mod set;
+#[cfg(feature = "std")]
+pub mod keyed;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+#[cfg(feature = "log")]
+pub mod log;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "indicatif")]
+pub mod indicatif;
+#[cfg(feature = "plotters")]
+pub mod plotters;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "tokio")]
+pub mod tokio_reporter;
+#[cfg(feature = "futures")]
+pub mod stream;
+#[cfg(any(feature = "tokio", feature = "futures"))]
+pub mod async_io;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "tdigest")]
+pub mod tdigest;
+#[cfg(feature = "hdrhistogram")]
+pub mod hdrhistogram;
+#[cfg(feature = "ddsketch")]
+pub mod ddsketch;
+#[cfg(feature = "hll")]
+pub mod hll;
+#[cfg(feature = "os")]
+pub mod os;
+#[cfg(feature = "humantime")]
+pub mod humantime;
 
 /// Types implementing this trait can be used as Instant type in TimeSource trait and for RunningAverage
 pub trait TimeInstant {
     /// Returns Duration elapsed since given TimeInstant and Self.
     fn duration_since(&self, since: Self) -> Duration;
+    /// Returns Duration elapsed since given TimeInstant and Self or error if given TimeInstant is later than Self.
+    /// Default implementation cannot tell if time went backwards and returns `duration_since()`.
+    fn try_duration_since(&self, since: Self) -> Result<Duration, TimeWentBackwards> where Self: Sized {
+        Ok(self.duration_since(since))
+    }
     /// Forward Self by given Duration into future.
     fn forward(&mut self, duration: Duration);
 }
@@ -43,19 +203,26 @@ pub trait TimeSource {
     fn now(&self) -> Self::Instant;
 }
 
+#[cfg(feature = "std")]
 impl TimeInstant for Instant {
     fn duration_since(&self, earlier: Self) -> Duration {
         self.duration_since(earlier)
     }
 
+    fn try_duration_since(&self, earlier: Self) -> Result<Duration, TimeWentBackwards> {
+        self.checked_duration_since(earlier).ok_or(TimeWentBackwards)
+    }
+
     fn forward(&mut self, duration: Duration) {
         *self += duration;
     }
 }
 
 /// TimeSource that uses real time clock via `Instant::now()`.
-#[derive(Debug)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
 pub struct RealTimeSource;
+#[cfg(feature = "std")]
 impl TimeSource for RealTimeSource {
     type Instant = Instant;
 
@@ -65,30 +232,120 @@ impl TimeSource for RealTimeSource {
 }
 
 fn dts(duration: Duration) -> f64 {
-    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+/// Number of buckets limited to number of nanoseconds of window of given width (but at least one) so that buckets of one nanosecond do not span more than the window.
+#[cfg(any(feature = "std", feature = "heapless"))]
+const fn bucket_capacity(duration: Duration, capacity: usize) -> usize {
+    let nanos = duration.as_nanos();
+    if capacity as u128 > nanos {
+        if nanos == 0 { 1 } else { nanos as usize }
+    } else {
+        capacity
+    }
+}
+
+/// Width of time span of each of given number of buckets of window of given width computed in whole nanoseconds (rounded down)
+/// but at least one nanosecond so that windows of more buckets than nanoseconds keep moving forward.
+const fn slot_duration(duration: Duration, capacity: usize) -> Duration {
+    let slot_nanos = match duration.as_nanos() / capacity as u128 {
+        0 => 1,
+        slot_nanos => slot_nanos,
+    };
+    // Fits as it is not more than nanoseconds of given duration
+    Duration::new((slot_nanos / 1_000_000_000) as u64, (slot_nanos % 1_000_000_000) as u32)
+}
+
+#[cfg(feature = "std")]
+const MAX_RESOLUTION_CAPACITY: u128 = 4096;
+
+#[cfg(feature = "std")]
+fn resolution_capacity(duration: Duration, bucket_duration: Duration) -> usize {
+    let capacity = match bucket_duration.as_nanos() {
+        0 => MAX_RESOLUTION_CAPACITY,
+        bucket_nanos => duration.as_nanos().div_ceil(bucket_nanos),
+    };
+    capacity.clamp(1, MAX_RESOLUTION_CAPACITY) as usize
+}
+
+/// Error returned when time instant is earlier than the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWentBackwards;
+
+impl fmt::Display for TimeWentBackwards {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "time instant is earlier than the previous one - time went backwards")
+    }
+}
+
+impl core::error::Error for TimeWentBackwards {}
+
+/// Handling of time instants earlier than the start of the current bucket by `RunningAverage::record()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BackwardsPolicy {
+    /// Panic, also in release builds, as `insert()` does with `strict` feature.
+    #[default]
+    Panic,
+    /// Return error without inserting as `try_insert()` does.
+    Error,
+    /// Insert into the current bucket as if no time elapsed.
+    Clamp,
 }
 
+#[cfg(feature = "std")]
+fn try_std(seconds: f64) -> Result<Duration, TimeWentBackwards> {
+    if seconds >= 0.0 {
+        // Float to integer casts saturate so seconds too large for Duration give Duration::MAX
+        Ok(from_nanos((seconds * 1e9).round() as u128))
+    } else {
+        Err(TimeWentBackwards)
+    }
+}
+
+#[cfg(feature = "std")]
+/// Negative seconds panic in debug builds or with `strict` feature; otherwise they give zero Duration as if no time elapsed.
 fn std(seconds: f64) -> Duration {
-    assert!(seconds >= 0.0, "RunningAverage negative duration - time going backwards?");
-    Duration::new(seconds.floor() as u64, ((seconds - seconds.floor()) * 1e-9) as u32)
+    if cfg!(feature = "strict") {
+        assert!(seconds >= 0.0, "RunningAverage negative duration - time going backwards?");
+    } else {
+        debug_assert!(seconds >= 0.0, "RunningAverage negative duration - time going backwards?");
+    }
+    try_std(seconds).unwrap_or_default()
 }
 
+fn from_nanos(nanos: u128) -> Duration {
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+#[cfg(feature = "std")]
 impl TimeInstant for f64 {
     fn duration_since(&self, earlier: Self) -> Duration {
         std(self - earlier)
     }
 
+    fn try_duration_since(&self, earlier: Self) -> Result<Duration, TimeWentBackwards> {
+        try_std(self - earlier)
+    }
+
     fn forward(&mut self, duration: Duration) {
         *self += dts(duration);
     }
 }
 
 /// TimeSource that has to be manually progressed forward via `ManualTimeSource::time_shift()` method.
-#[derive(Debug)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
 pub struct ManualTimeSource {
     now: f64,
 }
 
+#[cfg(feature = "std")]
 impl TimeSource for ManualTimeSource {
     type Instant = f64;
 
@@ -97,6 +354,14 @@ impl TimeSource for ManualTimeSource {
     }
 }
 
+#[cfg(feature = "std")]
+impl Default for ManualTimeSource {
+    fn default() -> ManualTimeSource {
+        ManualTimeSource::new()
+    }
+}
+
+#[cfg(feature = "std")]
 impl ManualTimeSource {
     pub fn new() -> ManualTimeSource {
         ManualTimeSource {
@@ -107,19 +372,66 @@ impl ManualTimeSource {
     pub fn time_shift(&mut self, seconds: f64) {
         self.now += seconds;
     }
+
+    /// Set current time to given number of seconds since time source creation.
+    /// Note: setting time earlier than time already seen by running average will make it panic.
+    pub fn set(&mut self, seconds: f64) {
+        self.now = seconds;
+    }
+
+    /// Returns current time as number of seconds since time source creation.
+    pub fn now_seconds(&self) -> f64 {
+        self.now
+    }
 }
 
 /// Represent result of the calculation of running average
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Measurement<T> {
     value: T, 
     duration: Duration,
 }
 
-use std::fmt;
+use core::fmt;
 impl<T> fmt::Display for Measurement<T> where T: Clone + fmt::Display + ToRate, <T as ToRate>::Output: Into<f64> {
+    /// Formats rate with 3 (or given precision) decimal places; alternate flag (`{:#}`) also shows the sum of values and width of the time window (e.g. `375.000 (3000 over 8s)`).
+    /// Width, fill and alignment are applied to the whole text that is aligned to the right by default.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:.3}", self.rate().into())
+        let precision = f.precision().unwrap_or(3);
+        let rate = self.rate().into();
+        let alternate = f.alternate();
+        let text = |out: &mut dyn fmt::Write| -> fmt::Result {
+            write!(out, "{:.*}", precision, rate)?;
+            if alternate {
+                write!(out, " ({} over {:?})", self.value, self.duration)?;
+            }
+            Ok(())
+        };
+
+        // Formatter::pad() would truncate to precision so text is measured and padded here
+        let mut width = CharCount(0);
+        text(&mut width)?;
+        let padding = f.width().unwrap_or(0).saturating_sub(width.0);
+        let (before, after) = match f.align() {
+            Some(fmt::Alignment::Left) => (0, padding),
+            Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+            Some(fmt::Alignment::Right) | None => (padding, 0),
+        };
+        let fill = f.fill();
+
+        (0..before).try_for_each(|_| fmt::Write::write_char(f, fill))?;
+        text(f)?;
+        (0..after).try_for_each(|_| fmt::Write::write_char(f, fill))
+    }
+}
+
+/// Counts characters written to it.
+struct CharCount(usize);
+
+impl fmt::Write for CharCount {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
     }
 }
 
@@ -129,6 +441,11 @@ impl<T> Measurement<T> {
         &self.value
     }
 
+    /// Returns width of the time window this measurement was calculated over
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
     /// Returns internal value of the measurement which is sum of all samples within time window consuming self
     pub fn unwrap(self) -> T {
         self.value
@@ -143,248 +460,2249 @@ impl<T> Measurement<T> {
     pub fn to_rate(self) -> <T as ToRate>::Output where T: ToRate {
         self.value.to_rate(self.duration)
     }
-}
-
-/// Represents running average calculation window.
-/// It is using specified window width that will consist of given number of accumulator buckets to ensure constant memory usage.
-#[derive(Debug)]
-pub struct RunningAverage<V: Default, I: TimeInstant + Copy> {
-    window: VecDeque<V>,
-    front: Option<I>,
-    duration: Duration,
-}
 
-impl<V: Default, I: TimeInstant + Copy> Default for RunningAverage<V, I> {
-    /// Crate new RunningAverage instance with window of 8 seconds width and 16 buckets.
-    fn default() -> RunningAverage<V, I> {
-        RunningAverage::new(Duration::from_secs(8))
+    /// Calculates rate as `f64` for any value convertible to `f64`.
+    pub fn rate_f64(&self) -> f64 where T: Clone + Into<f64> {
+        self.value.clone().into() / dts(self.duration)
     }
-}
 
-impl<V: Default, I: TimeInstant + Copy> RunningAverage<V, I> {
-    /// Crate new RunningAverage instance that will average over window of width of given duration using 16 buckets.
-    pub fn new(duration: Duration) -> RunningAverage<V, I> {
-        RunningAverage::with_capacity(duration, 16)
+    /// Calculates rate as `f32` for any value convertible to `f32` consuming self, avoiding `f64` math on targets with `f32` only or no FPU.
+    pub fn to_rate_f32(self) -> f32 where T: Into<f32> {
+        let duration = self.duration.as_secs() as f32 + self.duration.subsec_nanos() as f32 * 1e-9;
+        self.value.into() / duration
     }
 
-    /// Crate new RunningAverage instance that will average over window of width of given duration with specific number of buckets to use.
-    pub fn with_capacity(duration: Duration, capacity: usize) -> RunningAverage<V, I> {
-        assert!(capacity > 0, "RunningAverage capacity cannot be 0");
-        RunningAverage {
-            window: (0..capacity).map(|_| V::default()).collect(),
-            front: None,
-            duration: duration,
-        }
+    /// Calculates rate rounded down to whole number; negative rate is returned as 0 and rate too large saturates at `u64::MAX`.
+    pub fn rate_u64_floor(&self) -> u64 where T: Clone + Into<f64> {
+        // Float to integer casts round towards zero, saturate and map NaN to 0
+        self.rate_f64() as u64
     }
 
-    fn shift(&mut self, now: I) {
-        let front = self.front.get_or_insert(now);
-        let slot_duration = self.duration / self.window.len() as u32;
-        let mut slots_to_go = self.window.len();
-
-        while now.duration_since(*front) >= slot_duration {
-            // Stop if we zeroed all slots or this can loop for long time if shift was not called recently
-            if slots_to_go == 0 {
-                let since_front = now.duration_since(*front);
-                front.forward(since_front);
-                break;
-            }
-            self.window.pop_back();
-            self.window.push_front(V::default());
-            front.forward(slot_duration);
-            slots_to_go -= 1;
+    /// Calculates rate in fixed-point units of `1/scale` per second (e.g. scale of 1000 gives milli-units per second) using integer math only.
+    /// Rate is rounded down; rate too large or of zero width time window saturates at `u64::MAX` (or is 0 for zero value).
+    pub fn rate_fixed_u64(&self, scale: u64) -> u64 where T: Clone + Into<u64> {
+        let scaled = (self.value.clone().into() as u128 * scale as u128).saturating_mul(1_000_000_000);
+        match scaled.checked_div(self.duration.as_nanos()) {
+            Some(rate) => rate.min(u64::MAX as u128) as u64,
+            None if scaled == 0 => 0,
+            None => u64::MAX,
         }
     }
-    
-    /// Insert value to be average over at given time instant.
-    /// Panics if now is less than previous now - time cannot go backwards
-    pub fn insert(&mut self, now: I, val: V) where V: AddAssign<V> {
-        self.shift(now);
-        *self.window.front_mut().unwrap() += val;
+
+    /// Calculates rate in milli-units per second using integer math only; see `rate_fixed_u64()`.
+    pub fn rate_milli_u64(&self) -> u64 where T: Clone + Into<u64> {
+        self.rate_fixed_u64(1000)
     }
 
-    /// Calculate running average using time window ending at given time instant.
-    /// Panics if now is less than previous now - time cannot go backwards.
-    pub fn measurement<'i>(&'i mut self, now: I) -> Measurement<V> where V: Sum<&'i V> {
-        self.shift(now);
+    /// Suggest how many items to batch per flush so that, at the measured rate of items per second, batches fill up within given target latency.
+    /// Returns at least 1; rate too large saturates at `usize::MAX`.
+    #[cfg(feature = "std")]
+    pub fn suggest_batch_size(&self, target_latency: Duration) -> usize where T: Clone + Into<f64> {
+        // Float to integer casts saturate and map NaN to 0
+        ((self.rate_f64() * dts(target_latency)).round() as usize).max(1)
+    }
 
+    /// Pool given measurements into one of their values merged (e.g. summed) over sum of their time window widths,
+    /// so its rate is average of rates of given measurements weighted by their time window widths.
+    /// Note: rate of measurement pooled from no measurements is not a number.
+    pub fn combine(measurements: &[Measurement<T>]) -> Measurement<T> where T: Accumulate {
         Measurement {
-            value: self.window.iter().sum(),
-            duration: self.duration,
+            value: measurements.iter().fold(<T as Accumulate>::zero(), |sum, measurement| sum.merge(&measurement.value)),
+            duration: measurements.iter().map(|measurement| measurement.duration).sum(),
         }
     }
-}
 
-/// Represents running average calculation window where `shift` and `measurement` are using given time source to obtain value of `now` instant.
-/// It is using specified window width that will consist of given number of accumulator buckets to ensure constant memory usage.
-#[derive(Debug)]
-pub struct RealTimeRunningAverage<V: Default, TS: TimeSource = RealTimeSource> {
-    inner: RunningAverage<V, TS::Instant>,
-    time_source: TS,
+    /// Calculates interval of rate that contains the true rate with given confidence level (e.g. 0.95) as `(low, high)` pair.
+    /// Value is assumed to be number of events arriving independently (Poisson process); uses Wilson score interval
+    /// so that interval is meaningful also for small counts (e.g. for no events it is `0` to the rate of the few events that could have been missed).
+    /// Panics if level is not between 0 and 1 exclusive.
+    #[cfg(feature = "std")]
+    pub fn confidence_interval(&self, level: f64) -> (f64, f64) where T: Clone + Into<f64> {
+        assert!(level > 0.0 && level < 1.0, "confidence level needs to be between 0 and 1 exclusive");
+
+        let count = self.value.clone().into().max(0.0);
+        let seconds = dts(self.duration);
+        let z = normal_quantile(0.5 + level / 2.0);
+
+        let center = count + z * z / 2.0;
+        let spread = z * (count + z * z / 4.0).sqrt();
+        ((center - spread).max(0.0) / seconds, (center + spread) / seconds)
+    }
 }
 
-impl<V: Default> Default for RealTimeRunningAverage<V, RealTimeSource> {
-    fn default() -> RealTimeRunningAverage<V, RealTimeSource> {
-        RealTimeRunningAverage::new(Duration::from_secs(8))
+/// Converts measurement into its rate.
+impl<T: Into<f64>> From<Measurement<T>> for f64 {
+    fn from(measurement: Measurement<T>) -> f64 {
+        measurement.value.into() / dts(measurement.duration)
     }
 }
 
-impl<V: Default> RealTimeRunningAverage<V, RealTimeSource> {
-    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
-    /// Note: new() is parametrizing output to RealTimeSource as this cannot be inferred otherwise.
-    pub fn new(duration: Duration) -> RealTimeRunningAverage<V, RealTimeSource> {
-        let time_source = RealTimeSource;
+/// Error returned when combining measurements calculated over time windows of different width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationMismatch {
+    left: Duration,
+    right: Duration,
+}
 
-        RealTimeRunningAverage {
-            inner: RunningAverage::new(duration),
-            time_source,
-        }
+impl DurationMismatch {
+    /// Returns width of the time window of the left hand side measurement.
+    pub fn left(&self) -> Duration {
+        self.left
     }
-}
 
-impl<V: Default, TS: TimeSource> RealTimeRunningAverage<V, TS> {
-    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
-    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> RealTimeRunningAverage<V, TS> {
-        RealTimeRunningAverage {
-            inner: RunningAverage::with_capacity(duration, capacity),
-            time_source,
-        }
+    /// Returns width of the time window of the right hand side measurement.
+    pub fn right(&self) -> Duration {
+        self.right
     }
+}
 
-    /// Insert value to be average over now.
-    /// Panics if time source time goes backwards.
-    pub fn insert(&mut self, val: V) where V: AddAssign<V> {
-        let now = self.time_source.now();
-        self.inner.insert(now, val)
+impl fmt::Display for DurationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "measurements have different time window width: {:?} and {:?}", self.left, self.right)
     }
-    
-    /// Calculate running average using time window ending now.
-    /// Panics if time source time goes backwards.
-    pub fn measurement<'i>(&'i mut self) -> Measurement<V> where V: Sum<&'i V> {
-        let now = self.time_source.now();
-        self.inner.measurement(now)
+}
+
+impl core::error::Error for DurationMismatch {}
+
+/// Error returned when number of buckets is not valid for the width of the time window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCapacity {
+    duration: Duration,
+    capacity: usize,
+}
+
+impl InvalidCapacity {
+    /// Returns width of the time window.
+    pub fn duration(&self) -> Duration {
+        self.duration
     }
 
-    /// Return mutable reference to time source used.
-    pub fn time_source(&mut self) -> &mut TS {
-        &mut self.time_source
+    /// Returns requested number of buckets.
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 }
 
-/// Types implementing this trait can be used to calculate `Measurement::rate()` from.
-/// Note: This is not implemented for u64 as it cannot be converted precisely to f64 - use f64 instead for big numbers
-/// Note: Duration can be converted to f64 but will be rounded to fit in it so it is not 100% precise for max Duration
-pub trait ToRate {
-    type Output;
-    fn to_rate(self, duration: Duration) -> Self::Output;
+impl fmt::Display for InvalidCapacity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid capacity of {} buckets for time window of {:?} width: capacity needs to be between 1 and number of nanoseconds of the window", self.capacity, self.duration)
+    }
 }
 
-impl<T: Into<f64>> ToRate for T {
-    type Output = f64;
+impl core::error::Error for InvalidCapacity {}
 
-    fn to_rate(self, duration: Duration) -> f64 {
-        let v: f64 = self.into();
-        v / dts(duration)
+/// Check that there is at least one bucket and each bucket is at least one nanosecond wide.
+#[cfg(feature = "std")]
+fn validate_capacity(duration: Duration, capacity: usize) -> Result<(), InvalidCapacity> {
+    if capacity == 0 || capacity as u128 > duration.as_nanos() {
+        Err(InvalidCapacity { duration, capacity })
+    } else {
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn const_over_different_capacity() {
-        use super::*;
+fn matching_duration(left: Duration, right: Duration) -> Result<Duration, DurationMismatch> {
+    if left == right {
+        Ok(left)
+    } else {
+        Err(DurationMismatch { left, right })
+    }
+}
 
-        for capacity in 1..31 {
-            let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), capacity, ManualTimeSource::new());
+/// Sum values of measurements calculated over time windows of the same width.
+impl<T: Add<Output = T>> Add for Measurement<T> {
+    type Output = Result<Measurement<T>, DurationMismatch>;
 
-            tw.insert(10);
-            tw.time_source().time_shift(1.0);
-            tw.insert(10);
-            tw.time_source().time_shift(1.0);
-            tw.insert(10);
-            tw.time_source().time_shift(1.0);
-            tw.insert(10);
+    fn add(self, other: Measurement<T>) -> Self::Output {
+        Ok(Measurement {
+            duration: matching_duration(self.duration, other.duration)?,
+            value: self.value + other.value,
+        })
+    }
+}
 
-            assert_eq!(tw.measurement().unwrap(), 40, "for capacity {}: {:?}", capacity, tw);
-            assert_eq!(tw.measurement().to_rate(), 10.0, "for capacity {}: {:?}", capacity, tw);
-        }
+/// Subtract values of measurements calculated over time windows of the same width.
+impl<T: Sub<Output = T>> Sub for Measurement<T> {
+    type Output = Result<Measurement<T>, DurationMismatch>;
+
+    fn sub(self, other: Measurement<T>) -> Self::Output {
+        Ok(Measurement {
+            duration: matching_duration(self.duration, other.duration)?,
+            value: self.value - other.value,
+        })
     }
+}
 
-    #[test]
-    fn const_half_time_over_different_capacity() {
-        use super::*;
+/// Inverse of standard normal cumulative distribution function (Acklam's rational approximation).
+#[cfg(feature = "std")]
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02, 1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    const B: [f64; 5] = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02, 6.680131188771972e+01, -1.328068155288572e+01];
+    const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00, -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00, 3.754408661907416e+00];
+    const LOW: f64 = 0.02425;
+
+    let tail = |q: f64| (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0);
+
+    if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
 
-        for capacity in 1..31 {
-            let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), capacity, ManualTimeSource::new());
+#[cfg(feature = "std")]
+fn sparkline<I: Iterator<Item = f64> + Clone>(values: I) -> String {
+    const BLOCKS: [char; 8] = ['\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}'];
 
-            tw.insert(10);
-            tw.time_source().time_shift(1.0);
-            tw.insert(10);
-            tw.time_source().time_shift(1.0);
-            tw.time_source().time_shift(1.0);
+    let max = values.clone().fold(0.0, f64::max);
+    values.map(|v| if max > 0.0 {
+        BLOCKS[((v.max(0.0) / max) * (BLOCKS.len() - 1) as f64).round() as usize]
+    } else {
+        BLOCKS[0]
+    }).collect()
+}
 
-            assert_eq!(tw.measurement().unwrap(), 20, "for capacity {}: {:?}", capacity, tw);
-            assert_eq!(tw.measurement().to_rate(), 5.0, "for capacity {}: {:?}", capacity, tw);
+/// Types implementing this trait can be accumulated in `RunningAverage` buckets.
+/// Empty buckets are set to `zero()`, inserted values are accumulated into a bucket with `add()` and buckets are combined into measured value with `merge()`.
+/// This is implemented for primitive numeric types and `Duration`; custom accumulators (e.g. minimum or maximum) can be used by implementing it.
+pub trait Accumulate<V = Self> {
+    /// Returns value of empty bucket; it has to be identity of `merge()`.
+    fn zero() -> Self;
+    /// Accumulate inserted value.
+    fn add(&mut self, val: V);
+    /// Returns combination of this and other accumulated value.
+    fn merge(&self, other: &Self) -> Self;
+}
+
+macro_rules! impl_accumulate {
+    ($($t:ty),*) => {$(
+        impl Accumulate for $t {
+            fn zero() -> $t {
+                0 as $t
+            }
+
+            fn add(&mut self, val: $t) {
+                *self += val
+            }
+
+            fn merge(&self, other: &$t) -> $t {
+                *self + *other
+            }
         }
-    }
+    )*}
+}
 
-    #[test]
-    fn default_int() {
-        use super::*;
+impl_accumulate!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
 
-        let mut tw = RealTimeRunningAverage::default();
+impl Accumulate for Duration {
+    fn zero() -> Duration {
+        Duration::from_secs(0)
+    }
 
-        tw.insert(10);
-        tw.insert(10);
+    fn add(&mut self, val: Duration) {
+        *self += val
+    }
 
-        // Note: this may fail as it is based on real time
-        assert_eq!(tw.measurement().unwrap(), 20, "default: {:?}", tw);
-        assert_eq!(tw.measurement().to_rate(), 2.5, "default: {:?}", tw);
+    fn merge(&self, other: &Duration) -> Duration {
+        *self + *other
     }
+}
 
-    #[test]
-    fn default_f64() {
-        use super::*;
+/// Wrapper allowing types that implement `Add` but not `Accumulate` (e.g. third-party numeric types) to be used as running average values.
+/// Value of empty bucket is `T::default()`.
+/// ```
+/// use std::time::Duration;
+/// use running_average::{Adder, RealTimeRunningAverage, ManualTimeSource};
+///
+/// let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+/// tw.insert(Adder(2.0f64));
+/// tw.accumulate(3.0);
+/// assert_eq!(tw.measurement().to_rate(), 1.25);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Adder<T>(pub T);
+
+impl<T: Add<Output = T> + Default + Clone> Accumulate<T> for Adder<T> {
+    fn zero() -> Adder<T> {
+        Adder(T::default())
+    }
 
-        let mut tw = RealTimeRunningAverage::default();
+    fn add(&mut self, val: T) {
+        self.0 = core::mem::take(&mut self.0) + val
+    }
 
-        tw.insert(10f64);
-        tw.insert(10.0);
+    fn merge(&self, other: &Adder<T>) -> Adder<T> {
+        Adder(self.0.clone() + other.0.clone())
+    }
+}
 
-        // Note: this may fail as it is based on real time
-        assert_eq!(tw.measurement().unwrap(), 20.0, "default: {:?}", tw);
-        assert_eq!(tw.measurement().to_rate(), 2.5, "default: {:?}", tw);
+impl<T: Add<Output = T> + Default + Clone> Accumulate for Adder<T> {
+    fn zero() -> Adder<T> {
+        Adder(T::default())
     }
 
-    #[test]
-    fn long_time_shift() {
-        use super::*;
+    fn add(&mut self, val: Adder<T>) {
+        Accumulate::add(self, val.0)
+    }
 
-        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 16, ManualTimeSource::new());
+    fn merge(&self, other: &Adder<T>) -> Adder<T> {
+        Accumulate::<T>::merge(self, other)
+    }
+}
 
-        tw.insert(10);
-        tw.time_source().time_shift(1_000_000_000.0);
-        tw.insert(10);
-        tw.time_source().time_shift(1.0);
-        tw.insert(10);
-        tw.time_source().time_shift(1.0);
-        tw.insert(10);
-        tw.time_source().time_shift(1.0);
-        tw.insert(10);
+impl<T: Into<f64>> From<Adder<T>> for f64 {
+    fn from(adder: Adder<T>) -> f64 {
+        adder.0.into()
+    }
+}
 
-        assert_eq!(tw.measurement().unwrap(), 40, "long: {:?}", tw);
-        assert_eq!(tw.measurement().to_rate(), 10.0, "long: {:?}", tw);
+/// Direction of trend of bucket rates within time window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    /// Rate is increasing faster than the threshold.
+    Rising,
+    /// Rate is decreasing faster than the threshold.
+    Falling,
+    /// Rate is changing no faster than the threshold.
+    Flat,
+}
+
+impl Trend {
+    /// Classify given slope as rising or falling if its absolute value exceeds given threshold.
+    pub fn classify(slope: f64, threshold: f64) -> Trend {
+        if slope > threshold {
+            Trend::Rising
+        } else if slope < -threshold {
+            Trend::Falling
+        } else {
+            Trend::Flat
+        }
     }
+}
 
-    #[test]
-    fn measurement_display() {
-        use super::*;
+/// Represents running average calculation window.
+/// It is using specified window width that will consist of given number of accumulator buckets to ensure constant memory usage.
+/// Buckets are stored in `Vec` by default or in fixed size array (see `RunningAverage::new_const()`).
+/// Window can be narrower than one second (e.g. 100 ms with 10 ms buckets) as bucket time spans are computed in whole nanoseconds.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct RunningAverage<V: Accumulate, I: TimeInstant + Copy, S = Vec<V>> {
+    window: S,
+    head: usize,
+    front: Option<I>,
+    duration: Duration,
+    slot_duration: Duration,
+    slot_nanos: u128,
+    zero: fn() -> V,
+    backwards_policy: BackwardsPolicy,
+    #[cfg(any(test, feature = "testing"))]
+    previous_front: Option<I>,
+    value: PhantomData<V>,
+}
 
-        let mut tw = RealTimeRunningAverage::default();
+/// Represents running average calculation window.
+/// Without `std` feature there is no default bucket storage; buckets are stored in fixed size array or `heapless::Vec`.
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub struct RunningAverage<V: Accumulate, I: TimeInstant + Copy, S> {
+    window: S,
+    head: usize,
+    front: Option<I>,
+    duration: Duration,
+    slot_duration: Duration,
+    slot_nanos: u128,
+    zero: fn() -> V,
+    backwards_policy: BackwardsPolicy,
+    #[cfg(any(test, feature = "testing"))]
+    previous_front: Option<I>,
+    value: PhantomData<V>,
+}
 
-        tw.insert(10);
-        tw.insert(10);
+#[cfg(feature = "std")]
+impl<V: Accumulate, I: TimeInstant + Copy> Default for RunningAverage<V, I> {
+    /// Crate new RunningAverage instance with window of 8 seconds width and 16 buckets.
+    fn default() -> RunningAverage<V, I> {
+        RunningAverage::new(Duration::from_secs(8))
+    }
+}
 
-        assert_eq!(&format!("{}", tw.measurement()), "2.500");
+#[cfg(feature = "std")]
+impl<V: Accumulate, I: TimeInstant + Copy> RunningAverage<V, I> {
+    /// Crate new RunningAverage instance that will average over window of width of given duration using 16 buckets.
+    pub fn new(duration: Duration) -> RunningAverage<V, I> {
+        RunningAverage::with_capacity(duration, 16)
+    }
+
+    /// Crate new RunningAverage instance that will average over window of width of given duration with specific number of buckets to use.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it so that each bucket is one nanosecond wide.
+    pub fn with_capacity(duration: Duration, capacity: usize) -> RunningAverage<V, I> {
+        RunningAverage::with_capacity_and_init(duration, capacity, V::zero)
+    }
+
+    /// Crate new RunningAverage instance that will average over window of width of given duration with specific number of buckets to use
+    /// or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_with_capacity(duration: Duration, capacity: usize) -> Result<RunningAverage<V, I>, InvalidCapacity> {
+        validate_capacity(duration, capacity)?;
+        Ok(RunningAverage::with_capacity(duration, capacity))
+    }
+
+    /// Crate new RunningAverage instance that will average over window of width of given duration using buckets of about given width.
+    /// Number of buckets is window width divided by bucket width rounded up and clamped between 1 and 4096 buckets.
+    pub fn with_resolution(duration: Duration, bucket_duration: Duration) -> RunningAverage<V, I> {
+        RunningAverage::with_capacity(duration, resolution_capacity(duration, bucket_duration))
+    }
+
+    /// Crate new RunningAverage instance that will average over window of width of given duration with specific number of buckets to use
+    /// that are initialized and reset with value returned by given function instead of `Accumulate::zero()`.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn with_capacity_and_init(duration: Duration, capacity: usize, init: fn() -> V) -> RunningAverage<V, I> {
+        assert!(capacity > 0, "RunningAverage capacity cannot be 0");
+        let capacity = bucket_capacity(duration, capacity);
+        let slot_duration = slot_duration(duration, capacity);
+
+        RunningAverage {
+            window: (0..capacity).map(|_| init()).collect(),
+            head: 0,
+            front: None,
+            duration,
+            slot_duration,
+            slot_nanos: slot_duration.as_nanos(),
+            zero: init,
+            backwards_policy: BackwardsPolicy::Panic,
+            #[cfg(any(test, feature = "testing"))]
+            previous_front: None,
+            value: PhantomData,
+        }
+    }
+
+    /// Returns approximate number of bytes of memory used by this instance: its inline size and heap allocated buckets.
+    /// Note: heap memory owned by bucket values (e.g. sketches) is not accounted for.
+    pub fn memory_footprint(&self) -> usize {
+        core::mem::size_of::<Self>() + self.window.capacity() * core::mem::size_of::<V>()
+    }
+}
+
+impl<V: Accumulate + Copy, I: TimeInstant + Copy, const N: usize> RunningAverage<V, I, [V; N]> {
+    /// Crate new RunningAverage instance with N buckets stored in array, which can be done in `const` context (e.g. to initialize `static` item).
+    /// Buckets are initialized with given zero value that needs to be equal to `V::zero()`.
+    /// Panics if N is 0 or greater than number of nanoseconds in the window.
+    /// ```
+    /// use std::sync::Mutex;
+    /// use std::time::{Duration, Instant};
+    /// use running_average::RunningAverage;
+    ///
+    /// static TRANSFER: Mutex<RunningAverage<u32, Instant, [u32; 16]>> = Mutex::new(RunningAverage::new_const(Duration::from_secs(8), 0));
+    ///
+    /// TRANSFER.lock().unwrap().insert(Instant::now(), 2000);
+    /// assert_eq!(TRANSFER.lock().unwrap().measurement(Instant::now()).unwrap(), 2000);
+    /// ```
+    pub const fn new_const(duration: Duration, zero: V) -> RunningAverage<V, I, [V; N]> {
+        assert!(N > 0, "RunningAverage capacity cannot be 0");
+        assert!(N as u128 <= duration.as_nanos(), "RunningAverage capacity cannot be greater than number of nanoseconds in the window");
+        let slot_duration = slot_duration(duration, N);
+
+        RunningAverage {
+            window: [zero; N],
+            head: 0,
+            front: None,
+            duration,
+            slot_duration,
+            slot_nanos: slot_duration.as_nanos(),
+            zero: V::zero,
+            backwards_policy: BackwardsPolicy::Panic,
+            #[cfg(any(test, feature = "testing"))]
+            previous_front: None,
+            value: PhantomData,
+        }
+    }
+
+    /// Returns approximate number of bytes of memory used by this instance, which is its inline size as buckets are stored inline.
+    /// Note: heap memory owned by bucket values (e.g. sketches) is not accounted for.
+    pub fn memory_footprint(&self) -> usize {
+        core::mem::size_of::<Self>()
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<V: Accumulate, I: TimeInstant + Copy, const N: usize> RunningAverage<V, I, heapless::Vec<V, N>> {
+    /// Crate new RunningAverage instance with given number of buckets, up to N, stored in `heapless::Vec` without heap allocation (requires `heapless` feature).
+    /// Panics if capacity is 0 or greater than N; capacity greater than number of nanoseconds in the window is clamped to it.
+    /// This works without `std` feature on targets without `alloc` when used with custom `TimeInstant`, e.g. hardware timer ticks.
+    /// ```
+    /// use core::time::Duration;
+    /// use running_average::{RunningAverage, TimeInstant};
+    ///
+    /// // Timer counting milliseconds
+    /// #[derive(Clone, Copy)]
+    /// struct Ticks(u32);
+    ///
+    /// impl TimeInstant for Ticks {
+    ///     fn duration_since(&self, since: Ticks) -> Duration {
+    ///         Duration::from_millis(self.0.wrapping_sub(since.0) as u64)
+    ///     }
+    ///
+    ///     fn forward(&mut self, duration: Duration) {
+    ///         self.0 = self.0.wrapping_add(duration.as_millis() as u32);
+    ///     }
+    /// }
+    ///
+    /// let mut tw = RunningAverage::<u32, Ticks, heapless::Vec<u32, 16>>::with_heapless_capacity(Duration::from_secs(4), 4);
+    /// tw.insert(Ticks(0), 40);
+    /// assert_eq!(tw.measurement(Ticks(1500)).rate(), 10.0);
+    /// ```
+    pub fn with_heapless_capacity(duration: Duration, capacity: usize) -> RunningAverage<V, I, heapless::Vec<V, N>> {
+        assert!(capacity > 0, "RunningAverage capacity cannot be 0");
+        assert!(capacity <= N, "RunningAverage capacity cannot be greater than heapless::Vec capacity");
+        let capacity = bucket_capacity(duration, capacity);
+        let slot_duration = slot_duration(duration, capacity);
+
+        let mut window = heapless::Vec::new();
+        for _ in 0..capacity {
+            let _ = window.push(V::zero());
+        }
+
+        RunningAverage {
+            window,
+            head: 0,
+            front: None,
+            duration,
+            slot_duration,
+            slot_nanos: slot_duration.as_nanos(),
+            zero: V::zero,
+            backwards_policy: BackwardsPolicy::Panic,
+            #[cfg(any(test, feature = "testing"))]
+            previous_front: None,
+            value: PhantomData,
+        }
+    }
+
+    /// Returns approximate number of bytes of memory used by this instance, which is its inline size as buckets are stored inline.
+    /// Note: heap memory owned by bucket values (e.g. sketches) is not accounted for.
+    pub fn memory_footprint(&self) -> usize {
+        core::mem::size_of::<Self>()
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy, S: AsRef<[V]> + AsMut<[V]>> RunningAverage<V, I, S> {
+    fn shift(&mut self, now: I) {
+        #[cfg(any(test, feature = "testing"))] {
+            self.previous_front = self.front;
+        }
+        let zero = self.zero;
+        let front = self.front.get_or_insert(now);
+        let since_front = now.duration_since(*front).as_nanos();
+        let len = self.window.as_ref().len();
+
+        if since_front < self.slot_nanos {
+            return
+        }
+
+        let slots = since_front.checked_div(self.slot_nanos).unwrap_or(u128::MAX);
+
+        if slots < len as u128 {
+            // Rotate index of the most recent slot over expired slots zeroing them in place
+            let slots = slots as usize;
+            let start = self.head + 1;
+            let end = start + slots;
+
+            if end <= len {
+                self.window.as_mut()[start..end].iter_mut().for_each(|val| *val = zero());
+            } else {
+                self.window.as_mut()[start..].iter_mut().for_each(|val| *val = zero());
+                self.window.as_mut()[..end - len].iter_mut().for_each(|val| *val = zero());
+            }
+            self.head = (self.head + slots) % len;
+            front.forward(from_nanos(slots as u128 * self.slot_nanos));
+        } else {
+            self.window.as_mut().iter_mut().for_each(|val| *val = zero());
+
+            if slots == len as u128 {
+                front.forward(from_nanos(len as u128 * self.slot_nanos));
+            } else {
+                // Shift was not called for longer than the window width
+                front.forward(from_nanos(since_front));
+            }
+        }
+    }
+
+    /// Merge buckets of given running average into buckets of this one of the same age after shifting both to given time instant.
+    #[cfg(feature = "std")]
+    fn absorb<OS: AsRef<[V]> + AsMut<[V]>>(&mut self, now: I, other: &mut RunningAverage<V, I, OS>) {
+        self.shift(now);
+        other.shift(now);
+
+        let len = self.window.as_ref().len();
+        for (age, val) in other.recent_first().enumerate().take(len) {
+            let index = (self.head + len - age) % len;
+            let merged = self.window.as_ref()[index].merge(val);
+            self.window.as_mut()[index] = merged;
+        }
+    }
+
+    /// Iterate over slots starting with the most recent one.
+    fn recent_first(&self) -> impl DoubleEndedIterator<Item = &V> + Clone {
+        let (recent, oldest) = self.window.as_ref().split_at(self.head + 1);
+        recent.iter().rev().chain(oldest.iter().rev())
+    }
+    
+    /// Insert value to be average over at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn insert(&mut self, now: I, val: V) {
+        self.shift(now);
+        self.window.as_mut()[self.head].add(val);
+    }
+
+    /// Insert value to be average over at given time instant or return error without inserting if now is earlier than the start of the current bucket.
+    pub fn try_insert(&mut self, now: I, val: V) -> Result<(), TimeWentBackwards> {
+        if let Some(front) = self.front {
+            now.try_duration_since(front)?;
+        }
+        self.insert(now, val);
+        Ok(())
+    }
+
+    /// Insert value of other type to be accumulated into bucket at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn accumulate<A>(&mut self, now: I, val: A) where V: Accumulate<A> {
+        self.shift(now);
+        self.window.as_mut()[self.head].add(val);
+    }
+
+    /// Use given handling of time going backwards by `record()`.
+    pub fn with_backwards_policy(mut self, backwards_policy: BackwardsPolicy) -> RunningAverage<V, I, S> {
+        self.backwards_policy = backwards_policy;
+        self
+    }
+
+    /// Returns handling of time going backwards used by `record()`.
+    pub fn backwards_policy(&self) -> BackwardsPolicy {
+        self.backwards_policy
+    }
+
+    /// Insert value to be average over at given time instant handling now earlier than the start of the current bucket according to `backwards_policy()`:
+    /// panic, return error without inserting (as `try_insert()`) or insert into the current bucket.
+    pub fn record(&mut self, now: I, val: V) -> Result<(), TimeWentBackwards> {
+        let now = match self.front {
+            Some(front) => match (now.try_duration_since(front), self.backwards_policy) {
+                (Ok(_), _) => now,
+                (Err(err), BackwardsPolicy::Panic) => panic!("RunningAverage {}", err),
+                (Err(err), BackwardsPolicy::Error) => return Err(err),
+                (Err(_), BackwardsPolicy::Clamp) => front,
+            },
+            None => now,
+        };
+        self.insert(now, val);
+        Ok(())
+    }
+
+    /// Insert values with their time instants in order shifting the window only once per bucket;
+    /// consecutive values falling into the same bucket are accumulated together before being added to it.
+    /// Panics if now is less than previous now - time cannot go backwards
+    #[cfg(feature = "std")]
+    fn insert_batch(&mut self, values: impl IntoIterator<Item = (I, V)>) {
+        let mut pending: Option<V> = None;
+
+        for (now, val) in values {
+            let in_bucket = self.front.is_some_and(|front| now.duration_since(front).as_nanos() < self.slot_nanos);
+
+            match pending {
+                Some(ref mut pending) if in_bucket => pending.add(val),
+                _ => {
+                    if let Some(pending) = pending.take() {
+                        self.window.as_mut()[self.head].add(pending);
+                    }
+                    self.shift(now);
+                    pending = Some(val);
+                }
+            }
+        }
+
+        if let Some(pending) = pending {
+            self.window.as_mut()[self.head].add(pending);
+        }
+    }
+
+    /// Calculate running average using time window ending at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn measurement(&mut self, now: I) -> Measurement<V> {
+        self.shift(now);
+
+        Measurement {
+            value: self.window.as_ref().iter().fold((self.zero)(), |sum, val| sum.merge(val)),
+            duration: self.duration,
+        }
+    }
+
+    /// Create independent copy of this instance with the same buckets, e.g. to project how the measurement would change after inserting values
+    /// without disturbing the original.
+    pub fn fork(&self) -> RunningAverage<V, I, S> where S: Clone {
+        RunningAverage {
+            window: self.window.clone(),
+            head: self.head,
+            front: self.front,
+            duration: self.duration,
+            slot_duration: self.slot_duration,
+            slot_nanos: self.slot_nanos,
+            zero: self.zero,
+            backwards_policy: self.backwards_policy,
+            #[cfg(any(test, feature = "testing"))]
+            previous_front: self.previous_front,
+            value: PhantomData,
+        }
+    }
+
+    /// Calculate running average using time window ending at given time instant and reset all buckets,
+    /// so that next drain only returns values inserted after this one (e.g. for delta exporters).
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn drain(&mut self, now: I) -> Measurement<V> {
+        self.shift(now);
+        let zero = self.zero;
+
+        Measurement {
+            value: self.window.as_mut().iter_mut().fold(zero(), |sum, val| sum.merge(&core::mem::replace(val, zero()))),
+            duration: self.duration,
+        }
+    }
+
+    /// Calculate running average using time window ending at given time instant without shifting the window.
+    /// Buckets that expired by given time instant are skipped so the result is the same as of `measurement()`.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn peek(&self, now: I) -> Measurement<V> {
+        let expired = match self.front {
+            Some(front) => now.duration_since(front).as_nanos().checked_div(self.slot_nanos).unwrap_or(u128::MAX),
+            None => 0,
+        };
+        let live = (self.window.as_ref().len() as u128).saturating_sub(expired) as usize;
+
+        Measurement {
+            value: self.recent_first().take(live).fold((self.zero)(), |sum, val| sum.merge(val)),
+            duration: self.duration,
+        }
+    }
+
+    /// Return iterator over values accumulated in each bucket of time window ending at given time instant, starting with the most recent bucket.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn buckets<'i>(&'i mut self, now: I) -> impl Iterator<Item = &'i V> + 'i {
+        self.shift(now);
+        self.recent_first()
+    }
+
+    /// Render values accumulated in each bucket of time window ending at given time instant as Unicode block characters, oldest bucket first.
+    /// Blocks are scaled to the largest bucket value; negative values are rendered as the lowest block.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    #[cfg(feature = "std")]
+    pub fn sparkline(&mut self, now: I) -> String where V: Clone + Into<f64> {
+        self.shift(now);
+        sparkline(self.recent_first().rev().map(|v| v.clone().into()))
+    }
+
+    /// Returns width of the time span covered by single bucket.
+    pub fn bucket_duration(&self) -> Duration {
+        self.slot_duration
+    }
+
+    /// Validate internal consistency of the window at given time instant: bucket count and width, position of the most recent bucket,
+    /// window front not being ahead of given time instant nor behind its position before the last shift, `peek()` being the same as `measurement()`
+    /// and shifting to given time instant resetting expired buckets while keeping the others (requires `testing` feature).
+    /// Panics with description of the violated invariant.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn check_invariants(&self, now: I) where I: PartialOrd, S: Clone, V: PartialEq + fmt::Debug {
+        let len = self.window.as_ref().len();
+        assert!(len > 0, "RunningAverage has no buckets");
+        assert!(self.head < len, "RunningAverage most recent bucket index {} out of {} buckets", self.head, len);
+        assert_eq!(self.slot_nanos, self.slot_duration.as_nanos(), "RunningAverage bucket width mismatch");
+        assert_eq!(self.slot_nanos, (self.duration.as_nanos() / len as u128).max(1), "RunningAverage bucket width does not match window width over {} buckets", len);
+
+        if let Some(front) = self.front {
+            assert!(front <= now, "RunningAverage window front is ahead of now");
+            if let Some(previous_front) = self.previous_front {
+                assert!(previous_front <= front, "RunningAverage window front moved backwards");
+            }
+        }
+
+        let mut shifted = self.fork();
+        assert_eq!(self.peek(now).value, shifted.measurement(now).value, "RunningAverage peek differs from measurement");
+
+        let expired = match self.front {
+            Some(front) => now.duration_since(front).as_nanos().checked_div(self.slot_nanos).unwrap_or(u128::MAX).min(len as u128) as usize,
+            None => 0,
+        };
+        for (age, val) in shifted.recent_first().enumerate() {
+            if age < expired {
+                assert_eq!(*val, (self.zero)(), "RunningAverage expired bucket {} buckets old was not reset", age);
+            } else {
+                assert_eq!(Some(val), self.recent_first().nth(age - expired), "RunningAverage bucket {} buckets old was not kept by shift", age);
+            }
+        }
+    }
+
+    /// Calculate standard error of the mean of bucket rates (bucket value divided by width of its time span) of time window ending at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    #[cfg(feature = "std")]
+    pub fn standard_error(&mut self, now: I) -> f64 where V: Clone + Into<f64> {
+        self.shift(now);
+
+        let slot = dts(self.slot_duration);
+        let count = self.window.as_ref().len() as f64;
+        if count < 2.0 {
+            return 0.0
+        }
+
+        let rates = self.window.as_ref().iter().map(|v| v.clone().into() / slot);
+        let mean = rates.clone().sum::<f64>() / count;
+        let variance = rates.map(|rate| (rate - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        (variance / count).sqrt()
+    }
+
+    /// Calculate median of bucket rates (bucket value divided by width of its time span) of time window ending at given time instant,
+    /// so that single anomalous bucket (e.g. burst of retransmissions) does not skew the rate.
+    /// Note: for even number of buckets it is mean of the two middle bucket rates.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    #[cfg(feature = "std")]
+    pub fn robust_rate(&mut self, now: I) -> f64 where V: Clone + Into<f64> {
+        self.shift(now);
+
+        let slot = dts(self.slot_duration);
+        let mut rates = self.window.as_ref().iter().map(|v| v.clone().into() / slot).collect::<Vec<_>>();
+        rates.sort_by(f64::total_cmp);
+
+        let middle = rates.len() / 2;
+        if rates.len() % 2 == 0 {
+            (rates[middle - 1] + rates[middle]) / 2.0
+        } else {
+            rates[middle]
+        }
+    }
+
+    /// Forecast rate given horizon ahead of given time instant by fitting double exponential smoothing (Holt's linear trend method)
+    /// with default level and trend smoothing factors of 0.5 over bucket rates of time window ending at given time instant.
+    /// See `forecast_with_smoothing()`.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn forecast(&mut self, now: I, horizon: Duration) -> f64 where V: Clone + Into<f64> {
+        self.forecast_with_smoothing(now, horizon, 0.5, 0.5)
+    }
+
+    /// Forecast rate given horizon ahead of given time instant by fitting double exponential smoothing (Holt's linear trend method)
+    /// with given level and trend smoothing factors over bucket rates of time window ending at given time instant, oldest first.
+    /// Bigger factors make forecast follow recent buckets more closely.
+    /// Note: the current bucket is fitted too although it may be only partially filled.
+    /// Panics if smoothing factors are not between 0 and 1 or if now is less than previous now - time cannot go backwards.
+    pub fn forecast_with_smoothing(&mut self, now: I, horizon: Duration, level_smoothing: f64, trend_smoothing: f64) -> f64 where V: Clone + Into<f64> {
+        assert!((0.0..=1.0).contains(&level_smoothing), "RunningAverage forecast level smoothing factor needs to be between 0 and 1");
+        assert!((0.0..=1.0).contains(&trend_smoothing), "RunningAverage forecast trend smoothing factor needs to be between 0 and 1");
+        self.shift(now);
+
+        let slot = dts(self.slot_duration);
+        let mut rates = self.recent_first().rev().map(|v| v.clone().into() / slot);
+
+        let first = rates.next().unwrap_or_default();
+        let mut rates = rates.peekable();
+        let mut level = first;
+        let mut trend = rates.peek().map(|second| second - first).unwrap_or_default();
+
+        for rate in rates {
+            let last_level = level;
+            level = level_smoothing * rate + (1.0 - level_smoothing) * (level + trend);
+            trend = trend_smoothing * (level - last_level) + (1.0 - trend_smoothing) * trend;
+        }
+
+        level + trend * dts(horizon) / slot
+    }
+
+    /// Calculate least-squares slope of bucket rates of time window ending at given time instant against time, in rate units per second.
+    /// Returns 0 if there is only one bucket.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn trend_slope(&mut self, now: I) -> f64 where V: Clone + Into<f64> {
+        self.shift(now);
+
+        let slot = dts(self.slot_duration);
+        let count = self.window.as_ref().len() as f64;
+        if count < 2.0 {
+            return 0.0
+        }
+
+        let mean_time = (count - 1.0) / 2.0 * slot;
+        let rates = self.recent_first().rev().map(|v| v.clone().into() / slot).enumerate().map(|(i, rate)| (i as f64 * slot - mean_time, rate));
+        let mean_rate = rates.clone().map(|(_, rate)| rate).sum::<f64>() / count;
+
+        let covariance = rates.clone().map(|(time, rate)| time * (rate - mean_rate)).sum::<f64>();
+        let variance = rates.map(|(time, _)| time * time).sum::<f64>();
+        covariance / variance
+    }
+
+    /// Check if rate of the newest bucket of time window ending at given time instant deviates from mean of rates of the remaining buckets
+    /// by more than given number of their standard deviations (e.g. burst or dropout).
+    /// Returns false if there are less than two remaining buckets.
+    /// Note: the newest bucket may be only partially filled so it may be reported as dropout early in its time span.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    #[cfg(feature = "std")]
+    pub fn is_anomalous(&mut self, now: I, threshold_sigma: f64) -> bool where V: Clone + Into<f64> {
+        self.shift(now);
+
+        let slot = dts(self.slot_duration);
+        let count = self.window.as_ref().len() as f64 - 1.0;
+        if count < 2.0 {
+            return false
+        }
+
+        let mut rates = self.recent_first().map(|v| v.clone().into() / slot);
+        let newest = rates.next().expect("RunningAverage window with no buckets");
+        let mean = rates.clone().sum::<f64>() / count;
+        let std_dev = (rates.map(|rate| (rate - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt();
+
+        (newest - mean).abs() > threshold_sigma * std_dev
+    }
+
+    /// Classify trend of bucket rates of time window ending at given time instant as rising or falling if absolute value of `trend_slope()` exceeds given threshold.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn trend(&mut self, now: I, threshold: f64) -> Trend where V: Clone + Into<f64> {
+        Trend::classify(self.trend_slope(now), threshold)
+    }
+
+    /// Suggest how long after given time instant to wait before next insert so that rate measured over the time window drops to given target rate.
+    /// Returns zero duration if measured rate is not above the target rate; assumes nothing more is inserted while waiting.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn suggest_delay(&mut self, now: I, target_rate: f64) -> Duration where V: Clone + Into<f64> {
+        self.shift(now);
+
+        let mut excess = self.window.as_ref().iter().map(|v| v.clone().into()).sum::<f64>() - target_rate * dts(self.duration);
+        if excess <= 0.0 {
+            return Duration::from_secs(0)
+        }
+
+        // Oldest bucket leaves the window at the end of current bucket time span and every following bucket one span later
+        let slot_duration = self.bucket_duration();
+        let into_slot = self.front.map(|front| now.duration_since(front)).unwrap_or_default();
+        let mut delay = slot_duration.checked_sub(into_slot).unwrap_or_default();
+
+        for val in self.recent_first().rev() {
+            excess -= val.clone().into();
+            if excess <= 0.0 {
+                break
+            }
+            delay += slot_duration;
+        }
+        delay
+    }
+}
+
+/// Represents running average calculation window where `shift` and `measurement` are using given time source to obtain value of `now` instant.
+/// It is using specified window width that will consist of given number of accumulator buckets to ensure constant memory usage.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct RealTimeRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RunningAverage<V, TS::Instant>,
+    time_source: TS,
+    labels: std::sync::Arc<labels::Labels>,
+}
+
+#[cfg(feature = "std")]
+impl<V: Accumulate> Default for RealTimeRunningAverage<V, RealTimeSource> {
+    fn default() -> RealTimeRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::new(Duration::from_secs(8))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Accumulate> RealTimeRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    /// Note: new() is parametrizing output to RealTimeSource as this cannot be inferred otherwise.
+    pub fn new(duration: Duration) -> RealTimeRunningAverage<V, RealTimeSource> {
+        let time_source = RealTimeSource;
+
+        RealTimeRunningAverage {
+            inner: RunningAverage::new(duration),
+            time_source,
+            labels: Default::default(),
+        }
+    }
+
+    /// Crate new instance with window of given width duration with specific number of buckets to use and using RealTimeSource as time source for `now` instant.
+    /// See `RunningAverage::with_capacity()`.
+    pub fn with_capacity(duration: Duration, capacity: usize) -> RealTimeRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::with_time_source(duration, capacity, RealTimeSource)
+    }
+
+    /// Crate new instance with window of given width duration with specific number of buckets to use and using RealTimeSource as time source for `now` instant
+    /// or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_with_capacity(duration: Duration, capacity: usize) -> Result<RealTimeRunningAverage<V, RealTimeSource>, InvalidCapacity> {
+        RealTimeRunningAverage::try_with_time_source(duration, capacity, RealTimeSource)
+    }
+
+    /// Crate new instance with window of given width duration using buckets of about given width and using RealTimeSource as time source for `now` instant.
+    /// See `RunningAverage::with_resolution()`.
+    pub fn with_resolution(duration: Duration, bucket_duration: Duration) -> RealTimeRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::with_time_source(duration, resolution_capacity(duration, bucket_duration), RealTimeSource)
+    }
+
+    /// Crate new instance with window of given width duration with specific number of buckets to use that are initialized and reset with value returned by given function
+    /// and using RealTimeSource as time source for `now` instant.
+    pub fn with_capacity_and_init(duration: Duration, capacity: usize, init: fn() -> V) -> RealTimeRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage {
+            inner: RunningAverage::with_capacity_and_init(duration, capacity, init),
+            time_source: RealTimeSource,
+            labels: Default::default(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Accumulate, TS: TimeSource> RealTimeRunningAverage<V, TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> RealTimeRunningAverage<V, TS> {
+        RealTimeRunningAverage {
+            inner: RunningAverage::with_capacity(duration, capacity),
+            time_source,
+            labels: Default::default(),
+        }
+    }
+
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant
+    /// or return error if capacity is 0 or greater than number of nanoseconds in the window.
+    pub fn try_with_time_source(duration: Duration, capacity: usize, time_source: TS) -> Result<RealTimeRunningAverage<V, TS>, InvalidCapacity> {
+        validate_capacity(duration, capacity)?;
+        Ok(RealTimeRunningAverage::with_time_source(duration, capacity, time_source))
+    }
+
+    /// Crate new instance with window of 8 seconds width and 16 buckets using given as time source for `now` instant.
+    pub fn with_time_source_default(time_source: TS) -> RealTimeRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source_duration(Duration::from_secs(8), time_source)
+    }
+
+    /// Crate new instance with window of given width duration and 16 buckets using given as time source for `now` instant.
+    pub fn with_time_source_duration(duration: Duration, time_source: TS) -> RealTimeRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source(duration, 16, time_source)
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        let now = self.time_source.now();
+        self.inner.insert(now, val)
+    }
+
+    /// Insert value to be average over now or return error without inserting if time source time went backwards past the start of the current bucket.
+    pub fn try_insert(&mut self, val: V) -> Result<(), TimeWentBackwards> {
+        let now = self.time_source.now();
+        self.inner.try_insert(now, val)
+    }
+
+    /// Insert value of other type to be accumulated into bucket now.
+    /// Panics if time source time goes backwards.
+    pub fn accumulate<A>(&mut self, val: A) where V: Accumulate<A> {
+        let now = self.time_source.now();
+        self.inner.accumulate(now, val)
+    }
+
+    /// Use given handling of time source time going backwards by `record()`.
+    pub fn with_backwards_policy(mut self, backwards_policy: BackwardsPolicy) -> RealTimeRunningAverage<V, TS> {
+        self.inner.backwards_policy = backwards_policy;
+        self
+    }
+
+    /// Insert value to be average over now handling time source time going backwards past the start of the current bucket according to backwards policy.
+    /// See `RunningAverage::record()`.
+    pub fn record(&mut self, val: V) -> Result<(), TimeWentBackwards> {
+        let now = self.time_source.now();
+        self.inner.record(now, val)
+    }
+    
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        let now = self.time_source.now();
+        self.inner.measurement(now)
+    }
+
+    /// Create independent copy of this instance with the same buckets, name and labels and copy of the time source.
+    /// Note: copy of `ManualTimeSource` can be shifted independently of the original.
+    pub fn fork(&self) -> RealTimeRunningAverage<V, TS> where V: Clone, TS: Clone {
+        RealTimeRunningAverage {
+            inner: self.inner.fork(),
+            time_source: self.time_source.clone(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// Calculate running average using time window ending now and reset all buckets.
+    /// See `RunningAverage::drain()`.
+    /// Panics if time source time goes backwards.
+    pub fn drain(&mut self) -> Measurement<V> {
+        let now = self.time_source.now();
+        self.inner.drain(now)
+    }
+
+    /// Calculate running average using time window ending now without shifting the window.
+    /// Panics if time source time goes backwards.
+    pub fn peek(&self) -> Measurement<V> {
+        let now = self.time_source.now();
+        self.inner.peek(now)
+    }
+
+    /// Return iterator over values accumulated in each bucket of time window ending now, starting with the most recent bucket.
+    /// Panics if time source time goes backwards.
+    pub fn buckets<'i>(&'i mut self) -> impl Iterator<Item = &'i V> + 'i {
+        let now = self.time_source.now();
+        self.inner.buckets(now)
+    }
+
+    /// Render values accumulated in each bucket of time window ending now as Unicode block characters, oldest bucket first.
+    /// Blocks are scaled to the largest bucket value; negative values are rendered as the lowest block.
+    /// Panics if time source time goes backwards.
+    pub fn sparkline(&mut self) -> String where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.sparkline(now)
+    }
+
+    /// Returns width of the time span covered by single bucket.
+    pub fn bucket_duration(&self) -> Duration {
+        self.inner.bucket_duration()
+    }
+
+    /// Calculate standard error of the mean of bucket rates of time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn standard_error(&mut self) -> f64 where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.standard_error(now)
+    }
+
+    /// Calculate median of bucket rates of time window ending now.
+    /// See `RunningAverage::robust_rate()`.
+    /// Panics if time source time goes backwards.
+    pub fn robust_rate(&mut self) -> f64 where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.robust_rate(now)
+    }
+
+    /// Calculate least-squares slope of bucket rates of time window ending now against time, in rate units per second.
+    /// See `RunningAverage::trend_slope()`.
+    /// Panics if time source time goes backwards.
+    pub fn trend_slope(&mut self) -> f64 where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.trend_slope(now)
+    }
+
+    /// Check if rate of the newest bucket of time window ending now deviates from rates of the remaining buckets by more than given number of standard deviations.
+    /// See `RunningAverage::is_anomalous()`.
+    /// Panics if time source time goes backwards.
+    pub fn is_anomalous(&mut self, threshold_sigma: f64) -> bool where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.is_anomalous(now, threshold_sigma)
+    }
+
+    /// Classify trend of bucket rates of time window ending now given threshold of slope.
+    /// See `RunningAverage::trend()`.
+    /// Panics if time source time goes backwards.
+    pub fn trend(&mut self, threshold: f64) -> Trend where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.trend(now, threshold)
+    }
+
+    /// Forecast rate given horizon ahead of now by fitting double exponential smoothing over bucket rates of time window ending now.
+    /// See `RunningAverage::forecast()`.
+    /// Panics if time source time goes backwards.
+    pub fn forecast(&mut self, horizon: Duration) -> f64 where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.forecast(now, horizon)
+    }
+
+    /// Suggest how long to wait before next insert so that rate measured over the time window drops to given target rate.
+    /// Returns zero duration if measured rate is not above the target rate; assumes nothing more is inserted while waiting.
+    /// Panics if time source time goes backwards.
+    pub fn suggest_delay(&mut self, target_rate: f64) -> Duration where V: Clone + Into<f64> {
+        let now = self.time_source.now();
+        self.inner.suggest_delay(now, target_rate)
+    }
+
+    /// Suggest how many items to batch per flush so that flush intervals hit given target latency at the rate measured over time window ending now.
+    /// See `Measurement::suggest_batch_size()`.
+    /// Panics if time source time goes backwards.
+    pub fn suggest_batch_size(&mut self, target_latency: Duration) -> usize where V: Clone + Into<f64> {
+        self.measurement().suggest_batch_size(target_latency)
+    }
+
+    /// Returns approximate number of bytes of memory used by this instance: its inline size, heap allocated buckets and name and labels.
+    /// Note: heap memory owned by bucket values (e.g. sketches) or by time source is not accounted for.
+    pub fn memory_footprint(&self) -> usize {
+        core::mem::size_of::<Self>() - core::mem::size_of::<RunningAverage<V, TS::Instant>>() + self.inner.memory_footprint() + core::mem::size_of::<labels::Labels>() + self.labels.heap_footprint()
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+
+    /// Returns name and labels of this running average.
+    pub fn labels(&self) -> &labels::Labels {
+        &self.labels
+    }
+
+    /// Return mutable reference to name and labels of this running average.
+    /// Note: measurements taken before keep name and labels they were taken with.
+    pub fn labels_mut(&mut self) -> &mut labels::Labels {
+        std::sync::Arc::make_mut(&mut self.labels)
+    }
+}
+
+/// Types implementing this trait allow inserting values to be averaged over now.
+/// This allows instrumentation helpers to record into owned, borrowed or shared running averages alike.
+pub trait Insert {
+    /// Type of values inserted.
+    type Value;
+    /// Insert value to be average over now.
+    fn insert(&mut self, val: Self::Value);
+}
+
+#[cfg(feature = "std")]
+impl<V: Accumulate, TS: TimeSource> Insert for RealTimeRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        RealTimeRunningAverage::insert(self, val)
+    }
+}
+
+impl<T: Insert + ?Sized> Insert for &mut T {
+    type Value = T::Value;
+
+    fn insert(&mut self, val: T::Value) {
+        (**self).insert(val)
+    }
+}
+
+/// Adapter converting values with given closure before inserting them into wrapped target,
+/// so that call sites can record values in their natural unit while the running average accumulates the canonical one.
+/// ```
+/// use std::time::Duration;
+/// use running_average::{Insert, MappedRecorder, RealTimeRunningAverage};
+///
+/// const FRAME_BYTES: u32 = 1500;
+///
+/// let mut bytes = RealTimeRunningAverage::<u32>::new(Duration::from_secs(8));
+/// let mut frames = MappedRecorder::new(&mut bytes, |frames: u32| frames * FRAME_BYTES);
+///
+/// frames.insert(4);
+///
+/// // Note: this may fail as it is based on real time
+/// assert_eq!(bytes.measurement().unwrap(), 6000);
+/// ```
+pub struct MappedRecorder<R: Insert, A, F: FnMut(A) -> R::Value> {
+    target: R,
+    map: F,
+    value: PhantomData<fn(A)>,
+}
+
+impl<R: Insert, A, F: FnMut(A) -> R::Value> fmt::Debug for MappedRecorder<R, A, F> where R: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MappedRecorder").field("target", &self.target).finish()
+    }
+}
+
+impl<R: Insert, A, F: FnMut(A) -> R::Value> MappedRecorder<R, A, F> {
+    /// Crate new instance inserting values converted with given closure into given target.
+    pub fn new(target: R, map: F) -> MappedRecorder<R, A, F> {
+        MappedRecorder {
+            target,
+            map,
+            value: PhantomData,
+        }
+    }
+
+    /// Return mutable reference to the wrapped target.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.target
+    }
+
+    /// Return the wrapped target consuming self.
+    pub fn into_inner(self) -> R {
+        self.target
+    }
+}
+
+impl<R: Insert, A, F: FnMut(A) -> R::Value> Insert for MappedRecorder<R, A, F> {
+    type Value = A;
+
+    fn insert(&mut self, val: A) {
+        let val = (self.map)(val);
+        self.target.insert(val)
+    }
+}
+
+/// Types implementing this trait can be used to calculate `Measurement::rate()` from.
+/// It is implemented for primitive numbers that can be converted to `f64` without loss, for `Adder` of them and for `Duration` (rate being seconds per second, e.g. busy fraction).
+/// Note: This is not implemented for u64 as it cannot be converted precisely to f64 - use f64 instead for big numbers
+/// Note: Duration is converted to f64 seconds so it is not 100% precise for very long durations
+pub trait ToRate {
+    type Output;
+    fn to_rate(self, duration: Duration) -> Self::Output;
+}
+
+macro_rules! impl_to_rate {
+    ($($t:ty),*) => {$(
+        impl ToRate for $t {
+            type Output = f64;
+
+            fn to_rate(self, duration: Duration) -> f64 {
+                let v: f64 = self.into();
+                v / dts(duration)
+            }
+        }
+    )*}
+}
+
+impl_to_rate!(u8, u16, u32, i8, i16, i32, f32, f64);
+
+impl<T: Into<f64>> ToRate for Adder<T> {
+    type Output = f64;
+
+    fn to_rate(self, duration: Duration) -> f64 {
+        let v: f64 = self.into();
+        v / dts(duration)
+    }
+}
+
+impl ToRate for Duration {
+    type Output = f64;
+
+    fn to_rate(self, duration: Duration) -> f64 {
+        dts(self) / dts(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn const_over_different_capacity() {
+        use super::*;
+
+        for capacity in 1..31 {
+            let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), capacity, ManualTimeSource::new());
+
+            tw.insert(10);
+            tw.time_source().time_shift(1.0);
+            tw.insert(10);
+            tw.time_source().time_shift(1.0);
+            tw.insert(10);
+            tw.time_source().time_shift(1.0);
+            tw.insert(10);
+
+            assert_eq!(tw.measurement().unwrap(), 40, "for capacity {}: {:?}", capacity, tw);
+            assert_eq!(tw.measurement().to_rate(), 10.0, "for capacity {}: {:?}", capacity, tw);
+        }
+    }
+
+    #[test]
+    fn const_half_time_over_different_capacity() {
+        use super::*;
+
+        for capacity in 1..31 {
+            let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), capacity, ManualTimeSource::new());
+
+            tw.insert(10);
+            tw.time_source().time_shift(1.0);
+            tw.insert(10);
+            tw.time_source().time_shift(1.0);
+            tw.time_source().time_shift(1.0);
+
+            assert_eq!(tw.measurement().unwrap(), 20, "for capacity {}: {:?}", capacity, tw);
+            assert_eq!(tw.measurement().to_rate(), 5.0, "for capacity {}: {:?}", capacity, tw);
+        }
+    }
+
+    #[test]
+    fn default_int() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::default();
+
+        tw.insert(10);
+        tw.insert(10);
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(tw.measurement().unwrap(), 20, "default: {:?}", tw);
+        assert_eq!(tw.measurement().to_rate(), 2.5, "default: {:?}", tw);
+    }
+
+    #[test]
+    fn default_f64() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::default();
+
+        tw.insert(10f64);
+        tw.insert(10.0);
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(tw.measurement().unwrap(), 20.0, "default: {:?}", tw);
+        assert_eq!(tw.measurement().to_rate(), 2.5, "default: {:?}", tw);
+    }
+
+    #[test]
+    fn long_time_shift() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 16, ManualTimeSource::new());
+
+        tw.insert(10);
+        tw.time_source().time_shift(1_000_000_000.0);
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(10);
+
+        assert_eq!(tw.measurement().unwrap(), 40, "long: {:?}", tw);
+        assert_eq!(tw.measurement().to_rate(), 10.0, "long: {:?}", tw);
+    }
+
+    #[test]
+    fn buckets_most_recent_first() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(20);
+        tw.time_source().time_shift(2.0);
+
+        assert_eq!(tw.bucket_duration(), Duration::from_secs(1));
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![0, 0, 20, 10]);
+    }
+
+    #[test]
+    fn rotate_over_buffer_end() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        for val in 1..7 {
+            tw.insert(val);
+            tw.time_source().time_shift(1.0);
+        }
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![0, 6, 5, 4]);
+
+        tw.insert(7);
+        tw.time_source().time_shift(3.0);
+        tw.insert(8);
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![8, 0, 0, 7]);
+        assert_eq!(tw.measurement().unwrap(), 15);
+    }
+
+    #[test]
+    fn rate_confidence_interval() {
+        use super::*;
+
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+        assert!((normal_quantile(0.005) + 2.575829).abs() < 1e-6);
+
+        let (low, high) = Measurement { value: 100, duration: Duration::from_secs(10) }.confidence_interval(0.95);
+        assert!((low - 8.2226).abs() < 1e-3, "{}", low);
+        assert!((high - 12.1608).abs() < 1e-3, "{}", high);
+
+        let (low, high) = Measurement { value: 0, duration: Duration::from_secs(1) }.confidence_interval(0.95);
+        assert_eq!(low, 0.0);
+        assert!((high - 3.8415).abs() < 1e-3, "{}", high);
+    }
+
+    #[test]
+    fn bucket_rate_standard_error() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(8), 4, ManualTimeSource::new());
+        assert_eq!(tw.standard_error(), 0.0);
+
+        tw.insert(2);
+        for val in 2..5 {
+            tw.time_source().time_shift(2.0);
+            tw.insert(val * 2);
+        }
+
+        assert!((tw.standard_error() - 0.645497).abs() < 1e-6);
+
+        let mut single = RealTimeRunningAverage::with_time_source(Duration::from_secs(1), 1, ManualTimeSource::new());
+        single.insert(10);
+        assert_eq!(single.standard_error(), 0.0);
+    }
+
+    #[test]
+    fn median_bucket_rate() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.robust_rate(), 0.0);
+
+        tw.insert(1000);
+        for val in &[2, 4, 3] {
+            tw.time_source().time_shift(1.0);
+            tw.insert(*val);
+        }
+
+        assert_eq!(tw.measurement().to_rate(), 252.25);
+        assert_eq!(tw.robust_rate(), 3.5);
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(6), 3, ManualTimeSource::new());
+        tw.insert(2);
+        tw.time_source().time_shift(2.0);
+        tw.insert(1000);
+        assert_eq!(tw.robust_rate(), 1.0);
+    }
+
+    #[test]
+    fn forecast_ahead() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.forecast(Duration::from_secs(1)), 0.0);
+
+        tw.insert(1);
+        for val in 2..5 {
+            tw.time_source().time_shift(1.0);
+            tw.insert(val);
+        }
+
+        // Linear trend is followed exactly
+        assert_eq!(tw.forecast(Duration::from_secs(0)), 4.0);
+        assert_eq!(tw.forecast(Duration::from_secs(2)), 6.0);
+
+        let now = tw.time_source().now();
+        assert_eq!(tw.inner.forecast_with_smoothing(now, Duration::from_secs(1), 0.0, 0.0), 5.0);
+        // Full level smoothing with no trend update keeps initial trend
+        assert_eq!(tw.inner.forecast_with_smoothing(now, Duration::from_millis(500), 1.0, 0.0), 4.5);
+
+        let mut single = RealTimeRunningAverage::with_time_source(Duration::from_secs(1), 1, ManualTimeSource::new());
+        single.insert(10);
+        assert_eq!(single.forecast(Duration::from_secs(10)), 10.0);
+    }
+
+    #[test]
+    fn bucket_rate_trend() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(8), 4, ManualTimeSource::new());
+        assert_eq!(tw.trend_slope(), 0.0);
+        assert_eq!(tw.trend(0.0), Trend::Flat);
+
+        // Bucket rates of 1, 2, 3, 4 every 2 seconds
+        tw.insert(2);
+        for val in 2..5 {
+            tw.time_source().time_shift(2.0);
+            tw.insert(val * 2);
+        }
+
+        assert_eq!(tw.trend_slope(), 0.5);
+        assert_eq!(tw.trend(0.25), Trend::Rising);
+        assert_eq!(tw.trend(0.5), Trend::Flat);
+
+        tw.time_source().time_shift(6.0);
+        assert_eq!(tw.trend_slope(), -0.6);
+        assert_eq!(tw.trend(0.5), Trend::Falling);
+
+        let mut single = RealTimeRunningAverage::with_time_source(Duration::from_secs(1), 1, ManualTimeSource::new());
+        single.insert(10);
+        assert_eq!(single.trend_slope(), 0.0);
+    }
+
+    #[test]
+    fn newest_bucket_anomaly() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(5), 5, ManualTimeSource::new());
+        assert!(!tw.is_anomalous(3.0));
+
+        // Bucket rates of 9, 11, 9, 11 with standard deviation of about 1.15
+        tw.insert(9);
+        for val in &[11, 9, 11] {
+            tw.time_source().time_shift(1.0);
+            tw.insert(*val);
+        }
+        tw.time_source().time_shift(1.0);
+        tw.insert(12);
+        assert!(!tw.is_anomalous(3.0));
+        tw.insert(2);
+        assert!(tw.is_anomalous(3.0));
+        assert!(!tw.is_anomalous(4.0));
+
+        // Dropout
+        tw.time_source().time_shift(1.0);
+        assert!(tw.is_anomalous(3.0));
+
+        let mut short = RealTimeRunningAverage::with_time_source(Duration::from_secs(2), 2, ManualTimeSource::new());
+        short.insert(1000);
+        assert!(!short.is_anomalous(0.0));
+    }
+
+    #[test]
+    fn capacity_from_resolution() {
+        use super::*;
+
+        let tw = RunningAverage::<u32, f64>::with_resolution(Duration::from_secs(60), Duration::from_secs(5));
+        assert_eq!(tw.bucket_duration(), Duration::from_secs(5));
+
+        let tw = RunningAverage::<u32, f64>::with_resolution(Duration::from_secs(10), Duration::from_secs(3));
+        assert_eq!(tw.window.len(), 4);
+
+        let tw = RunningAverage::<u32, f64>::with_resolution(Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(tw.window.len(), 1);
+
+        let tw = RunningAverage::<u32, f64>::with_resolution(Duration::from_secs(86400), Duration::from_millis(1));
+        assert_eq!(tw.window.len(), 4096);
+
+        let tw = RunningAverage::<u32, f64>::with_resolution(Duration::from_secs(1), Duration::from_secs(0));
+        assert_eq!(tw.window.len(), 4096);
+
+        let tw = RealTimeRunningAverage::<u32>::with_resolution(Duration::from_secs(8), Duration::from_secs(1));
+        assert_eq!(tw.bucket_duration(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn sub_second_window() {
+        use super::*;
+
+        // 100ms window with 10ms buckets using nanosecond precise time instants
+        let start = Instant::now();
+        let mut tw = RunningAverage::<u32, Instant>::with_capacity(Duration::from_millis(100), 10);
+        assert_eq!(tw.bucket_duration(), Duration::from_millis(10));
+
+        for ms in 0..100 {
+            tw.insert(start + Duration::from_millis(ms), 1);
+        }
+        let now = start + Duration::from_millis(99);
+        assert_eq!(tw.measurement(now).unwrap(), 100);
+        assert_eq!(tw.measurement(now).to_rate(), 1000.0);
+        assert_eq!(tw.measurement(start + Duration::from_millis(109)).unwrap(), 90);
+        assert_eq!(tw.measurement(start + Duration::from_nanos(109_999_999)).unwrap(), 90);
+        assert_eq!(tw.measurement(start + Duration::from_millis(110)).unwrap(), 80);
+
+        // 1ms window with 3 buckets of 333333ns
+        let mut tw = RunningAverage::<u32, Instant>::with_capacity(Duration::from_millis(1), 3);
+        assert_eq!(tw.bucket_duration(), Duration::from_nanos(333_333));
+        tw.insert(start, 3);
+        assert_eq!(tw.measurement(start + Duration::from_nanos(999_998)).unwrap(), 3);
+        assert_eq!(tw.measurement(start + Duration::from_nanos(999_999)).unwrap(), 0);
+
+        // Manual time at 10ms steps
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_millis(100), 10, ManualTimeSource::new());
+        for _ in 0..100 {
+            tw.insert(1);
+            tw.time_source().time_shift(0.01);
+        }
+        // One insert per bucket and the current bucket is empty
+        assert_eq!(tw.measurement().unwrap(), 9);
+    }
+
+    #[test]
+    fn window_memory_footprint() {
+        use super::*;
+        use core::mem::size_of;
+
+        let tw = RunningAverage::<u64, f64>::with_capacity(Duration::from_secs(8), 100);
+        assert_eq!(tw.memory_footprint(), size_of::<RunningAverage<u64, f64>>() + 800);
+
+        let tw = RunningAverage::<u32, f64, [u32; 16]>::new_const(Duration::from_secs(8), 0);
+        assert_eq!(tw.memory_footprint(), size_of::<RunningAverage<u32, f64, [u32; 16]>>());
+        assert!(tw.memory_footprint() >= 64);
+
+        let mut tw = RealTimeRunningAverage::<f64, _>::with_time_source(Duration::from_secs(8), 16, ManualTimeSource::new());
+        let unlabeled = tw.memory_footprint();
+        assert_eq!(unlabeled, size_of::<RealTimeRunningAverage<f64, ManualTimeSource>>() + 128 + size_of::<labels::Labels>());
+
+        tw.labels_mut().set_name("egress");
+        assert!(tw.memory_footprint() >= unlabeled + 6);
+    }
+
+    #[test]
+    fn absorb_merges_buckets_by_age() {
+        use super::*;
+
+        let mut tw = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+        let mut other = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+        tw.insert(0.0, 1);
+        other.insert(2.0, 2);
+
+        tw.absorb(3.0, &mut other);
+        assert_eq!(tw.measurement(3.0).unwrap(), 3);
+        assert_eq!(tw.measurement(4.0).unwrap(), 2);
+        assert_eq!(tw.measurement(5.0).unwrap(), 2);
+        assert_eq!(tw.measurement(6.0).unwrap(), 0);
+        tw.check_invariants(6.0);
+    }
+
+    #[test]
+    fn drain_resets_buckets() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.drain().unwrap(), 0);
+
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(20);
+        assert_eq!(tw.drain().unwrap(), 30);
+        assert_eq!(tw.measurement().unwrap(), 0);
+
+        tw.insert(5);
+        tw.time_source().time_shift(0.5);
+        tw.insert(5);
+        let drained = tw.drain();
+        assert_eq!(drained.unwrap(), 10);
+        assert_eq!(tw.drain().to_rate(), 0.0);
+
+        // Bucket alignment is kept
+        tw.time_source().time_shift(0.5);
+        tw.insert(7);
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.measurement().unwrap(), 7);
+        tw.time_source().time_shift(1.0);
+        assert_eq!(tw.measurement().unwrap(), 0);
+    }
+
+    #[test]
+    fn fork_what_if() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        tw.labels_mut().set_name("egress");
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(20);
+
+        let mut what_if = tw.fork();
+        what_if.insert(100);
+        assert_eq!(what_if.measurement().unwrap(), 130);
+        assert_eq!(what_if.labels().name(), Some("egress"));
+        what_if.time_source().time_shift(3.0);
+        assert_eq!(what_if.measurement().unwrap(), 120);
+
+        assert_eq!(tw.measurement().unwrap(), 30);
+        tw.insert(1);
+        assert_eq!(tw.measurement().unwrap(), 31);
+
+        let mut tw = RunningAverage::<u32, f64, [u32; 4]>::new_const(Duration::from_secs(4), 0);
+        tw.insert(0.0, 5);
+        let mut fork = tw.fork();
+        fork.insert(0.0, 5);
+        assert_eq!(fork.measurement(0.0).unwrap(), 10);
+        assert_eq!(tw.measurement(0.0).unwrap(), 5);
+    }
+
+    #[test]
+    fn mapped_recorder() {
+        use super::*;
+        use super::shared::SharedRunningAverage;
+
+        let bytes = SharedRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let mut frames = MappedRecorder::new(bytes.clone(), |frames: u16| u32::from(frames) * 1500);
+
+        frames.insert(2);
+        frames.insert(1);
+        assert_eq!(bytes.measurement().unwrap(), 4500);
+
+        let mut seconds = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        {
+            let mut busy = MappedRecorder::new(&mut seconds, |busy: Duration| busy.as_secs_f64());
+            busy.insert(Duration::from_millis(500));
+            busy.get_mut().insert(1.5);
+        }
+        assert_eq!(seconds.measurement().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn measurement_arithmetic() {
+        use super::*;
+
+        let eth0 = Measurement { value: 40, duration: Duration::from_secs(4) };
+        let eth1 = Measurement { value: 10, duration: Duration::from_secs(4) };
+
+        assert_eq!((eth0.clone() + eth1.clone()).unwrap().rate(), 12.5);
+        assert_eq!((eth0.clone() - eth1).unwrap().unwrap(), 30);
+
+        let err = (eth0 + Measurement { value: 10, duration: Duration::from_secs(8) }).unwrap_err();
+        assert_eq!(err.left(), Duration::from_secs(4));
+        assert_eq!(err.right(), Duration::from_secs(8));
+        assert_eq!(err.to_string(), "measurements have different time window width: 4s and 8s");
+    }
+
+    #[test]
+    fn combine_measurements() {
+        use super::*;
+
+        let pooled = Measurement::combine(&[
+            Measurement { value: 40.0, duration: Duration::from_secs(4) },
+            Measurement { value: 20.0, duration: Duration::from_secs(8) },
+            Measurement { value: 0.0, duration: Duration::from_secs(8) },
+        ]);
+        assert_eq!(pooled.duration(), Duration::from_secs(20));
+        assert_eq!(pooled.rate(), 3.0);
+
+        assert!(Measurement::<f64>::combine(&[]).rate().is_nan());
+
+        // Accumulate types that cannot be summed
+        #[derive(Debug, Clone, PartialEq)]
+        struct Max(u32);
+
+        impl Accumulate for Max {
+            fn zero() -> Max {
+                Max(0)
+            }
+
+            fn add(&mut self, val: Max) {
+                self.0 = self.0.max(val.0)
+            }
+
+            fn merge(&self, other: &Max) -> Max {
+                Max(self.0.max(other.0))
+            }
+        }
+
+        let pooled = Measurement::combine(&[
+            Measurement { value: Max(3), duration: Duration::from_secs(4) },
+            Measurement { value: Max(7), duration: Duration::from_secs(4) },
+        ]);
+        assert_eq!(pooled.unwrap(), Max(7));
+    }
+
+    #[test]
+    fn rate_conversions() {
+        use super::*;
+
+        let measurement = Measurement { value: 30u32, duration: Duration::from_secs(4) };
+        assert_eq!(measurement.rate_f64(), 7.5);
+        assert_eq!(measurement.rate_u64_floor(), 7);
+        assert_eq!(f64::from(measurement), 7.5);
+
+        assert_eq!(Measurement { value: -30.0, duration: Duration::from_secs(4) }.rate_u64_floor(), 0);
+        assert_eq!(Measurement { value: 1.0, duration: Duration::from_secs(0) }.rate_u64_floor(), u64::MAX);
+        assert_eq!(Measurement { value: 0.0, duration: Duration::from_secs(0) }.rate_u64_floor(), 0);
+
+        assert_eq!(Measurement { value: 30u16, duration: Duration::from_secs(4) }.to_rate_f32(), 7.5f32);
+        assert_eq!(Measurement { value: 3.0f32, duration: Duration::from_millis(1500) }.to_rate_f32(), 2.0f32);
+
+        assert_eq!(Measurement { value: 30u32, duration: Duration::from_secs(4) }.rate_milli_u64(), 7500);
+        assert_eq!(Measurement { value: 10u8, duration: Duration::from_secs(3) }.rate_fixed_u64(100), 333);
+        assert_eq!(Measurement { value: u64::MAX, duration: Duration::from_millis(1) }.rate_milli_u64(), u64::MAX);
+        assert_eq!(Measurement { value: 1u32, duration: Duration::from_secs(0) }.rate_milli_u64(), u64::MAX);
+        assert_eq!(Measurement { value: 0u32, duration: Duration::from_secs(0) }.rate_milli_u64(), 0);
+    }
+
+    #[test]
+    fn real_time_with_capacity() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::<u32>::with_capacity(Duration::from_secs(4), 8);
+        assert_eq!(tw.bucket_duration(), Duration::from_millis(500));
+        assert_eq!(tw.buckets().count(), 8);
+    }
+
+    #[test]
+    fn time_source_with_default_configuration() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::<u32, _>::with_time_source_default(ManualTimeSource::new());
+        assert_eq!(tw.measurement().duration(), Duration::from_secs(8));
+        assert_eq!(tw.buckets().count(), 16);
+
+        let mut tw = RealTimeRunningAverage::<u32, _>::with_time_source_duration(Duration::from_secs(4), ManualTimeSource::new());
+        assert_eq!(tw.measurement().duration(), Duration::from_secs(4));
+        assert_eq!(tw.bucket_duration(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn manual_time_source_set() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::default());
+        tw.insert(10);
+
+        tw.time_source().set(3.5);
+        assert_eq!(tw.time_source().now_seconds(), 3.5);
+        tw.insert(20);
+        assert_eq!(tw.measurement().unwrap(), 30);
+
+        tw.time_source().set(10.0);
+        tw.time_source().time_shift(0.5);
+        assert_eq!(tw.time_source().now_seconds(), 10.5);
+        assert_eq!(tw.measurement().unwrap(), 0);
+    }
+
+    #[test]
+    fn const_array_buckets() {
+        use super::*;
+
+        const WINDOW: RunningAverage<u32, f64, [u32; 4]> = RunningAverage::new_const(Duration::from_secs(4), 0);
+        let mut array = WINDOW;
+        let mut vec = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+        assert_eq!(array.bucket_duration(), vec.bucket_duration());
+
+        for (now, val) in [(0.0, 1), (1.5, 2), (2.0, 3), (5.5, 4), (6.0, 5)] {
+            array.insert(now, val);
+            vec.insert(now, val);
+            assert_eq!(array.buckets(now).collect::<Vec<_>>(), vec.buckets(now).collect::<Vec<_>>());
+        }
+        assert_eq!(array.measurement(6.0).unwrap(), 9);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_buckets() {
+        use super::*;
+
+        let mut tw = RunningAverage::<u32, f64, heapless::Vec<u32, 16>>::with_heapless_capacity(Duration::from_secs(4), 4);
+        assert_eq!(tw.bucket_duration(), Duration::from_secs(1));
+
+        tw.insert(0.0, 10);
+        tw.insert(1.0, 20);
+        tw.insert(4.0, 30);
+        assert_eq!(tw.buckets(4.0).cloned().collect::<Vec<_>>(), vec![30, 0, 0, 20]);
+        assert_eq!(tw.measurement(4.0).unwrap(), 50);
+    }
+
+    #[test]
+    fn peek_matches_measurement() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.peek().unwrap(), 0);
+
+        for val in 1..5 {
+            tw.insert(val);
+            tw.time_source().time_shift(1.0);
+        }
+
+        for _ in 0..5 {
+            let peeked = tw.peek().unwrap();
+            assert_eq!(peeked, tw.measurement().unwrap());
+            tw.time_source().time_shift(1.0);
+        }
+        assert_eq!(tw.peek().unwrap(), 0);
+    }
+
+    #[test]
+    fn sub_second_time_shift() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(2), 4, ManualTimeSource::new());
+
+        tw.insert(10);
+        tw.time_source().time_shift(0.5);
+        tw.insert(20);
+        tw.time_source().time_shift(0.75);
+        tw.insert(30);
+
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![30, 20, 10, 0]);
+        tw.time_source().time_shift(0.25);
+        assert_eq!(tw.buckets().cloned().collect::<Vec<_>>(), vec![0, 30, 20, 10]);
+    }
+
+    #[test]
+    fn suggest_delay_to_target_rate() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.insert(20);
+        tw.time_source().time_shift(1.0);
+        tw.insert(20);
+        tw.time_source().time_shift(1.0);
+        tw.insert(20);
+
+        assert_eq!(tw.suggest_delay(20.0), Duration::from_secs(0));
+        assert_eq!(tw.suggest_delay(10.0), Duration::from_secs(2));
+        assert_eq!(tw.suggest_delay(5.0), Duration::from_secs(3));
+        assert_eq!(tw.suggest_delay(0.0), Duration::from_secs(4));
+
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.suggest_delay(5.0), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn suggest_batch_size_for_target_latency() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(tw.suggest_batch_size(Duration::from_millis(100)), 1);
+
+        tw.insert(4000);
+        assert_eq!(tw.suggest_batch_size(Duration::from_millis(100)), 100);
+        assert_eq!(tw.suggest_batch_size(Duration::from_millis(250)), 250);
+        assert_eq!(tw.suggest_batch_size(Duration::from_micros(100)), 1);
+
+        let measurement = Measurement { value: f64::INFINITY, duration: Duration::from_secs(1) };
+        assert_eq!(measurement.suggest_batch_size(Duration::from_secs(1)), usize::MAX);
+    }
+
+    #[test]
+    fn duration_values() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.insert(Duration::from_millis(500));
+        tw.time_source().time_shift(1.0);
+        tw.insert(Duration::from_millis(1500));
+
+        assert_eq!(tw.measurement().unwrap(), Duration::from_secs(2));
+        assert_eq!(tw.measurement().rate(), 0.5);
+
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.measurement().to_rate(), 0.375);
+    }
+
+    #[test]
+    fn sparkline_oldest_first() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert_eq!(&tw.sparkline(), "\u{2581}\u{2581}\u{2581}\u{2581}");
+
+        tw.insert(70);
+        tw.time_source().time_shift(1.0);
+        tw.insert(10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(-10);
+        tw.time_source().time_shift(1.0);
+        tw.insert(35);
+
+        assert_eq!(&tw.sparkline(), "\u{2588}\u{2582}\u{2581}\u{2585}");
+    }
+
+    #[test]
+    fn measurement_display() {
+        use super::*;
+
+        let mut tw = RealTimeRunningAverage::default();
+
+        tw.insert(10);
+        tw.insert(10);
+
+        assert_eq!(&format!("{}", tw.measurement()), "2.500");
+        assert_eq!(&format!("{:#}", tw.measurement()), "2.500 (20 over 8s)");
+        assert_eq!(&format!("{:#}", Measurement { value: 1.5, duration: Duration::from_millis(500) }), "3.000 (1.5 over 500ms)");
+
+        assert_eq!(&format!("[{:8}]", tw.measurement()), "[   2.500]");
+        assert_eq!(&format!("[{:<8.1}]", tw.measurement()), "[2.5     ]");
+        assert_eq!(&format!("[{:*^9}]", tw.measurement()), "[**2.500**]");
+        assert_eq!(&format!("[{:>#20}]", tw.measurement()), "[  2.500 (20 over 8s)]");
+        assert_eq!(&format!("[{:2}]", tw.measurement()), "[2.500]");
+    }
+
+    #[test]
+    fn custom_accumulator() {
+        use super::*;
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Max(u32);
+
+        impl Accumulate<u32> for Max {
+            fn zero() -> Max {
+                Max(0)
+            }
+
+            fn add(&mut self, val: u32) {
+                self.0 = self.0.max(val)
+            }
+
+            fn merge(&self, other: &Max) -> Max {
+                Max(self.0.max(other.0))
+            }
+        }
+
+        impl Accumulate for Max {
+            fn zero() -> Max {
+                Max(0)
+            }
+
+            fn add(&mut self, val: Max) {
+                self.0 = self.0.max(val.0)
+            }
+
+            fn merge(&self, other: &Max) -> Max {
+                Max(self.0.max(other.0))
+            }
+        }
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.accumulate(10);
+        tw.accumulate(30);
+        tw.time_source().time_shift(1.0);
+        tw.insert(Max(20));
+        assert_eq!(tw.measurement().unwrap(), Max(30));
+
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.measurement().unwrap(), Max(20));
+    }
+
+    #[test]
+    fn init_buckets() {
+        use super::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static INITS: AtomicUsize = AtomicUsize::new(0);
+
+        fn init() -> u32 {
+            INITS.fetch_add(1, Ordering::Relaxed);
+            0
+        }
+
+        let mut tw = RealTimeRunningAverage::with_capacity_and_init(Duration::from_secs(4), 4, init);
+        assert_eq!(INITS.load(Ordering::Relaxed), 4);
+
+        tw.insert(10);
+        assert_eq!(tw.inner.measurement(Instant::now() + Duration::from_secs(1)).unwrap(), 10);
+        assert_eq!(INITS.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn adder_values() {
+        use super::*;
+
+        #[derive(Debug, Default, Clone, PartialEq)]
+        struct Money(u64);
+
+        impl Add for Money {
+            type Output = Money;
+
+            fn add(self, other: Money) -> Money {
+                Money(self.0 + other.0)
+            }
+        }
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        tw.insert(Adder(Money(10)));
+        tw.time_source().time_shift(1.0);
+        tw.accumulate(Money(20));
+        assert_eq!(tw.measurement().unwrap(), Adder(Money(30)));
+
+        tw.time_source().time_shift(3.0);
+        assert_eq!(tw.measurement().unwrap(), Adder(Money(20)));
+    }
+
+    #[test]
+    fn capacity_validation() {
+        use super::*;
+
+        assert_eq!(RunningAverage::<u32, f64>::try_with_capacity(Duration::from_secs(1), 0).unwrap_err().capacity(), 0);
+        assert!(RunningAverage::<u32, f64>::try_with_capacity(Duration::from_secs(0), 1).is_err());
+        assert!(RunningAverage::<u32, f64>::try_with_capacity(Duration::from_nanos(10), 11).is_err());
+        // Would be truncated if bucket width was computed with capacity cast to u32
+        let err = RealTimeRunningAverage::<u32>::try_with_capacity(Duration::from_secs(1), u32::MAX as usize + 2).unwrap_err();
+        assert_eq!(err.duration(), Duration::from_secs(1));
+        assert!(err.to_string().starts_with("invalid capacity of 4294967297 buckets for time window of 1s width"));
+
+        let mut tw = RunningAverage::<u32, f64>::try_with_capacity(Duration::from_nanos(10), 10).unwrap();
+        assert_eq!(tw.bucket_duration(), Duration::from_nanos(1));
+        tw.insert(0.0, 1);
+        assert_eq!(tw.measurement(0.0).unwrap(), 1);
+
+        // Capacity is clamped to number of nanoseconds so that buckets of one nanosecond do not span more than the window
+        let mut tw = RunningAverage::<u32, f64>::with_capacity(Duration::from_nanos(2), 4);
+        assert_eq!(tw.bucket_duration(), Duration::from_nanos(1));
+        assert_eq!(tw.buckets(0.0).count(), 2);
+        tw.insert(0.0, 1);
+        tw.insert(1e-9, 1);
+        assert_eq!(tw.measurement(1e-9).unwrap(), 2);
+        tw.insert(2e-9, 1);
+        assert_eq!(tw.measurement(2e-9).unwrap(), 2);
+        assert_eq!(tw.measurement(2e-9).duration(), Duration::from_nanos(2));
+        assert_eq!(tw.measurement(1.0).unwrap(), 0);
+        tw.check_invariants(1.0);
+
+        let tw = RunningAverage::<u32, f64>::with_capacity(Duration::from_nanos(0), 4);
+        assert_eq!(tw.window.len(), 1);
+
+        let mut tw = RealTimeRunningAverage::<u32, _>::try_with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()).unwrap();
+        tw.insert(4);
+        assert_eq!(tw.measurement().rate(), 1.0);
+    }
+
+    #[test]
+    fn invariants_violations() {
+        use super::*;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut tw = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+        tw.insert(1.0, 1);
+        tw.insert(2.0, 2);
+        tw.check_invariants(3.0);
+        tw.check_invariants(8.0);
+
+        let mut corrupted = tw.fork();
+        corrupted.front = Some(0.0);
+        assert!(catch_unwind(AssertUnwindSafe(|| corrupted.check_invariants(3.0))).is_err());
+    }
+
+    #[test]
+    fn try_insert_backwards() {
+        use super::*;
+
+        let mut tw = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+        assert_eq!(tw.try_insert(0.0, 1), Ok(()));
+        assert_eq!(tw.try_insert(2.5, 1), Ok(()));
+        // Within the current bucket
+        assert_eq!(tw.try_insert(2.2, 1), Ok(()));
+        assert_eq!(tw.try_insert(1.5, 1), Err(TimeWentBackwards));
+        assert_eq!(tw.measurement(2.5).unwrap(), 3);
+
+        let mut tw = RealTimeRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        tw.time_source().time_shift(2.0);
+        assert_eq!(tw.try_insert(1), Ok(()));
+        tw.time_source().time_shift(-1.0);
+        assert_eq!(tw.try_insert(1), Err(TimeWentBackwards));
+        assert_eq!(TimeWentBackwards.to_string(), "time instant is earlier than the previous one - time went backwards");
+
+        let now = Instant::now();
+        assert_eq!(now.try_duration_since(now + Duration::from_secs(1)), Err(TimeWentBackwards));
+    }
+
+    #[test]
+    fn duration_conversion_extremes() {
+        use super::*;
+
+        assert_eq!(std(0.3), Duration::from_millis(300));
+        assert_eq!(std(1e-10), Duration::from_secs(0));
+        assert_eq!(std(2.9999999999), Duration::from_secs(3));
+        assert_eq!(std(1e30), Duration::MAX);
+        assert_eq!(std(f64::INFINITY), Duration::MAX);
+        assert_eq!(try_std(-0.1), Err(TimeWentBackwards));
+        assert_eq!(try_std(0.3), Ok(Duration::from_millis(300)));
+        assert_eq!(from_nanos(u128::MAX), Duration::MAX);
+        assert_eq!(from_nanos(Duration::MAX.as_nanos()), Duration::MAX);
+        assert_eq!(dts(Duration::MAX), u64::MAX as f64 + 1.0);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn from_nanos_matches_duration(secs in 0..=u64::MAX, nanos in 0..1_000_000_000u32) {
+            use super::*;
+
+            let duration = Duration::new(secs, nanos);
+            proptest::prop_assert_eq!(from_nanos(duration.as_nanos()), duration);
+        }
+
+        #[test]
+        fn std_round_trips_dts(secs in 0..(1u64 << 20), nanos in 0..1_000_000_000u32) {
+            use super::*;
+
+            let duration = Duration::new(secs, nanos);
+            proptest::prop_assert_eq!(std(dts(duration)), duration);
+        }
+
+        #[test]
+        fn std_saturates(seconds in 0.0..f64::MAX) {
+            use super::*;
+
+            let duration = std(seconds);
+            proptest::prop_assert!(seconds < 1.8e19 || duration == Duration::MAX);
+            proptest::prop_assert!((dts(duration) - seconds.min(dts(Duration::MAX))).abs() <= seconds.max(1.0) * 1e-9);
+        }
     }
 }
@@ -0,0 +1,362 @@
+//! Rate limiting based on rate measured over time window.
+//!
+//! `RateLimiter` both measures throughput and tells if more can be done now without exceeding target rate.
+//! ```
+//! use std::thread::sleep;
+//! use std::time::Duration;
+//! use running_average::limiter::{RateLimiter, Decision};
+//!
+//! let mut limiter = RateLimiter::<u32>::new(Duration::from_secs(8));
+//!
+//! // Send up to 1KB/s
+//! for _ in 0..4 {
+//!     match limiter.check(1000.0) {
+//!         Decision::Allow => limiter.insert(100),
+//!         Decision::Delay(delay) => sleep(delay),
+//!     }
+//! }
+//! ```
+//!
+//! `Backpressure` advises producers whether to keep reading from upstream given the measured rate; it uses hysteresis so that the advice does not flap around the target rate.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::limiter::{Backpressure, Advice};
+//!
+//! let mut tw = RealTimeRunningAverage::<u32>::new(Duration::from_secs(8));
+//! let mut backpressure = Backpressure::new(1000.0);
+//!
+//! tw.insert(100);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(backpressure.advise(&tw.measurement()), Advice::Proceed);
+//! ```
+//!
+//! `RateController` is PID controller using measured rate as process variable for closed-loop throttling.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::limiter::RateController;
+//!
+//! let mut tw = RealTimeRunningAverage::<f64>::new(Duration::from_secs(8));
+//! // Keep rate at 100/s releasing between 0 and 50 tokens per round
+//! let mut controller = RateController::new(100.0, 0.5, 0.1, 0.0).limits(0.0, 50.0);
+//!
+//! for _ in 0..4 {
+//!     let tokens = controller.update(&tw.measurement(), Duration::from_millis(100));
+//!     tw.insert(tokens.floor());
+//! }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+
+/// Result of checking rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Measured rate is within the target rate.
+    Allow,
+    /// Measured rate exceeds the target rate; it will drop to the target rate after given delay if nothing more is inserted.
+    Delay(Duration),
+}
+
+/// Advice on whether producer should keep going given measured rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// Measured rate is within the target rate.
+    Proceed,
+    /// Measured rate exceeds the target rate; it will drop to the resume rate after given delay if nothing more is inserted.
+    SlowDown(Duration),
+    /// Measured rate reached the stop rate.
+    Stop,
+}
+
+/// Backpressure advisor comparing measured rate against target rate with hysteresis.
+///
+/// Advice is `SlowDown` once rate exceeds the target rate and `Stop` once it reaches the stop rate.
+/// After `Stop` the advice stays `Stop` until rate drops to the target rate and after `SlowDown` or `Stop` it is not `Proceed` until rate drops to the resume rate.
+#[derive(Debug, Clone)]
+pub struct Backpressure {
+    target_rate: f64,
+    resume_rate: f64,
+    stop_rate: f64,
+    last: Advice,
+}
+
+impl Backpressure {
+    /// Crate new instance with given target rate, resume rate of 90% and stop rate of 200% of the target rate.
+    pub fn new(target_rate: f64) -> Backpressure {
+        Backpressure::with_thresholds(target_rate, target_rate * 0.9, target_rate * 2.0)
+    }
+
+    /// Crate new instance with given target, resume and stop rates.
+    /// Panics if resume rate is greater than target rate or stop rate is lower than target rate.
+    pub fn with_thresholds(target_rate: f64, resume_rate: f64, stop_rate: f64) -> Backpressure {
+        assert!(resume_rate <= target_rate, "Backpressure resume rate cannot be greater than target rate");
+        assert!(stop_rate >= target_rate, "Backpressure stop rate cannot be lower than target rate");
+
+        Backpressure {
+            target_rate,
+            resume_rate,
+            stop_rate,
+            last: Advice::Proceed,
+        }
+    }
+
+    /// Advise based on given measurement taking previous advice into account.
+    pub fn advise<V: Clone + Into<f64>>(&mut self, measurement: &Measurement<V>) -> Advice {
+        let rate = measurement.rate_f64();
+
+        self.last = if rate >= self.stop_rate || (self.last == Advice::Stop && rate > self.target_rate) {
+            Advice::Stop
+        } else if rate > self.target_rate || (self.last != Advice::Proceed && rate > self.resume_rate) {
+            // Assuming values are spread evenly over the window
+            Advice::SlowDown(measurement.duration().mul_f64(1.0 - self.resume_rate / rate))
+        } else {
+            Advice::Proceed
+        };
+        self.last
+    }
+
+    /// Returns the last advice given.
+    pub fn last(&self) -> Advice {
+        self.last
+    }
+}
+
+/// PID controller driving rate measured over time window towards setpoint rate.
+///
+/// Control signal is `kp * error + ki * integral of error + kd * derivative of error` where error is setpoint rate minus measured rate,
+/// so it is positive when more should be done (e.g. tokens to release) and negative when measured rate is above the setpoint (e.g. delay to apply).
+/// When control signal is limited the error is not integrated while it would push the signal further beyond the limits.
+#[derive(Debug, Clone)]
+pub struct RateController {
+    setpoint: f64,
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    min: f64,
+    max: f64,
+    integral: f64,
+    last_error: Option<f64>,
+}
+
+impl RateController {
+    /// Crate new instance with given setpoint rate and proportional, integral and derivative gains.
+    pub fn new(setpoint: f64, kp: f64, ki: f64, kd: f64) -> RateController {
+        RateController {
+            setpoint,
+            kp,
+            ki,
+            kd,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+            integral: 0.0,
+            last_error: None,
+        }
+    }
+
+    /// Limit control signal to given range.
+    /// Panics if min is greater than max.
+    pub fn limits(mut self, min: f64, max: f64) -> RateController {
+        assert!(min <= max, "RateController min limit cannot be greater than max limit");
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Change setpoint rate keeping state of the controller.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        self.setpoint = setpoint
+    }
+
+    /// Returns setpoint rate.
+    pub fn setpoint(&self) -> f64 {
+        self.setpoint
+    }
+
+    /// Calculate control signal given measurement and time elapsed since previous update.
+    /// Derivative term is 0 on first update or if elapsed time is zero.
+    pub fn update<V: Clone + Into<f64>>(&mut self, measurement: &Measurement<V>, elapsed: Duration) -> f64 {
+        let error = self.setpoint - measurement.rate_f64();
+        let elapsed = elapsed.as_secs_f64();
+
+        let derivative = match self.last_error {
+            Some(last_error) if elapsed > 0.0 => (error - last_error) / elapsed,
+            _ => 0.0,
+        };
+        self.last_error = Some(error);
+
+        let integral = self.integral + error * elapsed;
+        let output = self.kp * error + self.ki * integral + self.kd * derivative;
+
+        if output > self.max {
+            if error < 0.0 {
+                self.integral = integral;
+            }
+            self.max
+        } else if output < self.min {
+            if error > 0.0 {
+                self.integral = integral;
+            }
+            self.min
+        } else {
+            self.integral = integral;
+            output
+        }
+    }
+
+    /// Reset integral and derivative state.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_error = None;
+    }
+}
+
+/// Running average that can be checked against target rate before inserting.
+pub struct RateLimiter<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for RateLimiter<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter").field("inner", &self.inner).finish()
+    }
+}
+
+impl<V: Accumulate> Default for RateLimiter<V, RealTimeSource> {
+    /// Crate new RateLimiter instance with window of 8 seconds width and 16 buckets.
+    fn default() -> RateLimiter<V, RealTimeSource> {
+        RealTimeRunningAverage::default().into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> From<RealTimeRunningAverage<V, TS>> for RateLimiter<V, TS> {
+    fn from(running_average: RealTimeRunningAverage<V, TS>) -> RateLimiter<V, TS> {
+        RateLimiter {
+            inner: running_average,
+        }
+    }
+}
+
+impl<V: Accumulate> RateLimiter<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> RateLimiter<V, RealTimeSource> {
+        RealTimeRunningAverage::new(duration).into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> RateLimiter<V, TS> {
+    /// Check rate measured over time window ending now against given target rate.
+    /// Panics if time source time goes backwards.
+    pub fn check(&mut self, target_rate: f64) -> Decision where V: Clone + Into<f64> {
+        match self.inner.suggest_delay(target_rate) {
+            delay if delay == Duration::from_secs(0) => Decision::Allow,
+            delay => Decision::Delay(delay),
+        }
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        self.inner.insert(val)
+    }
+
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        self.inner.measurement()
+    }
+
+    /// Return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        &mut self.inner
+    }
+
+    /// Return the underlying running average consuming self.
+    pub fn into_inner(self) -> RealTimeRunningAverage<V, TS> {
+        self.inner
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for RateLimiter<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        RateLimiter::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn delay_until_rate_drops() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut limiter = RateLimiter::from(RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()));
+
+        assert_eq!(limiter.check(10.0), Decision::Allow);
+        limiter.insert(30);
+        assert_eq!(limiter.check(10.0), Decision::Allow);
+
+        limiter.get_mut().time_source().time_shift(1.0);
+        limiter.insert(20);
+
+        // 50 in window; 10 needs to go for 10/s which happens when bucket with 30 leaves the window
+        assert_eq!(limiter.check(10.0), Decision::Delay(Duration::from_secs(3)));
+        // Both buckets need to go
+        assert_eq!(limiter.check(1.0), Decision::Delay(Duration::from_secs(4)));
+
+        limiter.get_mut().time_source().time_shift(3.0);
+        assert_eq!(limiter.check(10.0), Decision::Allow);
+        assert_eq!(limiter.check(1.0), Decision::Delay(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn backpressure_hysteresis() {
+        use super::*;
+
+        let measurement = |value: f64| Measurement { value, duration: Duration::from_secs(4) };
+        let mut backpressure = Backpressure::with_thresholds(10.0, 5.0, 30.0);
+
+        assert_eq!(backpressure.advise(&measurement(40.0)), Advice::Proceed);
+        assert_eq!(backpressure.advise(&measurement(80.0)), Advice::SlowDown(Duration::from_secs(3)));
+        // Below target but above resume rate
+        assert_eq!(backpressure.advise(&measurement(32.0)), Advice::SlowDown(Duration::from_millis(1500)));
+        assert_eq!(backpressure.advise(&measurement(20.0)), Advice::Proceed);
+        assert_eq!(backpressure.advise(&measurement(32.0)), Advice::Proceed);
+
+        assert_eq!(backpressure.advise(&measurement(120.0)), Advice::Stop);
+        // Below stop but above target rate
+        assert_eq!(backpressure.advise(&measurement(60.0)), Advice::Stop);
+        assert_eq!(backpressure.advise(&measurement(40.0)), Advice::SlowDown(Duration::from_secs(2)));
+        assert_eq!(backpressure.last(), Advice::SlowDown(Duration::from_secs(2)));
+        assert_eq!(backpressure.advise(&measurement(20.0)), Advice::Proceed);
+    }
+
+    #[test]
+    fn rate_controller() {
+        use super::*;
+
+        let measurement = |value: f64| Measurement { value, duration: Duration::from_secs(4) };
+        let second = Duration::from_secs(1);
+
+        let mut controller = RateController::new(10.0, 0.5, 0.25, 1.0);
+        assert_eq!(controller.update(&measurement(0.0), second), 5.0 + 2.5);
+        // Error of 4 with integral of 14 and derivative of -6
+        assert_eq!(controller.update(&measurement(24.0), second), -0.5);
+        controller.reset();
+        assert_eq!(controller.update(&measurement(40.0), second), 0.0);
+
+        // Integral does not wind up while limited
+        let mut controller = RateController::new(10.0, 0.0, 1.0, 0.0).limits(0.0, 5.0);
+        assert_eq!(controller.update(&measurement(0.0), second), 5.0);
+        assert_eq!(controller.update(&measurement(0.0), second), 5.0);
+        assert_eq!(controller.update(&measurement(80.0), second), 0.0);
+        assert_eq!(controller.update(&measurement(40.0), second), 0.0);
+        assert_eq!(controller.update(&measurement(0.0), second), 5.0);
+    }
+}
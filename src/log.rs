@@ -0,0 +1,187 @@
+//! Periodic logging of measurements via the `log` crate facade (requires `log` feature).
+//!
+//! `SharedRunningAverage::log_every()` spawns a `Reporter` that logs rate line on a schedule
+//! while `RealTimeRunningAverage::log_every()` wraps running average so that rate line is logged on insert when interval elapsed since last logged line.
+//!
+//! Templates can contain `{rate}`, `{value}` and `{window}` (width of the time window in seconds) placeholders.
+//! ```
+//! use std::time::Duration;
+//! use log::Level;
+//! use running_average::RealTimeRunningAverage;
+//!
+//! let mut tw = RealTimeRunningAverage::<u32>::default().log_every(Duration::from_secs(1), Level::Info, "downloading at {rate} B/s");
+//!
+//! // Got 2KB of data; logged if a second passed since last line
+//! tw.insert(2000);
+//! ```
+
+use std::fmt;
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime};
+
+use ::log::Level;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, TimeInstant, TimeSource, dts};
+use super::reporter::Reporter;
+use super::shared::SharedRunningAverage;
+use super::sink::MeasurementSink;
+
+/// MeasurementSink that logs each measurement formatted with template at given level.
+#[derive(Debug, Clone)]
+pub struct LogSink {
+    level: Level,
+    template: String,
+}
+
+impl LogSink {
+    /// Create new LogSink logging measurements formatted with given template at given level.
+    pub fn new(level: Level, template: &str) -> LogSink {
+        LogSink {
+            level,
+            template: template.to_owned(),
+        }
+    }
+
+    /// Format measurement with the template.
+    pub fn format<V>(&self, measurement: &Measurement<V>) -> String where V: Clone + Into<f64> {
+        let value: f64 = measurement.value().clone().into();
+        self.template
+            .replace("{rate}", &format!("{:.3}", measurement.rate_f64()))
+            .replace("{value}", &value.to_string())
+            .replace("{window}", &dts(measurement.duration()).to_string())
+    }
+
+    fn log<V>(&self, measurement: &Measurement<V>) where V: Clone + Into<f64> {
+        if ::log::log_enabled!(self.level) {
+            ::log::log!(self.level, "{}", self.format(measurement));
+        }
+    }
+}
+
+impl<V> MeasurementSink<V> for LogSink where V: Clone + Into<f64> {
+    type Error = Infallible;
+
+    fn record(&mut self, _ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Infallible> {
+        self.log(measurement);
+        Ok(())
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> SharedRunningAverage<V, TS> {
+    /// Spawn `Reporter` that logs measurement formatted with given template at given level every interval.
+    pub fn log_every(&self, interval: Duration, level: Level, template: &str) -> Reporter<V, LogSink>
+        where V: Clone + Into<f64> + Send + 'static, TS: Send + 'static, TS::Instant: Send {
+        Reporter::spawn(self.clone(), interval, LogSink::new(level, template))
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> RealTimeRunningAverage<V, TS> {
+    /// Wrap this running average so that measurement formatted with given template is logged at given level on insert
+    /// when at least interval elapsed since last logged line (or first insert).
+    pub fn log_every(self, interval: Duration, level: Level, template: &str) -> LogEvery<V, TS> {
+        LogEvery {
+            inner: self,
+            sink: LogSink::new(level, template),
+            interval,
+            last: None,
+        }
+    }
+}
+
+/// Running average wrapper logging measurement on insert at most once per interval.
+pub struct LogEvery<V: Accumulate, TS: TimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+    sink: LogSink,
+    interval: Duration,
+    last: Option<TS::Instant>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for LogEvery<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogEvery").field("inner", &self.inner).field("sink", &self.sink).field("interval", &self.interval).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> LogEvery<V, TS> {
+    /// Insert value to be average over now and log measurement if interval elapsed since last logged line.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) where V: Clone + Into<f64> {
+        self.inner.insert(val);
+
+        let now = self.inner.time_source().now();
+        let last = *self.last.get_or_insert(now);
+        if now.duration_since(last) >= self.interval {
+            self.last = Some(now);
+            let measurement = self.inner.measurement();
+            self.sink.log(&measurement);
+        }
+    }
+
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        self.inner.measurement()
+    }
+
+    /// Return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        &mut self.inner
+    }
+
+    /// Return the underlying running average consuming self.
+    pub fn into_inner(self) -> RealTimeRunningAverage<V, TS> {
+        self.inner
+    }
+}
+
+impl<V, TS: TimeSource> Insert for LogEvery<V, TS> where V: Accumulate + Clone + Into<f64> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        LogEvery::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use ::log::{Log, Metadata, Record};
+
+    static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CollectLogger;
+
+    impl Log for CollectLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            LINES.lock().unwrap().push(format!("{} {}", record.level(), record.args()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn log_on_insert_every_interval() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        ::log::set_logger(&CollectLogger).unwrap();
+        ::log::set_max_level(::log::LevelFilter::Trace);
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new())
+            .log_every(Duration::from_secs(2), Level::Info, "{value} in {window}s: {rate}/s");
+
+        tw.insert(10);
+        tw.get_mut().time_source().time_shift(1.0);
+        tw.insert(10);
+        tw.get_mut().time_source().time_shift(1.0);
+        tw.insert(20);
+        tw.get_mut().time_source().time_shift(1.0);
+        tw.insert(10);
+
+        assert_eq!(*LINES.lock().unwrap(), vec!["INFO 40 in 4s: 10.000/s".to_owned()]);
+    }
+}
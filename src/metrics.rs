@@ -0,0 +1,143 @@
+//! Integration with the `metrics` crate facade (requires `metrics` feature).
+//!
+//! `GaugeSink` sets a gauge to the measured rate and can be driven by `Reporter` to update the gauge on a schedule;
+//! `WindowCounter` can be used by a `metrics::Recorder` implementation to turn counter increments into running average inserts.
+//! ```
+//! use std::time::Duration;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::metrics::spawn_gauge_reporter;
+//!
+//! let shared = SharedRunningAverage::<u32>::default();
+//!
+//! // Update "download.rate" gauge with measured rate every second
+//! let reporter = spawn_gauge_reporter(shared.clone(), Duration::from_secs(1), "download.rate");
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//! # reporter.stop().unwrap();
+//! ```
+
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use ::metrics::{CounterFn, Gauge, SharedString};
+
+use super::{Accumulate, Measurement, RealTimeSource, TimeSource};
+use super::io::insert_count;
+use super::reporter::Reporter;
+use super::shared::SharedRunningAverage;
+use super::sink::MeasurementSink;
+
+/// MeasurementSink that sets a `metrics` gauge to the measured rate.
+#[derive(Debug, Clone)]
+pub struct GaugeSink {
+    gauge: Gauge,
+}
+
+impl GaugeSink {
+    /// Create new GaugeSink setting given gauge.
+    pub fn new(gauge: Gauge) -> GaugeSink {
+        GaugeSink {
+            gauge
+        }
+    }
+
+    /// Create new GaugeSink registering gauge of given name with the currently installed global recorder.
+    pub fn register<N: Into<SharedString>>(name: N) -> GaugeSink {
+        GaugeSink::new(::metrics::gauge!(name.into()))
+    }
+}
+
+impl<V> MeasurementSink<V> for GaugeSink where V: Clone + Into<f64> {
+    type Error = Infallible;
+
+    fn record(&mut self, _ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Infallible> {
+        self.gauge.set(measurement.rate_f64());
+        Ok(())
+    }
+}
+
+/// Spawn `Reporter` that will set gauge of given name registered with the currently installed global recorder to rate of given shared running average every interval.
+pub fn spawn_gauge_reporter<V, TS, N>(shared: SharedRunningAverage<V, TS>, interval: Duration, name: N) -> Reporter<V, GaugeSink>
+    where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send, N: Into<SharedString> {
+    Reporter::spawn(shared, interval, GaugeSink::register(name))
+}
+
+/// `metrics::CounterFn` implementation that inserts counter increments into shared running average.
+/// Use `metrics::Counter::from_arc()` to create a counter handle from it in a `metrics::Recorder` implementation.
+pub struct WindowCounter<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    shared: SharedRunningAverage<V, TS>,
+    absolute: AtomicU64,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for WindowCounter<V, TS> where SharedRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WindowCounter").field("shared", &self.shared).field("absolute", &self.absolute).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> WindowCounter<V, TS> {
+    /// Create new WindowCounter inserting increments into given shared running average.
+    pub fn new(shared: SharedRunningAverage<V, TS>) -> WindowCounter<V, TS> {
+        WindowCounter {
+            shared,
+            absolute: AtomicU64::new(0),
+        }
+    }
+
+    /// Return reference to shared running average increments are inserted into.
+    pub fn shared(&self) -> &SharedRunningAverage<V, TS> {
+        &self.shared
+    }
+}
+
+impl<V, TS: TimeSource> CounterFn for WindowCounter<V, TS> where V: Accumulate + From<u32> {
+    fn increment(&self, value: u64) {
+        self.absolute.fetch_add(value, Ordering::Relaxed);
+        insert_count(&mut &self.shared, value);
+    }
+
+    /// Inserts difference from the previously known counter value if counter went up.
+    fn absolute(&self, value: u64) {
+        let previous = self.absolute.fetch_max(value, Ordering::Relaxed);
+        if value > previous {
+            insert_count(&mut &self.shared, value - previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn gauge_set_to_rate() {
+        use super::*;
+        use std::sync::Arc;
+
+        let value = Arc::new(AtomicU64::new(0));
+        let mut sink = GaugeSink::new(Gauge::from_arc(value.clone()));
+
+        sink.record(SystemTime::now(), &Measurement { value: 40, duration: Duration::from_secs(4) }).unwrap();
+        assert_eq!(f64::from_bits(value.load(Ordering::Relaxed)), 10.0);
+    }
+
+    #[test]
+    fn counter_inserts_increments() {
+        use super::*;
+        use std::sync::Arc;
+        use super::super::ManualTimeSource;
+        use ::metrics::Counter;
+
+        let shared = SharedRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let counter = Counter::from_arc(Arc::new(WindowCounter::new(shared.clone())));
+
+        counter.increment(10);
+        counter.increment(5);
+        assert_eq!(shared.measurement().unwrap(), 15);
+
+        counter.absolute(20);
+        counter.absolute(18);
+        assert_eq!(shared.measurement().unwrap(), 20);
+    }
+}
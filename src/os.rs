@@ -0,0 +1,197 @@
+//! Process CPU usage measured over time window (requires `os` feature).
+//!
+//! `CpuUsage` samples CPU time consumed by the process from `CpuTimeProbe` on demand and records CPU time consumed since the previous sample
+//! as busy time of `UtilizationTracker`, giving "process CPU % over the last N seconds".
+//! CPU time of each sample is accounted to the bucket current at the time of that sample so it should be sampled at least once per bucket width for the best accuracy.
+//! ```
+//! use std::time::Duration;
+//! use running_average::os::CpuUsage;
+//!
+//! let mut cpu = CpuUsage::new(Duration::from_secs(8));
+//!
+//! # #[cfg(target_os = "linux")]
+//! assert!(cpu.percent().unwrap() >= 0.0);
+//! ```
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use super::{RealTimeSource, TimeSource};
+use super::utilization::UtilizationTracker;
+
+/// Source of total CPU time consumed by the process.
+pub trait CpuTimeProbe {
+    /// Returns total CPU time consumed so far; it is expected not to decrease between calls.
+    fn cpu_time(&mut self) -> io::Result<Duration>;
+}
+
+impl<F: FnMut() -> io::Result<Duration>> CpuTimeProbe for F {
+    fn cpu_time(&mut self) -> io::Result<Duration> {
+        self()
+    }
+}
+
+/// Probe reading user and system CPU time of the current process from `/proc/self/stat`.
+/// Returns error of `Unsupported` kind on other platforms than Linux.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessCpuTime;
+
+/// Returns clock ticks per second used by `/proc` (`USER_HZ`) as reported by `sysconf(_SC_CLK_TCK)`.
+#[cfg(target_os = "linux")]
+fn user_hz() -> io::Result<u64> {
+    use std::os::raw::{c_int, c_long};
+
+    extern "C" {
+        fn sysconf(name: c_int) -> c_long;
+    }
+
+    // Same on all Linux architectures for both glibc and musl
+    const SC_CLK_TCK: c_int = 2;
+
+    // sysconf only reads system configuration
+    match unsafe { sysconf(SC_CLK_TCK) } {
+        hz if hz > 0 => Ok(hz as u64),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+impl CpuTimeProbe for ProcessCpuTime {
+    #[cfg(target_os = "linux")]
+    fn cpu_time(&mut self) -> io::Result<Duration> {
+        let stat = std::fs::read_to_string("/proc/self/stat")?;
+        // Command name can contain spaces so fields are counted from its closing parenthesis
+        let fields = stat.rsplit_once(')').map(|(_, fields)| fields).unwrap_or_default();
+        let mut fields = fields.split_whitespace().skip(11);
+
+        let mut ticks = || -> io::Result<u64> {
+            fields.next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed /proc/self/stat"))
+        };
+        let ticks = ticks()? + ticks()?;
+        let user_hz = user_hz()?;
+
+        Ok(Duration::from_secs(ticks / user_hz) + Duration::from_nanos(ticks % user_hz * 1_000_000_000 / user_hz))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn cpu_time(&mut self) -> io::Result<Duration> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "process CPU time is only supported on Linux"))
+    }
+}
+
+/// CPU usage of the process sampled from given probe into utilization time window.
+pub struct CpuUsage<P: CpuTimeProbe = ProcessCpuTime, TS: TimeSource = RealTimeSource> {
+    probe: P,
+    last: Option<Duration>,
+    tracker: UtilizationTracker<TS>,
+}
+
+impl<P: CpuTimeProbe, TS: TimeSource> fmt::Debug for CpuUsage<P, TS> where P: fmt::Debug, UtilizationTracker<TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CpuUsage")
+            .field("probe", &self.probe)
+            .field("last", &self.last)
+            .field("tracker", &self.tracker)
+            .finish()
+    }
+}
+
+impl CpuUsage<ProcessCpuTime, RealTimeSource> {
+    /// Crate new instance sampling CPU time of the current process with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> CpuUsage<ProcessCpuTime, RealTimeSource> {
+        CpuUsage::with_probe(ProcessCpuTime, UtilizationTracker::new(duration))
+    }
+}
+
+impl<P: CpuTimeProbe, TS: TimeSource> CpuUsage<P, TS> {
+    /// Crate new instance sampling CPU time from given probe into given utilization tracker.
+    /// Number of workers of the tracker can be set to number of CPUs to get usage as fraction of all CPUs.
+    pub fn with_probe(probe: P, tracker: UtilizationTracker<TS>) -> CpuUsage<P, TS> {
+        CpuUsage {
+            probe,
+            last: None,
+            tracker,
+        }
+    }
+
+    /// Sample CPU time recording CPU time consumed since previous sample as busy time.
+    /// First sample only establishes the baseline.
+    /// Panics if time source time goes backwards.
+    pub fn sample(&mut self) -> io::Result<()> {
+        let cpu_time = self.probe.cpu_time()?;
+        if let Some(last) = self.last.replace(cpu_time) {
+            self.tracker.record(cpu_time.checked_sub(last).unwrap_or_default());
+        }
+        Ok(())
+    }
+
+    /// Sample CPU time and return fraction of busy CPU time over time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn utilization(&mut self) -> io::Result<f64> {
+        self.sample()?;
+        Ok(self.tracker.utilization())
+    }
+
+    /// Sample CPU time and return percentage of busy CPU time over time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn percent(&mut self) -> io::Result<f64> {
+        Ok(self.utilization()? * 100.0)
+    }
+
+    /// Returns the underlying utilization tracker.
+    pub fn tracker(&self) -> &UtilizationTracker<TS> {
+        &self.tracker
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cpu_usage_from_probe() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut cpu_time = Duration::from_secs(10);
+        let probe = move || {
+            cpu_time += Duration::from_secs(1);
+            Ok(cpu_time)
+        };
+
+        let mut cpu = CpuUsage::with_probe(probe, UtilizationTracker::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()));
+        assert_eq!(cpu.percent().unwrap(), 0.0);
+        assert_eq!(cpu.percent().unwrap(), 25.0);
+
+        cpu.tracker().shared().lock().time_source().time_shift(1.0);
+        assert_eq!(cpu.utilization().unwrap(), 0.5);
+
+        cpu.tracker().shared().lock().time_source().time_shift(3.0);
+        assert_eq!(cpu.utilization().unwrap(), 0.5);
+
+        let mut failing = CpuUsage::with_probe(|| Err(io::Error::other("gone")), UtilizationTracker::new(Duration::from_secs(4)));
+        assert!(failing.sample().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn process_cpu_time() {
+        use super::*;
+
+        let before = ProcessCpuTime.cpu_time().unwrap();
+        let mut x = 0u64;
+        for i in 0..10_000_000u64 {
+            x = x.wrapping_add(i * i);
+        }
+        assert!(x > 0);
+        assert!(ProcessCpuTime.cpu_time().unwrap() >= before);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn clock_ticks() {
+        use super::*;
+
+        assert!(user_hz().unwrap() > 0);
+    }
+}
@@ -0,0 +1,139 @@
+//! OpenTelemetry metrics integration (requires `otel` feature).
+//!
+//! `observe_rate()` registers an `ObservableGauge` that reports rate of shared running average on each collection of the meter provider.
+//! ```
+//! use std::time::Duration;
+//! use opentelemetry::global;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::otel::observe_rate;
+//!
+//! let shared = SharedRunningAverage::<u32>::new(Duration::from_secs(8));
+//! let _gauge = observe_rate(&global::meter("downloader"), "download.rate", &[("mirror", "eu")], shared.clone());
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//! ```
+
+use std::borrow::Cow;
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{AsyncInstrument, Meter, ObservableGauge};
+
+use super::{Accumulate, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Map label name and value pairs to OpenTelemetry attributes.
+pub fn attributes(labels: &[(&str, &str)]) -> Vec<KeyValue> {
+    labels.iter().map(|&(name, value)| KeyValue::new(name.to_owned(), value.to_owned())).collect()
+}
+
+/// Create observable gauge callback that observes rate of given shared running average with given attributes.
+/// Use with `AsyncInstrumentBuilder::with_callback()` to customise the instrument with description or unit.
+pub fn rate_callback<V, TS>(shared: SharedRunningAverage<V, TS>, attributes: Vec<KeyValue>) -> impl Fn(&dyn AsyncInstrument<f64>) + Send + Sync + 'static
+    where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+    move |observer| observer.observe(shared.measurement().rate_f64(), &attributes)
+}
+
+/// Register observable gauge of given name with given meter reporting rate of given shared running average with given labels as attributes.
+pub fn observe_rate<V, TS>(meter: &Meter, name: impl Into<Cow<'static, str>>, labels: &[(&str, &str)], shared: SharedRunningAverage<V, TS>) -> ObservableGauge<f64>
+    where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+    meter.f64_observable_gauge(name)
+        .with_callback(rate_callback(shared, attributes(labels)))
+        .build()
+}
+
+/// Register observable gauge named after given shared running average with given meter reporting its rate with its labels as attributes.
+/// Returns None if the shared running average has no name.
+pub fn observe_labeled<V, TS>(meter: &Meter, shared: SharedRunningAverage<V, TS>) -> Option<ObservableGauge<f64>>
+    where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+    let labels = shared.labels();
+    let name = labels.name()?.to_owned();
+    Some(observe_rate(meter, name, &labels.pairs(), shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use opentelemetry::metrics::{AsyncInstrumentBuilder, Callback, InstrumentProvider};
+    use super::{AsyncInstrument, Cow, KeyValue, ObservableGauge};
+
+    #[derive(Default)]
+    struct CollectProvider {
+        callbacks: Mutex<Vec<(Cow<'static, str>, Callback<f64>)>>,
+    }
+
+    impl InstrumentProvider for CollectProvider {
+        fn f64_observable_gauge(&self, builder: AsyncInstrumentBuilder<'_, ObservableGauge<f64>, f64>) -> ObservableGauge<f64> {
+            let mut callbacks = self.callbacks.lock().unwrap();
+            for callback in builder.callbacks {
+                callbacks.push((builder.name.clone(), callback));
+            }
+            ObservableGauge::new()
+        }
+    }
+
+    #[derive(Default)]
+    struct CollectObserver {
+        observed: Mutex<Vec<(f64, Vec<KeyValue>)>>,
+    }
+
+    impl AsyncInstrument<f64> for CollectObserver {
+        fn observe(&self, measurement: f64, attributes: &[KeyValue]) {
+            self.observed.lock().unwrap().push((measurement, attributes.to_vec()));
+        }
+    }
+
+    #[test]
+    fn observe_rate_on_collection() {
+        use super::*;
+        use super::super::ManualTimeSource;
+        use std::time::Duration;
+
+        let provider = Arc::new(CollectProvider::default());
+        let meter = Meter::new(provider.clone());
+
+        let shared = SharedRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let _gauge = observe_rate(&meter, "requests.rate", &[("service", "api")], shared.clone());
+
+        shared.insert(40);
+
+        let callbacks = provider.callbacks.lock().unwrap();
+        assert_eq!(callbacks.len(), 1);
+        assert_eq!(callbacks[0].0, "requests.rate");
+
+        let observer = CollectObserver::default();
+        (callbacks[0].1)(&observer);
+        assert_eq!(*observer.observed.lock().unwrap(), vec![(10.0, vec![KeyValue::new("service", "api")])]);
+    }
+
+    #[test]
+    fn observe_labeled_named_only() {
+        use super::*;
+        use super::super::{ManualTimeSource, RealTimeRunningAverage};
+        use std::time::Duration;
+
+        let provider = Arc::new(CollectProvider::default());
+        let meter = Meter::new(provider.clone());
+
+        let unnamed = SharedRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert!(observe_labeled(&meter, unnamed).is_none());
+
+        let shared = RealTimeRunningAverage::<u32, _>::builder()
+            .duration(Duration::from_secs(4))
+            .capacity(4)
+            .time_source(ManualTimeSource::new())
+            .name("egress.rate")
+            .label("iface", "eth0")
+            .build_shared();
+        assert!(observe_labeled(&meter, shared.clone()).is_some());
+        shared.insert(8);
+
+        let callbacks = provider.callbacks.lock().unwrap();
+        assert_eq!(callbacks.len(), 1);
+        assert_eq!(callbacks[0].0, "egress.rate");
+
+        let observer = CollectObserver::default();
+        (callbacks[0].1)(&observer);
+        assert_eq!(*observer.observed.lock().unwrap(), vec![(2.0, vec![KeyValue::new("iface", "eth0")])]);
+    }
+}
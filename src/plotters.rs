@@ -0,0 +1,132 @@
+//! Data adapters for charting throughput with `plotters` (requires `plotters` feature).
+//!
+//! `bucket_series()` returns rate of each bucket of time window with time relative to now on x axis
+//! and `MeasurementSeries` retains periodically taken measurements (e.g. by `Reporter`) as rate over time since UNIX epoch.
+//! Both can be passed to `plotters::series::LineSeries::new()` directly.
+//! ```
+//! use std::time::SystemTime;
+//! use plotters::prelude::*;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::sink::MeasurementSink;
+//! use running_average::plotters::{bucket_series, MeasurementSeries};
+//!
+//! let mut tw = RealTimeRunningAverage::<u32>::default();
+//! tw.insert(2000);
+//!
+//! let buckets: LineSeries<SVGBackend, _> = LineSeries::new(bucket_series(&mut tw), &RED);
+//!
+//! let mut history = MeasurementSeries::new(3600);
+//! history.record(SystemTime::now(), &tw.measurement()).unwrap();
+//! let history: LineSeries<SVGBackend, _> = LineSeries::new(&history, &BLUE);
+//! # drop((buckets, history));
+//! ```
+
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+use std::convert::Infallible;
+use std::iter::Copied;
+use std::time::SystemTime;
+
+use super::{Accumulate, Measurement, RealTimeRunningAverage, TimeSource, dts};
+use super::sink::{MeasurementSink, unix_seconds};
+
+/// Returns `(seconds, rate)` points for each bucket of time window ending now, oldest bucket first,
+/// where seconds is start of the bucket time span relative to start of the most recent bucket (so the last point is at 0)
+/// and rate is bucket value divided by width of the bucket time span.
+/// Panics if time source time goes backwards.
+pub fn bucket_series<V, TS>(running_average: &mut RealTimeRunningAverage<V, TS>) -> Vec<(f64, f64)> where V: Accumulate + Clone + Into<f64>, TS: TimeSource {
+    let bucket = dts(running_average.bucket_duration());
+    let mut points: Vec<(f64, f64)> = running_average.buckets()
+        .enumerate()
+        .map(|(age, value)| (-(age as f64) * bucket, value.clone().into() / bucket))
+        .collect();
+    points.reverse();
+    points
+}
+
+/// MeasurementSink that retains up to given number of most recent `(seconds since UNIX epoch, rate)` points.
+#[derive(Debug, Clone)]
+pub struct MeasurementSeries {
+    points: VecDeque<(f64, f64)>,
+    max_points: usize,
+}
+
+impl MeasurementSeries {
+    /// Create new MeasurementSeries retaining up to given number of most recent points.
+    pub fn new(max_points: usize) -> MeasurementSeries {
+        MeasurementSeries {
+            points: VecDeque::with_capacity(max_points),
+            max_points,
+        }
+    }
+
+    /// Returns number of retained points.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if no points are retained.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Return iterator over retained points, oldest first.
+    pub fn iter(&self) -> Copied<vec_deque::Iter<'_, (f64, f64)>> {
+        self.points.iter().copied()
+    }
+}
+
+impl<'s> IntoIterator for &'s MeasurementSeries {
+    type Item = (f64, f64);
+    type IntoIter = Copied<vec_deque::Iter<'s, (f64, f64)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> MeasurementSink<V> for MeasurementSeries where V: Clone + Into<f64> {
+    type Error = Infallible;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Infallible> {
+        if self.max_points == 0 {
+            return Ok(())
+        }
+        if self.points.len() == self.max_points {
+            self.points.pop_front();
+        }
+        self.points.push_back((unix_seconds(ts), measurement.rate_f64()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bucket_rates_oldest_first() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::ManualTimeSource;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(8), 4, ManualTimeSource::new());
+        tw.insert(10);
+        tw.time_source().time_shift(2.0);
+        tw.insert(4);
+
+        assert_eq!(bucket_series(&mut tw), vec![(-6.0, 0.0), (-4.0, 0.0), (-2.0, 5.0), (0.0, 2.0)]);
+    }
+
+    #[test]
+    fn retain_most_recent_measurements() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut series = MeasurementSeries::new(2);
+        for seconds in 1..4 {
+            series.record(UNIX_EPOCH + Duration::from_secs(seconds), &Measurement { value: seconds as u32, duration: Duration::from_secs(1) }).unwrap();
+        }
+
+        assert_eq!(series.len(), 2);
+        assert_eq!(series.into_iter().collect::<Vec<_>>(), vec![(2.0, 2.0), (3.0, 3.0)]);
+    }
+}
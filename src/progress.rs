@@ -0,0 +1,258 @@
+//! Progress reporting based on rate measured over time window.
+//!
+//! `Eta` estimates time remaining to complete a known amount of work from recent throughput
+//! so that the estimate adapts to changes in speed.
+//! ```
+//! use running_average::progress::Eta;
+//!
+//! // Downloading 10MB file
+//! let mut eta = Eta::new(10_000_000);
+//!
+//! // Got first 2KB of data
+//! eta.record(2000);
+//!
+//! println!("{:?} left", eta.remaining());
+//!
+//! // Print progress line like "0.0% • 250.0 B/s • 11h06m32s left"
+//! println!("{}", eta.report());
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+use super::format::SiRate;
+
+/// Estimates time remaining to complete given total amount of work based on rate of progress measured over time window.
+pub struct Eta<TS: TimeSource = RealTimeSource> {
+    window: RealTimeRunningAverage<f64, TS>,
+    total: u64,
+    progress: u64,
+}
+
+impl<TS: TimeSource> fmt::Debug for Eta<TS> where RealTimeRunningAverage<f64, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Eta").field("window", &self.window).field("total", &self.total).field("progress", &self.progress).finish()
+    }
+}
+
+impl Eta<RealTimeSource> {
+    /// Crate new instance for given total amount of work measuring progress over window of 8 seconds width.
+    pub fn new(total: u64) -> Eta<RealTimeSource> {
+        Eta::with_window(total, RealTimeRunningAverage::default())
+    }
+}
+
+impl<TS: TimeSource> Eta<TS> {
+    /// Crate new instance for given total amount of work measuring progress with given running average.
+    pub fn with_window(total: u64, window: RealTimeRunningAverage<f64, TS>) -> Eta<TS> {
+        Eta {
+            window,
+            total,
+            progress: 0,
+        }
+    }
+
+    /// Record that given amount of work is completed so far.
+    /// Progress going backwards (e.g. retried transfer) is not accounted as negative rate.
+    /// Panics if time source time goes backwards.
+    pub fn record(&mut self, progress: u64) {
+        if progress > self.progress {
+            self.window.insert((progress - self.progress) as f64);
+        }
+        self.progress = progress;
+    }
+
+    /// Record that given amount of additional work was completed.
+    /// Panics if time source time goes backwards.
+    pub fn advance(&mut self, amount: u64) {
+        let progress = self.progress.saturating_add(amount);
+        self.record(progress)
+    }
+
+    /// Returns amount of work completed so far.
+    pub fn progress(&self) -> u64 {
+        self.progress
+    }
+
+    /// Returns total amount of work.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Set total amount of work.
+    pub fn set_total(&mut self, total: u64) {
+        self.total = total;
+    }
+
+    /// Calculate rate of progress using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<f64> {
+        self.window.measurement()
+    }
+
+    /// Estimate time remaining to complete the total amount of work at rate of progress measured over time window ending now.
+    /// Returns None if no progress was made within the time window; saturates to `Duration::MAX` if rate is too low to be represented.
+    /// Panics if time source time goes backwards.
+    pub fn remaining(&mut self) -> Option<Duration> {
+        let left = self.total.saturating_sub(self.progress);
+        if left == 0 {
+            return Some(Duration::from_secs(0))
+        }
+
+        let rate = self.window.measurement().rate();
+        if rate > 0.0 {
+            Some(Duration::try_from_secs_f64(left as f64 / rate).unwrap_or(Duration::MAX))
+        } else {
+            None
+        }
+    }
+
+    /// Take snapshot of progress with rate and estimated time remaining measured over time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn report(&mut self) -> Progress {
+        Progress::new(self.progress, self.total, self.window.measurement().rate())
+    }
+
+    /// Return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<f64, TS> {
+        &mut self.window
+    }
+}
+
+/// Snapshot of progress of known amount of work in bytes.
+/// Displays as progress line with percent completed, rate in binary multiples of bytes per second and estimated time remaining like `42.3% • 1.8 MiB/s • 2m14s left`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    completed: u64,
+    total: u64,
+    rate: f64,
+}
+
+impl Progress {
+    /// Create new snapshot of given amount of completed work out of given total done at given rate per second.
+    pub fn new(completed: u64, total: u64, rate: f64) -> Progress {
+        Progress {
+            completed,
+            total,
+            rate,
+        }
+    }
+
+    /// Returns amount of work completed.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// Returns total amount of work.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns rate of progress per second.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Returns percent of total work completed; 100 if total is 0.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0
+        }
+        (self.completed as f64 / self.total as f64 * 100.0).min(100.0)
+    }
+
+    /// Estimate time remaining to complete the total amount of work at the rate.
+    /// Returns None if rate is not positive and work is not completed; saturates to `Duration::MAX` if rate is too low to be represented.
+    pub fn eta(&self) -> Option<Duration> {
+        let left = self.total.saturating_sub(self.completed);
+        if left == 0 {
+            Some(Duration::from_secs(0))
+        } else if self.rate > 0.0 {
+            Some(Duration::try_from_secs_f64(left as f64 / self.rate).unwrap_or(Duration::MAX))
+        } else {
+            None
+        }
+    }
+}
+
+fn fmt_bytes_rate(f: &mut fmt::Formatter, rate: f64) -> fmt::Result {
+    let (rate, prefix) = SiRate::new(rate).binary().scaled();
+    write!(f, "{:.1} {}B/s", rate, prefix)
+}
+
+fn fmt_duration(f: &mut fmt::Formatter, duration: Duration) -> fmt::Result {
+    let seconds = duration.as_secs().saturating_add(if duration.subsec_nanos() >= 500_000_000 { 1 } else { 0 });
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => write!(f, "{}s", s),
+        (0, m, s) => write!(f, "{}m{:02}s", m, s),
+        (h, m, s) => write!(f, "{}h{:02}m{:02}s", h, m, s),
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1}% \u{2022} ", self.percent())?;
+        fmt_bytes_rate(f, self.rate)?;
+        f.write_str(" \u{2022} ")?;
+        match self.eta() {
+            Some(eta) => fmt_duration(f, eta)?,
+            None => f.write_str("?")?,
+        }
+        f.write_str(" left")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn remaining_adapts_to_recent_rate() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut eta = Eta::with_window(1000, RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()));
+        assert_eq!(eta.remaining(), None);
+
+        for _ in 0..4 {
+            eta.get_mut().time_source().time_shift(1.0);
+            eta.advance(40);
+        }
+        // 160 done at 40/s
+        assert_eq!(eta.remaining(), Some(Duration::from_secs(21)));
+
+        // Slowed down to 10/s
+        for _ in 0..4 {
+            eta.get_mut().time_source().time_shift(1.0);
+            eta.advance(10);
+        }
+        assert_eq!(eta.progress(), 200);
+        assert_eq!(eta.remaining(), Some(Duration::from_secs(80)));
+
+        eta.record(1000);
+        assert_eq!(eta.remaining(), Some(Duration::from_secs(0)));
+
+        eta.get_mut().time_source().time_shift(4.0);
+        eta.set_total(2000);
+        assert_eq!(eta.remaining(), None);
+
+        // Long stall leaving tiny rate
+        let mut eta = Eta::with_window(u64::MAX, RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()));
+        eta.advance(1);
+        assert_eq!(eta.remaining(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn progress_display() {
+        use super::*;
+
+        assert_eq!(&Progress::new(423, 1000, 1.8 * 1024.0 * 1024.0).to_string(), "42.3% \u{2022} 1.8 MiB/s \u{2022} 0s left");
+        assert_eq!(&Progress::new(0, 1_000_000, 7462.7).to_string(), "0.0% \u{2022} 7.3 KiB/s \u{2022} 2m14s left");
+        assert_eq!(&Progress::new(0, 36_000, 10.0).to_string(), "0.0% \u{2022} 10.0 B/s \u{2022} 1h00m00s left");
+        assert_eq!(&Progress::new(10, 20, 0.0).to_string(), "50.0% \u{2022} 0.0 B/s \u{2022} ? left");
+        assert_eq!(&Progress::new(0, 0, 0.0).to_string(), "100.0% \u{2022} 0.0 B/s \u{2022} 0s left");
+
+        // Near-zero rate
+        assert_eq!(Progress::new(0, 100, 1e-20).eta(), Some(Duration::MAX));
+        assert_eq!(&Progress::new(0, 100, 1e-20).to_string(), "0.0% \u{2022} 0.0 B/s \u{2022} 5124095576030431h00m15s left");
+    }
+}
@@ -0,0 +1,245 @@
+//! Prometheus exposition of running averages (requires `prometheus` feature).
+//!
+//! `RateCollector` exposes named shared running averages as `<name>_rate` gauges with current rate and,
+//! for running averages created with `RateCollector::rate()`, `<name>_total` counters with lifetime total of inserted values.
+//! ```
+//! use std::time::Duration;
+//! use prometheus::{Registry, Encoder, TextEncoder};
+//! use running_average::prometheus::RateCollector;
+//!
+//! let mut collector = RateCollector::new();
+//! let ingest = collector.rate::<u32>("ingest_bytes", "Bytes ingested", &[("source", "kafka")], Duration::from_secs(8)).unwrap();
+//!
+//! let registry = Registry::new();
+//! registry.register(Box::new(collector)).unwrap();
+//!
+//! // Got 2KB of data
+//! ingest.insert(2000);
+//!
+//! let mut exposition = Vec::new();
+//! TextEncoder::new().encode(&registry.gather(), &mut exposition).unwrap();
+//! let exposition = String::from_utf8(exposition).unwrap();
+//!
+//! assert!(exposition.contains("ingest_bytes_rate{source=\"kafka\"} 250"));
+//! assert!(exposition.contains("ingest_bytes_total{source=\"kafka\"} 2000"));
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use ::prometheus::{Counter, Error, Gauge, Opts, Result};
+use ::prometheus::core::{Collector, Desc};
+use ::prometheus::proto::MetricFamily;
+
+use super::{Accumulate, Insert, Measurement, RealTimeSource, TimeSource};
+use super::shared::SharedRunningAverage;
+
+type Rate = Box<dyn Fn() -> f64 + Send + Sync>;
+
+struct Series {
+    rate: Rate,
+    gauge: Gauge,
+    total: Option<Counter>,
+}
+
+/// Prometheus collector exposing named shared running averages.
+/// All running averages need to be added before the collector is registered.
+#[derive(Default)]
+pub struct RateCollector {
+    series: Vec<Series>,
+}
+
+impl fmt::Debug for RateCollector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateCollector").field("series", &self.series.iter().map(|series| series.gauge.desc()[0].fq_name.as_str()).collect::<Vec<_>>()).finish()
+    }
+}
+
+fn opts(name: &str, suffix: &str, help: &str, labels: &[(&str, &str)]) -> Opts {
+    labels.iter().fold(Opts::new(format!("{}_{}", name, suffix), help), |opts, &(name, value)| opts.const_label(name, value))
+}
+
+impl RateCollector {
+    /// Create new empty RateCollector.
+    pub fn new() -> RateCollector {
+        RateCollector::default()
+    }
+
+    /// Create new shared running average with window of given width duration exposed as `<name>_rate` gauge and `<name>_total` counter with given constant labels.
+    pub fn rate<V>(&mut self, name: &str, help: &str, labels: &[(&str, &str)], duration: Duration) -> Result<ExposedRate<V, RealTimeSource>>
+        where V: Accumulate + Clone + Into<f64> + Send + 'static {
+        self.rate_with(name, help, labels, SharedRunningAverage::new(duration))
+    }
+
+    /// Expose given shared running average as `<name>_rate` gauge and `<name>_total` counter with given constant labels.
+    /// Only values inserted via returned ExposedRate are counted in the total.
+    pub fn rate_with<V, TS>(&mut self, name: &str, help: &str, labels: &[(&str, &str)], shared: SharedRunningAverage<V, TS>) -> Result<ExposedRate<V, TS>>
+        where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+        let total = Counter::with_opts(opts(name, "total", help, labels))?;
+        self.push(name, help, labels, shared.clone(), Some(total.clone()))?;
+
+        Ok(ExposedRate {
+            shared,
+            total,
+        })
+    }
+
+    /// Expose given shared running average as `<name>_rate` gauge with given constant labels.
+    pub fn add<V, TS>(&mut self, name: &str, help: &str, labels: &[(&str, &str)], shared: SharedRunningAverage<V, TS>) -> Result<()>
+        where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+        self.push(name, help, labels, shared, None)
+    }
+
+    /// Expose given shared running average as `<name>_rate` gauge using its name and labels as constant labels.
+    /// Returns error if the shared running average has no name.
+    pub fn add_labeled<V, TS>(&mut self, help: &str, shared: SharedRunningAverage<V, TS>) -> Result<()>
+        where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+        let labels = shared.labels();
+        let name = labels.name().ok_or_else(|| Error::Msg("running average has no name".to_owned()))?;
+        self.push(name, help, &labels.pairs(), shared.clone(), None)
+    }
+
+    fn push<V, TS>(&mut self, name: &str, help: &str, labels: &[(&str, &str)], shared: SharedRunningAverage<V, TS>, total: Option<Counter>) -> Result<()>
+        where V: Accumulate + Clone + Into<f64> + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+        let gauge = Gauge::with_opts(opts(name, "rate", help, labels))?;
+
+        self.series.push(Series {
+            rate: Box::new(move || shared.measurement().rate_f64()),
+            gauge,
+            total,
+        });
+        Ok(())
+    }
+}
+
+impl Collector for RateCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.series.iter()
+            .flat_map(|series| series.gauge.desc().into_iter().chain(series.total.iter().flat_map(|total| total.desc())))
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.series.iter()
+            .flat_map(|series| {
+                series.gauge.set((series.rate)());
+                series.gauge.collect().into_iter().chain(series.total.iter().flat_map(|total| total.collect()))
+            })
+            .collect()
+    }
+}
+
+/// Handle to shared running average exposed by `RateCollector` that also counts lifetime total of inserted values.
+/// Note: Negative values are not counted in the total as Prometheus counters cannot go down.
+pub struct ExposedRate<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    shared: SharedRunningAverage<V, TS>,
+    total: Counter,
+}
+
+impl<V: Accumulate, TS: TimeSource> Clone for ExposedRate<V, TS> {
+    fn clone(&self) -> ExposedRate<V, TS> {
+        ExposedRate {
+            shared: self.shared.clone(),
+            total: self.total.clone(),
+        }
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for ExposedRate<V, TS> where SharedRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExposedRate").field("shared", &self.shared).field("total", &self.total.get()).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> ExposedRate<V, TS> {
+    /// Insert value to be average over now and add it to the lifetime total.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&self, val: V) where V: Clone + Into<f64> {
+        let value: f64 = val.clone().into();
+        if value > 0.0 {
+            self.total.inc_by(value);
+        }
+        self.shared.insert(val)
+    }
+
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&self) -> Measurement<V> {
+        self.shared.measurement()
+    }
+
+    /// Returns lifetime total of inserted values.
+    pub fn total(&self) -> f64 {
+        self.total.get()
+    }
+
+    /// Return reference to the underlying shared running average.
+    pub fn shared(&self) -> &SharedRunningAverage<V, TS> {
+        &self.shared
+    }
+}
+
+impl<V: Accumulate + Clone + Into<f64>, TS: TimeSource> Insert for ExposedRate<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        ExposedRate::insert(self, val)
+    }
+}
+
+impl<V: Accumulate + Clone + Into<f64>, TS: TimeSource> Insert for &ExposedRate<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        ExposedRate::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn collect_rate_and_total() {
+        use super::*;
+        use ::prometheus::proto::MetricType;
+        use super::super::ManualTimeSource;
+
+        let mut collector = RateCollector::new();
+        let shared = SharedRunningAverage::<i32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let rate = collector.rate_with("requests", "Requests", &[("service", "api"), ("zone", "a")], shared.clone()).unwrap();
+        collector.add("errors", "Errors", &[], SharedRunningAverage::<i32>::default()).unwrap();
+
+        rate.insert(40);
+        rate.insert(-10);
+        shared.lock().time_source().time_shift(4.0);
+        rate.insert(8);
+
+        assert_eq!(collector.desc().len(), 3);
+
+        let families = collector.collect();
+        let names: Vec<_> = families.iter().map(|family| (family.name(), family.get_field_type())).collect();
+        assert_eq!(names, vec![("requests_rate", MetricType::GAUGE), ("requests_total", MetricType::COUNTER), ("errors_rate", MetricType::GAUGE)]);
+
+        assert_eq!(families[0].get_metric()[0].get_gauge().value(), 2.0);
+        assert_eq!(families[1].get_metric()[0].get_counter().value(), 48.0);
+
+        let labels: Vec<_> = families[0].get_metric()[0].get_label().iter().map(|label| (label.name(), label.value())).collect();
+        assert_eq!(labels, vec![("service", "api"), ("zone", "a")]);
+    }
+
+    #[test]
+    fn collect_labeled() {
+        use super::*;
+        use super::super::RealTimeRunningAverage;
+
+        let mut collector = RateCollector::new();
+        collector.add_labeled("Egress", RealTimeRunningAverage::<u32>::builder().name("egress").label("iface", "eth0").build_shared()).unwrap();
+        assert!(collector.add_labeled("Unnamed", SharedRunningAverage::<u32>::default()).is_err());
+
+        let families = collector.collect();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].name(), "egress_rate");
+
+        let labels: Vec<_> = families[0].get_metric()[0].get_label().iter().map(|label| (label.name(), label.value())).collect();
+        assert_eq!(labels, vec![("iface", "eth0")]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Process global registry of named shared running averages (requires `registry` feature).
+//!
+//! `rate()` returns shared running average registered under given name creating it on first use,
+//! so instrumented code does not need to be passed handles; exporters can enumerate all registered running averages with `rates()`.
+//! ```
+//! use running_average::registry;
+//!
+//! // Got 2KB of data
+//! registry::rate("ingest.bytes").insert(2000.0);
+//!
+//! for (name, shared) in registry::rates() {
+//!     println!("{}: {}", name, shared.measurement());
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
+
+use super::shared::SharedRunningAverage;
+use super::timer::Timer;
+
+fn registry() -> MutexGuard<'static, HashMap<&'static str, SharedRunningAverage<f64>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<&'static str, SharedRunningAverage<f64>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns shared running average registered under given name.
+/// New running average with window of 8 seconds width and 16 buckets is registered if there is none of that name.
+pub fn rate(name: &'static str) -> SharedRunningAverage<f64> {
+    registry().entry(name).or_default().clone()
+}
+
+/// Returns shared running average registered under given name.
+/// New running average with window of given width is registered if there is none of that name.
+pub fn rate_with(name: &'static str, duration: Duration) -> SharedRunningAverage<f64> {
+    registry().entry(name).or_insert_with(|| SharedRunningAverage::new(duration)).clone()
+}
+
+/// Register given shared running average under given name returning one previously registered under that name.
+pub fn register(name: &'static str, shared: SharedRunningAverage<f64>) -> Option<SharedRunningAverage<f64>> {
+    registry().insert(name, shared)
+}
+
+/// Returns shared running average registered under given name if any.
+pub fn get(name: &str) -> Option<SharedRunningAverage<f64>> {
+    registry().get(name).cloned()
+}
+
+/// Remove shared running average registered under given name returning it.
+pub fn unregister(name: &str) -> Option<SharedRunningAverage<f64>> {
+    registry().remove(name)
+}
+
+/// Returns all registered shared running averages with their names ordered by name.
+pub fn rates() -> Vec<(&'static str, SharedRunningAverage<f64>)> {
+    let mut rates: Vec<_> = registry().iter().map(|(name, shared)| (*name, shared.clone())).collect();
+    rates.sort_by_key(|(name, _)| *name);
+    rates
+}
+
+/// Registry rates of instrumented call site looked up on first call.
+/// Each call inserts `1` into calls rate and time spent in the call in seconds into seconds rate.
+#[derive(Debug)]
+pub struct CallSite {
+    calls: (&'static str, OnceLock<SharedRunningAverage<f64>>),
+    seconds: (&'static str, OnceLock<SharedRunningAverage<f64>>),
+}
+
+impl CallSite {
+    /// Create new call site recording into registry rates of given names.
+    pub const fn new(calls: &'static str, seconds: &'static str) -> CallSite {
+        CallSite {
+            calls: (calls, OnceLock::new()),
+            seconds: (seconds, OnceLock::new()),
+        }
+    }
+
+    /// Record call and start timer recording time until it is dropped.
+    pub fn start(&self) -> Timer<SharedRunningAverage<f64>> {
+        self.calls.1.get_or_init(|| rate(self.calls.0)).insert(1.0);
+        self.seconds.1.get_or_init(|| rate(self.seconds.0)).start_timer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn register_on_first_use() {
+        use super::*;
+
+        rate("test.registry.a").insert(10.0);
+        rate("test.registry.a").insert(30.0);
+        rate_with("test.registry.b", Duration::from_secs(4));
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(get("test.registry.a").unwrap().measurement().unwrap(), 40.0);
+        assert_eq!(get("test.registry.b").unwrap().measurement().duration(), Duration::from_secs(4));
+        assert!(get("test.registry.c").is_none());
+
+        let names: Vec<_> = rates().into_iter().map(|(name, _)| name).filter(|name| name.starts_with("test.registry.")).collect();
+        assert_eq!(names, vec!["test.registry.a", "test.registry.b"]);
+
+        assert!(unregister("test.registry.b").is_some());
+        assert!(register("test.registry.b", SharedRunningAverage::default()).is_none());
+    }
+
+    #[test]
+    fn record_call_site() {
+        use super::*;
+
+        static CALL_SITE: CallSite = CallSite::new("test.call.calls", "test.call.seconds");
+
+        drop(CALL_SITE.start());
+        CALL_SITE.start().cancel();
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(rate("test.call.calls").measurement().unwrap(), 2.0);
+        assert!(rate("test.call.seconds").measurement().unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,159 @@
+//! Background thread periodically recording measurements of shared running average to a sink.
+//!
+//! Measurements are recorded with name and labels of the shared running average (see `MeasurementSink::record_labeled()`).
+//!
+//! ```
+//! use std::io;
+//! use std::time::Duration;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::sink::JsonLinesSink;
+//! use running_average::reporter::Reporter;
+//!
+//! let shared = SharedRunningAverage::default();
+//!
+//! // Print JSON line with measurement every second
+//! let reporter = Reporter::spawn(shared.clone(), Duration::from_secs(1), JsonLinesSink::new(io::stdout()));
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//!
+//! // Stop reporting; this also happens when reporter is dropped
+//! reporter.stop().unwrap();
+//! ```
+
+use std::marker::PhantomData;
+use std::panic;
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{Accumulate, TimeSource};
+use super::shared::SharedRunningAverage;
+use super::sink::MeasurementSink;
+
+/// Owns a thread that takes measurement of shared running average every interval and records it to a sink.
+/// The thread is stopped when Reporter is dropped or explicitly with `stop()`.
+#[derive(Debug)]
+pub struct Reporter<V, S: MeasurementSink<V>> {
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<S, S::Error>>>,
+    value: PhantomData<fn(V)>,
+}
+
+impl<V, S: MeasurementSink<V>> Reporter<V, S> {
+    /// Spawn reporter thread that will record measurement of given shared running average to given sink every interval.
+    /// Reporting stops if sink fails to record measurement; the error is returned from `stop()`.
+    pub fn spawn<TS>(shared: SharedRunningAverage<V, TS>, interval: Duration, mut sink: S) -> Reporter<V, S>
+        where V: Accumulate + Send + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send, S: Send + 'static, S::Error: Send + 'static {
+        let (shutdown, signal) = channel();
+
+        let thread = thread::Builder::new().name("running-average-reporter".to_owned()).spawn(move || {
+            let mut deadline = Instant::now() + interval;
+
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+
+                match signal.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(sink),
+                }
+
+                let measurement = shared.labeled_measurement();
+                sink.record_labeled(SystemTime::now(), &measurement)?;
+
+                deadline += interval;
+            }
+        }).expect("failed to spawn reporter thread");
+
+        Reporter {
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+            value: PhantomData,
+        }
+    }
+
+    fn shutdown(&mut self) -> Option<thread::Result<Result<S, S::Error>>> {
+        self.shutdown.take();
+        self.thread.take().map(|thread| thread.join())
+    }
+
+    /// Stop reporter thread and return the sink or error if the sink failed to record measurement.
+    /// Panics if reporter thread panicked.
+    pub fn stop(mut self) -> Result<S, S::Error> {
+        match self.shutdown().expect("reporter already stopped") {
+            Ok(result) => result,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<V, S: MeasurementSink<V>> Drop for Reporter<V, S> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeasurementSink, SystemTime};
+    use super::super::Measurement;
+
+    #[derive(Debug, Default)]
+    struct CollectSink {
+        values: Vec<i32>,
+        fail_after: Option<usize>,
+    }
+
+    impl MeasurementSink<i32> for CollectSink {
+        type Error = String;
+
+        fn record(&mut self, _ts: SystemTime, measurement: &Measurement<i32>) -> Result<(), String> {
+            if self.fail_after == Some(self.values.len()) {
+                return Err("sink full".to_owned())
+            }
+            self.values.push(*measurement.value());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_periodically() {
+        use super::*;
+
+        let shared = SharedRunningAverage::default();
+        shared.insert(10);
+
+        let reporter = Reporter::spawn(shared.clone(), Duration::from_millis(10), CollectSink::default());
+        thread::sleep(Duration::from_millis(100));
+
+        let sink = reporter.stop().unwrap();
+        assert!(sink.values.len() >= 2, "reported: {:?}", sink.values);
+        assert!(sink.values.iter().all(|value| *value == 10), "reported: {:?}", sink.values);
+    }
+
+    #[test]
+    fn stops_on_sink_error() {
+        use super::*;
+
+        let shared = SharedRunningAverage::default();
+
+        let reporter = Reporter::spawn(shared, Duration::from_millis(1), CollectSink { values: Vec::new(), fail_after: Some(2) });
+        thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(reporter.stop().unwrap_err(), "sink full");
+    }
+
+    #[test]
+    fn stops_on_drop() {
+        use super::*;
+
+        let shared = SharedRunningAverage::<i32>::default();
+        let start = Instant::now();
+
+        let reporter = Reporter::spawn(shared, Duration::from_secs(3600), CollectSink::default());
+        drop(reporter);
+
+        // Dropping does not wait for next interval
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,265 @@
+//! Running average with buckets stored in lazily allocated chunks for very long windows of fine resolution.
+//!
+//! `SegmentedRunningAverage` groups buckets into chunks that are only allocated when value is inserted into one of their buckets
+//! and are freed once all of their buckets expire, so that e.g. day-long window of one second buckets takes memory only for periods that saw inserts
+//! and shifting it after long idle period does not need to reset every bucket one by one.
+//! ```
+//! use std::time::{Duration, Instant};
+//! use running_average::segmented::SegmentedRunningAverage;
+//!
+//! // One day window with one second buckets
+//! let mut tw = SegmentedRunningAverage::new(Duration::from_secs(86400), 86400);
+//! let now = Instant::now();
+//!
+//! tw.insert(now, 2000);
+//!
+//! assert_eq!(tw.allocated_chunks(), 1);
+//! assert_eq!(tw.measurement(now).unwrap(), 2000);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{bucket_capacity, from_nanos, slot_duration, Accumulate, Measurement, TimeInstant};
+
+/// Running average calculation window with buckets stored in chunks allocated on first insert into one of their buckets.
+pub struct SegmentedRunningAverage<V: Accumulate, I: TimeInstant + Copy> {
+    chunks: Vec<Option<Box<[V]>>>,
+    /// Number of slot the window was shifted to when value was last inserted into each chunk.
+    inserted: Vec<u128>,
+    chunk_size: usize,
+    capacity: usize,
+    head: usize,
+    /// Number of slots the window was shifted by since creation.
+    slot: u128,
+    front: Option<I>,
+    duration: Duration,
+    slot_nanos: u128,
+}
+
+impl<V: Accumulate + fmt::Debug, I: TimeInstant + Copy> fmt::Debug for SegmentedRunningAverage<V, I> where I: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SegmentedRunningAverage")
+            .field("chunks", &self.chunks)
+            .field("chunk_size", &self.chunk_size)
+            .field("capacity", &self.capacity)
+            .field("head", &self.head)
+            .field("front", &self.front)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> SegmentedRunningAverage<V, I> {
+    /// Crate new instance that will average over window of width of given duration with specific number of buckets grouped in chunks of 64 buckets.
+    pub fn new(duration: Duration, capacity: usize) -> SegmentedRunningAverage<V, I> {
+        SegmentedRunningAverage::with_chunk_size(duration, capacity, 64)
+    }
+
+    /// Crate new instance that will average over window of width of given duration with specific number of buckets grouped in chunks of given number of buckets.
+    /// Panics if capacity or chunk size is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn with_chunk_size(duration: Duration, capacity: usize, chunk_size: usize) -> SegmentedRunningAverage<V, I> {
+        assert!(capacity > 0, "SegmentedRunningAverage capacity cannot be 0");
+        assert!(chunk_size > 0, "SegmentedRunningAverage chunk size cannot be 0");
+        let capacity = bucket_capacity(duration, capacity);
+
+        SegmentedRunningAverage {
+            chunks: (0..capacity.div_ceil(chunk_size)).map(|_| None).collect(),
+            inserted: vec![0; capacity.div_ceil(chunk_size)],
+            chunk_size,
+            capacity,
+            head: 0,
+            slot: 0,
+            front: None,
+            duration,
+            slot_nanos: slot_duration(duration, capacity).as_nanos(),
+        }
+    }
+
+    /// Reset given range of buckets freeing chunks that are fully within the range
+    /// or that had no value inserted since the window was shifted by its capacity, as then all their buckets expired.
+    fn reset(&mut self, start: usize, end: usize) {
+        let mut index = start;
+        while index < end {
+            let chunk = index / self.chunk_size;
+            let chunk_start = chunk * self.chunk_size;
+            let chunk_end = (chunk_start + self.chunk_size).min(self.capacity);
+            let until = chunk_end.min(end);
+
+            if index == chunk_start && until == chunk_end || self.slot >= self.inserted[chunk].saturating_add(self.capacity as u128) {
+                self.chunks[chunk] = None;
+            } else if let Some(buckets) = self.chunks[chunk].as_mut() {
+                buckets[index - chunk_start..until - chunk_start].iter_mut().for_each(|val| *val = V::zero());
+            }
+            index = until;
+        }
+    }
+
+    fn shift(&mut self, now: I) {
+        let front = *self.front.get_or_insert(now);
+        let since_front = now.duration_since(front).as_nanos();
+
+        if since_front < self.slot_nanos {
+            return
+        }
+
+        let slots = since_front.checked_div(self.slot_nanos).unwrap_or(u128::MAX);
+        let len = self.capacity;
+
+        let forward = if slots < len as u128 {
+            // Rotate index of the most recent slot over expired slots resetting them
+            let slots = slots as usize;
+            let start = self.head + 1;
+            let end = start + slots;
+            self.slot += slots as u128;
+
+            if end <= len {
+                self.reset(start, end);
+            } else {
+                self.reset(start, len);
+                self.reset(0, end - len);
+            }
+            self.head = (self.head + slots) % len;
+            slots as u128 * self.slot_nanos
+        } else {
+            self.chunks.iter_mut().for_each(|chunk| *chunk = None);
+            self.slot = self.slot.saturating_add(slots);
+
+            if slots == len as u128 {
+                len as u128 * self.slot_nanos
+            } else {
+                // Shift was not called for longer than the window width
+                since_front
+            }
+        };
+
+        if let Some(front) = self.front.as_mut() {
+            front.forward(from_nanos(forward));
+        }
+    }
+
+    fn head_bucket(&mut self) -> &mut V {
+        let chunk = self.head / self.chunk_size;
+        let chunk_len = self.chunk_size.min(self.capacity - chunk * self.chunk_size);
+        self.inserted[chunk] = self.slot;
+        let buckets = self.chunks[chunk].get_or_insert_with(|| (0..chunk_len).map(|_| V::zero()).collect());
+        &mut buckets[self.head % self.chunk_size]
+    }
+
+    /// Insert value to be average over at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn insert(&mut self, now: I, val: V) {
+        self.shift(now);
+        self.head_bucket().add(val);
+    }
+
+    /// Insert value of other type to be accumulated into bucket at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn accumulate<A>(&mut self, now: I, val: A) where V: Accumulate<A> {
+        self.shift(now);
+        self.head_bucket().add(val);
+    }
+
+    /// Calculate running average using time window ending at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn measurement(&mut self, now: I) -> Measurement<V> {
+        self.shift(now);
+
+        Measurement {
+            value: self.chunks.iter().flatten().flat_map(|buckets| buckets.iter()).fold(V::zero(), |sum, val| sum.merge(val)),
+            duration: self.duration,
+        }
+    }
+
+    /// Returns width of time span covered by each bucket.
+    pub fn bucket_duration(&self) -> Duration {
+        from_nanos(self.slot_nanos)
+    }
+
+    /// Returns number of chunks of buckets currently allocated.
+    pub fn allocated_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn lazily_allocated_chunks() {
+        use super::*;
+
+        let mut tw = SegmentedRunningAverage::<u32, f64>::with_chunk_size(Duration::from_secs(10), 10, 4);
+        assert_eq!(tw.measurement(0.0).unwrap(), 0);
+        assert_eq!(tw.allocated_chunks(), 0);
+
+        for second in 0..10 {
+            tw.insert(second as f64, 1);
+        }
+        assert_eq!(tw.allocated_chunks(), 3);
+        assert_eq!(tw.measurement(9.0).unwrap(), 10);
+
+        // Buckets 0 to 3 expire freeing the first chunk
+        assert_eq!(tw.measurement(13.0).unwrap(), 6);
+        assert_eq!(tw.allocated_chunks(), 2);
+
+        tw.insert(14.0, 5);
+        assert_eq!(tw.allocated_chunks(), 2);
+        assert_eq!(tw.measurement(14.0).unwrap(), 10);
+
+        // Idle for longer than the window
+        assert_eq!(tw.measurement(100.0).unwrap(), 0);
+        assert_eq!(tw.allocated_chunks(), 0);
+
+        tw.accumulate(100.5, 2u32);
+        assert_eq!(tw.measurement(100.5).to_rate(), 0.2);
+        assert_eq!(tw.bucket_duration(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn chunks_freed_when_shifted_by_one_bucket() {
+        use super::*;
+
+        let mut tw = SegmentedRunningAverage::<u32, f64>::with_chunk_size(Duration::from_secs(8), 8, 4);
+        for second in 0..4 {
+            tw.insert(second as f64, 1);
+        }
+        tw.insert(5.0, 1);
+        assert_eq!(tw.allocated_chunks(), 2);
+
+        // Window moves one bucket at a time so no reset covers whole chunk
+        for second in 6..11 {
+            assert_eq!(tw.measurement(second as f64).unwrap(), 5 - (second - 7).max(0) as u32);
+            assert_eq!(tw.allocated_chunks(), 2);
+        }
+
+        // Bucket 3 of the first chunk expires and so all of its buckets
+        assert_eq!(tw.measurement(11.0).unwrap(), 1);
+        assert_eq!(tw.allocated_chunks(), 1);
+
+        // Insert into the second chunk keeps it allocated after its older buckets expire
+        tw.insert(12.0, 1);
+        assert_eq!(tw.measurement(13.0).unwrap(), 1);
+        assert_eq!(tw.allocated_chunks(), 1);
+        assert_eq!(tw.measurement(19.0).unwrap(), 1);
+        assert_eq!(tw.allocated_chunks(), 1);
+        assert_eq!(tw.measurement(20.0).unwrap(), 0);
+        assert_eq!(tw.allocated_chunks(), 0);
+    }
+
+    #[test]
+    fn same_as_running_average() {
+        use super::*;
+        use super::super::RunningAverage;
+
+        let mut segmented = SegmentedRunningAverage::<u64, f64>::with_chunk_size(Duration::from_secs(16), 16, 3);
+        let mut flat = RunningAverage::<u64, f64>::with_capacity(Duration::from_secs(16), 16);
+
+        let mut now = 0.0;
+        for step in 0..500u64 {
+            now += (step % 7) as f64 * 0.75;
+            segmented.insert(now, step);
+            flat.insert(now, step);
+            assert_eq!(segmented.measurement(now).unwrap(), flat.measurement(now).unwrap());
+        }
+    }
+}
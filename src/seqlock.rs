@@ -0,0 +1,219 @@
+//! Single writer running average publishing measurements to many readers via seqlock.
+//!
+//! `SeqlockRunningAverage` is owned by the recording thread that inserts without taking any lock;
+//! after each insert the measurement is published and `SeqlockReader` handles read it retrying if they raced with the writer.
+//! Readers never block the writer which makes this suitable for UI threads polling a hot recording thread.
+//! ```
+//! use std::thread;
+//! use running_average::seqlock::SeqlockRunningAverage;
+//!
+//! let mut tw = SeqlockRunningAverage::<u32>::default();
+//! let reader = tw.reader();
+//!
+//! thread::spawn(move || {
+//!     // Got 2KB of data
+//!     tw.insert(2000);
+//! }).join().unwrap();
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(reader.measurement().unwrap(), 2000.0);
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+
+#[derive(Debug, Default)]
+struct Published {
+    seq: AtomicU64,
+    value: AtomicU64,
+    duration: AtomicU64,
+}
+
+impl Published {
+    fn store(&self, value: f64, duration: Duration) {
+        // Only one writer so sequence number does not need read-modify-write
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+        self.duration.store(duration.as_nanos() as u64, Ordering::Relaxed);
+
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    fn load(&self) -> Measurement<f64> {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue
+            }
+
+            let value = f64::from_bits(self.value.load(Ordering::Relaxed));
+            let duration = Duration::from_nanos(self.duration.load(Ordering::Relaxed));
+
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return Measurement { value, duration }
+            }
+        }
+    }
+}
+
+/// Running average owned by single writer that publishes measurement after each insert to `SeqlockReader` handles.
+pub struct SeqlockRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+    published: Arc<Published>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for SeqlockRunningAverage<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeqlockRunningAverage").field("inner", &self.inner).field("published", &self.published.load()).finish()
+    }
+}
+
+impl<V: Accumulate> Default for SeqlockRunningAverage<V, RealTimeSource> {
+    /// Crate new SeqlockRunningAverage instance with window of 8 seconds width and 16 buckets.
+    fn default() -> SeqlockRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::default().into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> From<RealTimeRunningAverage<V, TS>> for SeqlockRunningAverage<V, TS> {
+    fn from(running_average: RealTimeRunningAverage<V, TS>) -> SeqlockRunningAverage<V, TS> {
+        let published = Published::default();
+        published.store(0.0, running_average.inner.duration);
+
+        SeqlockRunningAverage {
+            inner: running_average,
+            published: Arc::new(published),
+        }
+    }
+}
+
+impl<V: Accumulate> SeqlockRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> SeqlockRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::new(duration).into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> SeqlockRunningAverage<V, TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> SeqlockRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source(duration, capacity, time_source).into()
+    }
+
+    /// Create new handle reading measurements published by this running average.
+    pub fn reader(&self) -> SeqlockReader {
+        SeqlockReader {
+            published: self.published.clone(),
+        }
+    }
+
+    /// Insert value to be average over now and publish measurement of time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) where V: Clone + Into<f64> {
+        self.inner.insert(val);
+        self.publish();
+    }
+
+    /// Publish measurement of time window ending now without inserting.
+    /// Call this periodically if inserts stop so that readers can observe the rate decaying.
+    /// Panics if time source time goes backwards.
+    pub fn publish(&mut self) -> Measurement<V> where V: Clone + Into<f64> {
+        let measurement = self.inner.measurement();
+        self.published.store(measurement.value.clone().into(), measurement.duration);
+        measurement
+    }
+
+    /// Return mutable reference to the underlying running average.
+    /// Note: changes are published to readers on next insert or `publish()`.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        &mut self.inner
+    }
+
+    /// Return the underlying running average consuming self.
+    pub fn into_inner(self) -> RealTimeRunningAverage<V, TS> {
+        self.inner
+    }
+}
+
+impl<V, TS: TimeSource> Insert for SeqlockRunningAverage<V, TS> where V: Accumulate + Clone + Into<f64> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SeqlockRunningAverage::insert(self, val)
+    }
+}
+
+/// Cloneable handle reading the most recently published measurement of `SeqlockRunningAverage` without blocking its writer.
+#[derive(Clone)]
+pub struct SeqlockReader {
+    published: Arc<Published>,
+}
+
+impl fmt::Debug for SeqlockReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeqlockReader").field("published", &self.published.load()).finish()
+    }
+}
+
+impl SeqlockReader {
+    /// Returns the most recently published measurement.
+    /// Retries if the writer is publishing new measurement at the same time.
+    pub fn measurement(&self) -> Measurement<f64> {
+        self.published.load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn read_published_measurements() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = SeqlockRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let reader = tw.reader();
+        assert_eq!(reader.measurement().unwrap(), 0.0);
+        assert_eq!(reader.measurement().duration(), Duration::from_secs(4));
+
+        tw.insert(10u32);
+        tw.insert(30);
+        assert_eq!(reader.measurement().rate(), 10.0);
+
+        tw.get_mut().time_source().time_shift(4.0);
+        assert_eq!(reader.measurement().unwrap(), 40.0);
+        assert_eq!(tw.publish().unwrap(), 0);
+        assert_eq!(reader.measurement().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn read_while_writing() {
+        use super::*;
+        use std::thread;
+        use super::super::ManualTimeSource;
+
+        let mut tw = SeqlockRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let reader = tw.reader();
+
+        let writer = thread::spawn(move || for _ in 0..10_000 { tw.insert(1u32) });
+
+        let mut last = 0.0;
+        while !writer.is_finished() {
+            let measurement = reader.measurement();
+            assert_eq!(measurement.duration(), Duration::from_secs(4));
+            assert!(measurement.unwrap() >= last);
+            last = reader.measurement().unwrap();
+        }
+        writer.join().unwrap();
+
+        assert_eq!(reader.measurement().unwrap(), 10_000.0);
+    }
+}
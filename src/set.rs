@@ -0,0 +1,184 @@
+//! Several named running averages sharing one time window.
+//!
+//! `MetricSet` keeps values of all of its metrics in the same buckets so that recording several related values per event
+//! reads the time source and shifts the window only once.
+//! ```
+//! use std::time::Duration;
+//! use running_average::set::MetricSet;
+//!
+//! let mut requests = MetricSet::new(Duration::from_secs(8), &["requests", "bytes_in", "bytes_out"]);
+//!
+//! requests.insert_many(vec![("requests", 1), ("bytes_in", 200), ("bytes_out", 2000)]);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(requests.measurement("bytes_out").unwrap(), 2000);
+//! assert_eq!(requests.measurements().len(), 3);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Measurement, RealTimeSource, RunningAverage, TimeSource};
+
+/// Values of metrics accumulated in single bucket indexed by metric; grows on first insert into metric.
+#[derive(Debug, Clone, PartialEq)]
+struct Row<V>(Vec<V>);
+
+impl<V: Accumulate> Accumulate<(usize, V)> for Row<V> {
+    fn zero() -> Row<V> {
+        Row(Vec::new())
+    }
+
+    fn add(&mut self, (index, val): (usize, V)) {
+        if self.0.len() <= index {
+            self.0.resize_with(index + 1, V::zero);
+        }
+        self.0[index].add(val)
+    }
+
+    fn merge(&self, other: &Row<V>) -> Row<V> {
+        let (long, short) = if self.0.len() >= other.0.len() { (self, other) } else { (other, self) };
+        Row(long.0.iter().enumerate().map(|(index, val)| match short.0.get(index) {
+            Some(other) => val.merge(other),
+            None => val.merge(&V::zero()),
+        }).collect())
+    }
+}
+
+impl<V: Accumulate> Accumulate for Row<V> {
+    fn zero() -> Row<V> {
+        Row(Vec::new())
+    }
+
+    fn add(&mut self, other: Row<V>) {
+        for (index, val) in other.0.into_iter().enumerate() {
+            Accumulate::add(self, (index, val))
+        }
+    }
+
+    fn merge(&self, other: &Row<V>) -> Row<V> {
+        Accumulate::<(usize, V)>::merge(self, other)
+    }
+}
+
+/// Running averages of given names measured over the same time window using given time source to obtain value of `now` instant.
+pub struct MetricSet<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    names: Vec<&'static str>,
+    inner: RunningAverage<Row<V>, TS::Instant>,
+    time_source: TS,
+}
+
+impl<V: Accumulate + fmt::Debug, TS: TimeSource> fmt::Debug for MetricSet<V, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MetricSet")
+            .field("names", &self.names)
+            .field("inner", &self.inner)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<V: Accumulate> MetricSet<V, RealTimeSource> {
+    /// Crate new instance of metrics of given names with window of given width duration and 16 buckets using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration, names: &[&'static str]) -> MetricSet<V, RealTimeSource> {
+        MetricSet::with_time_source(duration, 16, names, RealTimeSource)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> MetricSet<V, TS> {
+    /// Crate new instance of metrics of given names with window of given width duration and number of buckets using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, names: &[&'static str], time_source: TS) -> MetricSet<V, TS> {
+        MetricSet {
+            names: names.to_vec(),
+            inner: RunningAverage::with_capacity(duration, capacity),
+            time_source,
+        }
+    }
+
+    fn index(&self, name: &str) -> usize {
+        self.names.iter().position(|n| *n == name).unwrap_or_else(|| panic!("MetricSet has no metric named {:?}", name))
+    }
+
+    /// Insert value of metric of given name to be average over now.
+    /// Panics if there is no metric of given name or if time source time goes backwards.
+    pub fn insert(&mut self, name: &str, val: V) {
+        let index = self.index(name);
+        let now = self.time_source.now();
+        self.inner.accumulate(now, (index, val))
+    }
+
+    /// Insert values of metrics of given names to be average over now reading time source once.
+    /// Panics if there is no metric of given name or if time source time goes backwards.
+    pub fn insert_many<'n>(&mut self, values: impl IntoIterator<Item = (&'n str, V)>) {
+        let now = self.time_source.now();
+        let mut row = Row(Vec::new());
+        for (name, val) in values {
+            row.add((self.index(name), val));
+        }
+        self.inner.insert(now, row)
+    }
+
+    /// Calculate running average of metric of given name using time window ending now.
+    /// Panics if there is no metric of given name or if time source time goes backwards.
+    pub fn measurement(&mut self, name: &str) -> Measurement<V> {
+        let index = self.index(name);
+        let now = self.time_source.now();
+
+        Measurement {
+            value: self.inner.buckets(now).filter_map(|row| row.0.get(index)).fold(V::zero(), |sum, val| sum.merge(val)),
+            duration: self.inner.duration,
+        }
+    }
+
+    /// Calculate running averages of all metrics using time window ending now, in order of their names given on construction.
+    /// Panics if time source time goes backwards.
+    pub fn measurements(&mut self) -> Vec<(&'static str, Measurement<V>)> {
+        let now = self.time_source.now();
+        let measurement = self.inner.measurement(now);
+        let duration = measurement.duration;
+        let mut values = measurement.value.0.into_iter();
+
+        self.names.iter().map(|name| (*name, Measurement {
+            value: values.next().unwrap_or_else(V::zero),
+            duration,
+        })).collect()
+    }
+
+    /// Returns names of metrics.
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn shared_window() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut set = MetricSet::with_time_source(Duration::from_secs(4), 4, &["requests", "bytes"], ManualTimeSource::new());
+        assert_eq!(set.names(), &["requests", "bytes"]);
+        assert_eq!(set.measurement("bytes").unwrap(), 0);
+
+        set.insert("bytes", 1000);
+        set.time_source().time_shift(1.0);
+        set.insert_many(vec![("requests", 1), ("bytes", 500)]);
+        set.insert_many(vec![("requests", 1)]);
+
+        assert_eq!(set.measurement("requests").unwrap(), 2);
+        assert_eq!(set.measurement("bytes").to_rate(), 375.0);
+
+        set.time_source().time_shift(3.0);
+        let measurements = set.measurements();
+        assert_eq!(measurements[0].0, "requests");
+        assert_eq!(*measurements[0].1.value(), 2);
+        assert_eq!(measurements[1].0, "bytes");
+        assert_eq!(*measurements[1].1.value(), 500);
+    }
+}
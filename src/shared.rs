@@ -0,0 +1,263 @@
+//! Running averages that can be shared between threads behind a mutex or a read-write lock.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+use super::labels::{LabeledMeasurement, Labels};
+
+/// Cloneable handle to `RealTimeRunningAverage` protected by a mutex so it can be inserted to and measured from multiple threads.
+pub struct SharedRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: Arc<Mutex<RealTimeRunningAverage<V, TS>>>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for SharedRunningAverage<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedRunningAverage").field("inner", &self.inner).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Clone for SharedRunningAverage<V, TS> {
+    fn clone(&self) -> SharedRunningAverage<V, TS> {
+        SharedRunningAverage {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<V: Accumulate> Default for SharedRunningAverage<V, RealTimeSource> {
+    /// Crate new SharedRunningAverage instance with window of 8 seconds width and 16 buckets.
+    fn default() -> SharedRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::default().into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> From<RealTimeRunningAverage<V, TS>> for SharedRunningAverage<V, TS> {
+    fn from(running_average: RealTimeRunningAverage<V, TS>) -> SharedRunningAverage<V, TS> {
+        SharedRunningAverage {
+            inner: Arc::new(Mutex::new(running_average)),
+        }
+    }
+}
+
+impl<V: Accumulate> SharedRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> SharedRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::new(duration).into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> SharedRunningAverage<V, TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> SharedRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source(duration, capacity, time_source).into()
+    }
+
+    /// Lock the shared running average for exclusive access.
+    /// Note: If other thread panicked while holding the lock the running average is still accessible.
+    pub fn lock(&self) -> MutexGuard<'_, RealTimeRunningAverage<V, TS>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&self, val: V) {
+        self.lock().insert(val)
+    }
+
+    /// Calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&self) -> Measurement<V> {
+        self.lock().measurement()
+    }
+
+    /// Calculate running average using time window ending now together with name and labels of the shared running average.
+    /// Panics if time source time goes backwards.
+    pub fn labeled_measurement(&self) -> LabeledMeasurement<V> {
+        self.lock().labeled_measurement()
+    }
+
+    /// Calculate running average using time window ending now and reset all buckets.
+    /// See `RunningAverage::drain()`.
+    /// Panics if time source time goes backwards.
+    pub fn drain(&self) -> Measurement<V> {
+        self.lock().drain()
+    }
+
+    /// Render values accumulated in each bucket of time window ending now as Unicode block characters, oldest bucket first.
+    /// Panics if time source time goes backwards.
+    pub fn sparkline(&self) -> String where V: Clone + Into<f64> {
+        self.lock().sparkline()
+    }
+
+    /// Suggest how long to wait before next insert so that rate measured over the time window drops to given target rate.
+    /// Returns zero duration if measured rate is not above the target rate; assumes nothing more is inserted while waiting.
+    /// Panics if time source time goes backwards.
+    pub fn suggest_delay(&self, target_rate: f64) -> Duration where V: Clone + Into<f64> {
+        self.lock().suggest_delay(target_rate)
+    }
+
+    /// Calculate standard error of the mean of bucket rates of time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn standard_error(&self) -> f64 where V: Clone + Into<f64> {
+        self.lock().standard_error()
+    }
+
+    /// Returns copy of name and labels of the shared running average.
+    pub fn labels(&self) -> Labels {
+        self.lock().labels().clone()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for SharedRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SharedRunningAverage::insert(self, val)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for &SharedRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SharedRunningAverage::insert(self, val)
+    }
+}
+
+/// Cloneable handle to `RealTimeRunningAverage` protected by a read-write lock for read-heavy use.
+/// Measurements take shared read lock (without shifting the window) and only inserts take exclusive write lock.
+pub struct SharedRwRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: Arc<RwLock<RealTimeRunningAverage<V, TS>>>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for SharedRwRunningAverage<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedRwRunningAverage").field("inner", &self.inner).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Clone for SharedRwRunningAverage<V, TS> {
+    fn clone(&self) -> SharedRwRunningAverage<V, TS> {
+        SharedRwRunningAverage {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<V: Accumulate> Default for SharedRwRunningAverage<V, RealTimeSource> {
+    /// Crate new SharedRwRunningAverage instance with window of 8 seconds width and 16 buckets.
+    fn default() -> SharedRwRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::default().into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> From<RealTimeRunningAverage<V, TS>> for SharedRwRunningAverage<V, TS> {
+    fn from(running_average: RealTimeRunningAverage<V, TS>) -> SharedRwRunningAverage<V, TS> {
+        SharedRwRunningAverage {
+            inner: Arc::new(RwLock::new(running_average)),
+        }
+    }
+}
+
+impl<V: Accumulate> SharedRwRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> SharedRwRunningAverage<V, RealTimeSource> {
+        RealTimeRunningAverage::new(duration).into()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> SharedRwRunningAverage<V, TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> SharedRwRunningAverage<V, TS> {
+        RealTimeRunningAverage::with_time_source(duration, capacity, time_source).into()
+    }
+
+    /// Lock the shared running average for shared read access.
+    /// Note: If other thread panicked while holding the lock the running average is still accessible.
+    pub fn read(&self) -> RwLockReadGuard<'_, RealTimeRunningAverage<V, TS>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the shared running average for exclusive access.
+    /// Note: If other thread panicked while holding the lock the running average is still accessible.
+    pub fn write(&self) -> RwLockWriteGuard<'_, RealTimeRunningAverage<V, TS>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Insert value to be average over now.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&self, val: V) {
+        self.write().insert(val)
+    }
+
+    /// Calculate running average using time window ending now holding only read lock.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&self) -> Measurement<V> {
+        self.read().peek()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for SharedRwRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SharedRwRunningAverage::insert(self, val)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for &SharedRwRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        SharedRwRunningAverage::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn insert_from_threads() {
+        use super::*;
+        use std::thread;
+        use super::super::ManualTimeSource;
+
+        let shared = SharedRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || for _ in 0..10 { shared.insert(1) })
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(shared.measurement().unwrap(), 40);
+        shared.lock().time_source().time_shift(4.0);
+        assert_eq!(shared.measurement().unwrap(), 0);
+    }
+
+    #[test]
+    fn read_while_inserting_from_threads() {
+        use super::*;
+        use std::thread;
+        use super::super::ManualTimeSource;
+
+        let shared = SharedRwRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || for _ in 0..10 { shared.insert(1); shared.measurement(); })
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(shared.measurement().unwrap(), 40);
+        shared.write().time_source().time_shift(4.0);
+        assert_eq!(shared.measurement().unwrap(), 0);
+    }
+}
@@ -0,0 +1,251 @@
+//! Destinations that periodically taken measurements can be recorded to.
+//!
+//! The `MeasurementSink` trait allows measurement reporting destinations to be plugged in; `JsonLinesSink` writes each measurement as a JSON object line.
+//! ```
+//! use std::time::SystemTime;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::sink::{MeasurementSink, JsonLinesSink};
+//!
+//! let mut sink = JsonLinesSink::new(Vec::new());
+//!
+//! let mut tw = RealTimeRunningAverage::default();
+//! tw.insert(2000);
+//!
+//! sink.record(SystemTime::now(), &tw.measurement()).unwrap();
+//! assert!(String::from_utf8(sink.into_inner()).unwrap().ends_with(",\"window\":8,\"value\":2000,\"rate\":250}\n"));
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use super::Measurement;
+use super::labels::{LabeledMeasurement, Labels};
+
+/// Types implementing this trait can be used to record measurements taken at given wall clock time.
+pub trait MeasurementSink<V> {
+    /// Type of error returned when measurement could not be recorded.
+    type Error;
+
+    /// Record measurement taken at given wall clock time.
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Self::Error>;
+
+    /// Record measurement taken at given wall clock time together with name and labels of the running average it was taken from.
+    /// Default implementation ignores name and labels and calls `record()`.
+    fn record_labeled(&mut self, ts: SystemTime, measurement: &LabeledMeasurement<V>) -> Result<(), Self::Error> {
+        self.record(ts, measurement)
+    }
+}
+
+impl<V, S: MeasurementSink<V> + ?Sized> MeasurementSink<V> for &mut S {
+    type Error = S::Error;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Self::Error> {
+        (**self).record(ts, measurement)
+    }
+
+    fn record_labeled(&mut self, ts: SystemTime, measurement: &LabeledMeasurement<V>) -> Result<(), Self::Error> {
+        (**self).record_labeled(ts, measurement)
+    }
+}
+
+impl<V, S: MeasurementSink<V> + ?Sized> MeasurementSink<V> for Box<S> {
+    type Error = S::Error;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Self::Error> {
+        (**self).record(ts, measurement)
+    }
+
+    fn record_labeled(&mut self, ts: SystemTime, measurement: &LabeledMeasurement<V>) -> Result<(), Self::Error> {
+        (**self).record_labeled(ts, measurement)
+    }
+}
+
+/// Seconds since UNIX epoch of given wall clock time; negative for times before the epoch.
+pub(crate) fn unix_seconds(ts: SystemTime) -> f64 {
+    use std::time::UNIX_EPOCH;
+
+    match ts.duration_since(UNIX_EPOCH) {
+        Ok(since) => super::dts(since),
+        Err(err) => -super::dts(err.duration()),
+    }
+}
+
+/// MeasurementSink that writes each measurement as a line containing JSON object with
+/// `timestamp` (seconds since UNIX epoch), `window` (width of the time window in seconds), `value` and `rate` fields.
+/// Non-finite numbers are written as `null`.
+/// Labelled sink, or sink recording labelled measurement of running average with name or labels, also writes `name` and `labels` object fields first.
+#[derive(Debug)]
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+    labels: String,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    /// Create new JsonLinesSink writing lines to given writer.
+    pub fn new(writer: W) -> JsonLinesSink<W> {
+        JsonLinesSink {
+            writer,
+            labels: String::new(),
+        }
+    }
+
+    /// Write given name (if set) and labels with each measurement.
+    pub fn labeled(mut self, labels: &Labels) -> JsonLinesSink<W> {
+        self.labels = json_label_fields(labels);
+        self
+    }
+
+    /// Return reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Return mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Return the underlying writer consuming self.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+struct JsonNumber(f64);
+
+impl fmt::Display for JsonNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_finite() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "null")
+        }
+    }
+}
+
+struct JsonString<'s>(&'s str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// Format name (if set) and labels as JSON object fields followed by comma.
+fn json_label_fields(labels: &Labels) -> String {
+    match labels.name() {
+        Some(name) => format!("\"name\":{},\"labels\":{},", JsonString(name), json_labels(labels)),
+        None => format!("\"labels\":{},", json_labels(labels)),
+    }
+}
+
+/// Format key/value pairs of given labels as JSON object.
+pub(crate) fn json_labels(labels: &Labels) -> String {
+    let pairs = labels.pairs().into_iter().map(|(key, value)| format!("{}:{}", JsonString(key), JsonString(value))).collect::<Vec<_>>();
+    format!("{{{}}}", pairs.join(","))
+}
+
+impl<V, W: Write> MeasurementSink<V> for JsonLinesSink<W> where V: Clone + Into<f64> {
+    type Error = io::Error;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), io::Error> {
+        write_json_line(&mut self.writer, &self.labels, ts, measurement)
+    }
+
+    /// Writes name and labels of the measurement unless it has none; then labels given to `labeled()` are written.
+    fn record_labeled(&mut self, ts: SystemTime, measurement: &LabeledMeasurement<V>) -> Result<(), io::Error> {
+        if measurement.labels().is_empty() {
+            self.record(ts, measurement)
+        } else {
+            write_json_line(&mut self.writer, &json_label_fields(measurement.labels()), ts, measurement)
+        }
+    }
+}
+
+fn write_json_line<V: Clone + Into<f64>>(writer: &mut impl Write, labels: &str, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), io::Error> {
+    writeln!(writer, "{{{}\"timestamp\":{},\"window\":{},\"value\":{},\"rate\":{}}}",
+        labels,
+        JsonNumber(unix_seconds(ts)),
+        JsonNumber(super::dts(measurement.duration())),
+        JsonNumber(measurement.value().clone().into()),
+        JsonNumber(measurement.rate_f64()))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn json_lines() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut sink = JsonLinesSink::new(Vec::new());
+
+        sink.record(UNIX_EPOCH + Duration::from_millis(1500), &Measurement { value: 40, duration: Duration::from_secs(4) }).unwrap();
+        sink.record(UNIX_EPOCH + Duration::from_secs(2), &Measurement { value: 2.5f64, duration: Duration::from_millis(500) }).unwrap();
+
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"timestamp\":1.5,\"window\":4,\"value\":40,\"rate\":10}\n{\"timestamp\":2,\"window\":0.5,\"value\":2.5,\"rate\":5}\n");
+    }
+
+    #[test]
+    fn json_lines_non_finite() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut sink = JsonLinesSink::new(Vec::new());
+
+        sink.record(UNIX_EPOCH, &Measurement { value: 1.0f64, duration: Duration::from_secs(0) }).unwrap();
+
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(), "{\"timestamp\":0,\"window\":0,\"value\":1,\"rate\":null}\n");
+    }
+
+    #[test]
+    fn json_lines_labeled() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut labels = Labels::new();
+        let mut sink = JsonLinesSink::new(Vec::new()).labeled(&labels);
+        sink.record(UNIX_EPOCH, &Measurement { value: 4, duration: Duration::from_secs(4) }).unwrap();
+
+        labels.set_name("egress");
+        labels.insert("iface", "eth\"0\"");
+        labels.insert("zone", "a\n");
+        let mut sink = JsonLinesSink::new(sink.into_inner()).labeled(&labels);
+        sink.record(UNIX_EPOCH, &Measurement { value: 4, duration: Duration::from_secs(4) }).unwrap();
+
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"labels\":{},\"timestamp\":0,\"window\":4,\"value\":4,\"rate\":1}\n\
+            {\"name\":\"egress\",\"labels\":{\"iface\":\"eth\\\"0\\\"\",\"zone\":\"a\\u000a\"},\"timestamp\":0,\"window\":4,\"value\":4,\"rate\":1}\n");
+    }
+
+    #[test]
+    fn json_lines_labeled_measurement() {
+        use super::*;
+        use std::sync::Arc;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut labels = Labels::new();
+        labels.set_name("egress");
+        let mut sink = JsonLinesSink::new(Vec::new()).labeled(&labels);
+
+        sink.record_labeled(UNIX_EPOCH, &LabeledMeasurement::new(Measurement { value: 4, duration: Duration::from_secs(4) }, Arc::new(Labels::new()))).unwrap();
+        labels.set_name("ingress");
+        labels.insert("iface", "eth0");
+        sink.record_labeled(UNIX_EPOCH, &LabeledMeasurement::new(Measurement { value: 4, duration: Duration::from_secs(4) }, Arc::new(labels))).unwrap();
+
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"name\":\"egress\",\"labels\":{},\"timestamp\":0,\"window\":4,\"value\":4,\"rate\":1}\n\
+            {\"name\":\"ingress\",\"labels\":{\"iface\":\"eth0\"},\"timestamp\":0,\"window\":4,\"value\":4,\"rate\":1}\n");
+    }
+}
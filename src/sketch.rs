@@ -0,0 +1,425 @@
+//! Approximate per-key windowed rates over huge key cardinalities using one count-min sketch per bucket and windowed heavy hitters.
+//!
+//! `SketchedRateMap` accumulates values inserted for each key (e.g. IP address or URL) into fixed size count-min sketch of the current bucket
+//! instead of keeping per-key state; sketches of all buckets are merged on measurement so memory usage does not depend on number of keys.
+//! Estimated values never underestimate and with probability of `1 - e^-depth` overestimate by no more than `e / width` of the total of all keys.
+//! ```
+//! use std::time::Duration;
+//! use running_average::sketch::SketchedRateMap;
+//!
+//! let mut requests = SketchedRateMap::new(Duration::from_secs(8));
+//!
+//! requests.insert("10.0.0.1", 1.0);
+//! requests.insert("10.0.0.1", 1.0);
+//! requests.insert("10.0.0.2", 1.0);
+//!
+//! // Note: this may fail as it is based on real time
+//! let rates = requests.measurement();
+//! assert_eq!(rates.value("10.0.0.1"), 2.0);
+//! assert_eq!(rates.rate("10.0.0.2"), 0.125);
+//! ```
+//!
+//! `TopK` reports keys with highest windowed rates (e.g. top talkers over the last minute) monitoring fixed number of keys per bucket with the space-saving algorithm.
+//! ```
+//! use std::time::Duration;
+//! use running_average::sketch::TopK;
+//!
+//! let mut talkers = TopK::new(Duration::from_secs(60));
+//!
+//! talkers.insert("10.0.0.1", 6000.0);
+//! talkers.insert("10.0.0.2", 120.0);
+//! talkers.insert("10.0.0.1", 6000.0);
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(talkers.measurement(1), vec![("10.0.0.1", 200.0)]);
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use super::{dts, Accumulate, RealTimeSource, RunningAverage, TimeSource};
+
+fn hash<K: Hash + ?Sized>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Value of key hash inserted into count-min sketch of given dimensions.
+#[derive(Debug, Clone, Copy)]
+struct Increment {
+    hash: u64,
+    val: f64,
+    width: usize,
+    depth: usize,
+}
+
+/// Count-min sketch of `depth` rows of `width` counters; counters are allocated on first increment so that empty sketch takes no memory.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CountMin {
+    width: usize,
+    counters: Vec<f64>,
+}
+
+impl CountMin {
+    /// Iterate counter indices of given key hash, one in each row.
+    fn indices(hash: u64, width: usize, depth: usize) -> impl Iterator<Item = usize> {
+        // Derive row hashes from two halves of the key hash (Kirsch-Mitzenmacher)
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        (0..depth as u64).map(move |row| row as usize * width + (h1.wrapping_add(row.wrapping_mul(h2)) % width as u64) as usize)
+    }
+
+    fn estimate(&self, hash: u64) -> f64 {
+        if self.counters.is_empty() {
+            return 0.0
+        }
+        let depth = self.counters.len() / self.width;
+        CountMin::indices(hash, self.width, depth).map(|index| self.counters[index]).fold(f64::INFINITY, f64::min)
+    }
+
+    fn total(&self) -> f64 {
+        self.counters.iter().take(self.width).sum()
+    }
+}
+
+impl Accumulate<Increment> for CountMin {
+    fn zero() -> CountMin {
+        CountMin::default()
+    }
+
+    fn add(&mut self, increment: Increment) {
+        if self.counters.is_empty() {
+            self.width = increment.width;
+            self.counters = vec![0.0; increment.width * increment.depth];
+        }
+        for index in CountMin::indices(increment.hash, increment.width, increment.depth) {
+            self.counters[index] += increment.val;
+        }
+    }
+
+    fn merge(&self, other: &CountMin) -> CountMin {
+        if self.counters.is_empty() {
+            return other.clone()
+        }
+        let mut sum = self.clone();
+        sum.counters.iter_mut().zip(other.counters.iter()).for_each(|(sum, val)| *sum += val);
+        sum
+    }
+}
+
+impl Accumulate for CountMin {
+    fn zero() -> CountMin {
+        CountMin::default()
+    }
+
+    fn add(&mut self, other: CountMin) {
+        *self = Accumulate::<CountMin>::merge(self, &other)
+    }
+
+    fn merge(&self, other: &CountMin) -> CountMin {
+        Accumulate::<Increment>::merge(self, other)
+    }
+}
+
+/// Approximate running averages of values inserted for keys of type K using given time source to obtain value of `now` instant.
+pub struct SketchedRateMap<K: ?Sized, TS: TimeSource = RealTimeSource> {
+    buckets: RunningAverage<CountMin, TS::Instant>,
+    width: usize,
+    depth: usize,
+    time_source: TS,
+    key: PhantomData<fn(&K)>,
+}
+
+impl<K: ?Sized, TS: TimeSource> fmt::Debug for SketchedRateMap<K, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SketchedRateMap")
+            .field("buckets", &self.buckets)
+            .field("width", &self.width)
+            .field("depth", &self.depth)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<K: Hash + ?Sized> SketchedRateMap<K, RealTimeSource> {
+    /// Crate new instance with window of given width duration, 16 buckets, sketches of 2048 counters in 4 rows and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> SketchedRateMap<K, RealTimeSource> {
+        SketchedRateMap::with_time_source(duration, 16, 2048, 4, RealTimeSource)
+    }
+}
+
+impl<K: Hash + ?Sized, TS: TimeSource> SketchedRateMap<K, TS> {
+    /// Crate new instance with window of given width duration and number of buckets holding sketches of given width of counters in given depth of rows
+    /// using given as time source for `now` instant.
+    /// Panics if width or depth is 0.
+    pub fn with_time_source(duration: Duration, capacity: usize, width: usize, depth: usize, time_source: TS) -> SketchedRateMap<K, TS> {
+        assert!(width > 0 && depth > 0, "SketchedRateMap sketch dimensions cannot be 0");
+        SketchedRateMap {
+            buckets: RunningAverage::with_capacity(duration, capacity),
+            width,
+            depth,
+            time_source,
+            key: PhantomData,
+        }
+    }
+
+    /// Insert value to be average over now for given key.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, key: &K, val: f64) {
+        let now = self.time_source.now();
+        self.buckets.accumulate(now, Increment { hash: hash(key), val, width: self.width, depth: self.depth })
+    }
+
+    /// Merge sketches of time window ending now for estimating values and rates of keys.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> SketchedRates<K> {
+        let now = self.time_source.now();
+        let measurement = self.buckets.measurement(now);
+
+        SketchedRates {
+            sketch: measurement.value,
+            duration: measurement.duration,
+            key: PhantomData,
+        }
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+/// Merged sketch of values inserted within the time window for estimating values and rates of keys.
+pub struct SketchedRates<K: ?Sized> {
+    sketch: CountMin,
+    duration: Duration,
+    key: PhantomData<fn(&K)>,
+}
+
+impl<K: ?Sized> fmt::Debug for SketchedRates<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SketchedRates").field("duration", &self.duration).field("total", &self.sketch.total()).finish()
+    }
+}
+
+impl<K: Hash + ?Sized> SketchedRates<K> {
+    /// Returns estimated sum of values inserted for given key within the time window.
+    pub fn value(&self, key: &K) -> f64 {
+        self.sketch.estimate(hash(key))
+    }
+
+    /// Returns estimated rate of values inserted for given key per second.
+    pub fn rate(&self, key: &K) -> f64 {
+        self.value(key) / dts(self.duration)
+    }
+
+    /// Returns exact sum of values inserted for all keys within the time window.
+    pub fn total(&self) -> f64 {
+        self.sketch.total()
+    }
+
+    /// Returns width of the time window.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Space-saving summary monitoring up to `counters` keys with highest sums of values.
+#[derive(Debug, Clone)]
+struct SpaceSaving<K> {
+    counters: usize,
+    monitored: HashMap<K, f64>,
+}
+
+impl<K: Hash + Eq + Clone> SpaceSaving<K> {
+    /// Keep only `counters` keys of highest sums.
+    fn prune(&mut self) {
+        if self.monitored.len() > self.counters {
+            let mut entries: Vec<_> = self.monitored.drain().collect();
+            entries.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            entries.truncate(self.counters);
+            self.monitored.extend(entries);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Accumulate<(K, f64, usize)> for SpaceSaving<K> {
+    fn zero() -> SpaceSaving<K> {
+        SpaceSaving {
+            counters: 0,
+            monitored: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, (key, val, counters): (K, f64, usize)) {
+        self.counters = counters;
+
+        if let Some(sum) = self.monitored.get_mut(&key) {
+            *sum += val;
+        } else if self.monitored.len() < self.counters {
+            self.monitored.insert(key, val);
+        } else {
+            // Replace key of the lowest sum taking over its sum as the new key may have been seen while not monitored
+            let (min_key, min) = self.monitored.iter()
+                .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(Ordering::Equal))
+                .map(|(key, sum)| (key.clone(), *sum))
+                .expect("SpaceSaving with no counters");
+            self.monitored.remove(&min_key);
+            self.monitored.insert(key, min + val);
+        }
+    }
+
+    fn merge(&self, other: &SpaceSaving<K>) -> SpaceSaving<K> {
+        let mut sum = self.clone();
+        sum.counters = self.counters.max(other.counters);
+        for (key, val) in &other.monitored {
+            *sum.monitored.entry(key.clone()).or_insert(0.0) += val;
+        }
+        sum.prune();
+        sum
+    }
+}
+
+impl<K: Hash + Eq + Clone> Accumulate for SpaceSaving<K> {
+    fn zero() -> SpaceSaving<K> {
+        Accumulate::<(K, f64, usize)>::zero()
+    }
+
+    fn add(&mut self, other: SpaceSaving<K>) {
+        *self = Accumulate::<SpaceSaving<K>>::merge(self, &other)
+    }
+
+    fn merge(&self, other: &SpaceSaving<K>) -> SpaceSaving<K> {
+        Accumulate::<(K, f64, usize)>::merge(self, other)
+    }
+}
+
+/// Heavy hitters: keys of type K with highest running averages of inserted values using given time source to obtain value of `now` instant.
+/// Each bucket monitors given number of keys with the space-saving algorithm and summaries of all buckets are merged on measurement.
+/// Sums of values of the reported keys may be overestimated by up to sum of the least monitored key of each bucket.
+pub struct TopK<K: Hash + Eq + Clone, TS: TimeSource = RealTimeSource> {
+    buckets: RunningAverage<SpaceSaving<K>, TS::Instant>,
+    counters: usize,
+    time_source: TS,
+}
+
+impl<K: Hash + Eq + Clone + fmt::Debug, TS: TimeSource> fmt::Debug for TopK<K, TS> where TS::Instant: fmt::Debug, TS: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TopK")
+            .field("buckets", &self.buckets)
+            .field("counters", &self.counters)
+            .field("time_source", &self.time_source)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone> TopK<K, RealTimeSource> {
+    /// Crate new instance with window of given width duration, 16 buckets monitoring 64 keys each and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> TopK<K, RealTimeSource> {
+        TopK::with_time_source(duration, 16, 64, RealTimeSource)
+    }
+}
+
+impl<K: Hash + Eq + Clone, TS: TimeSource> TopK<K, TS> {
+    /// Crate new instance with window of given width duration and number of buckets each monitoring given number of keys
+    /// using given as time source for `now` instant.
+    /// Monitoring a few times more keys than will be queried improves accuracy.
+    /// Panics if number of monitored keys is 0.
+    pub fn with_time_source(duration: Duration, capacity: usize, counters: usize, time_source: TS) -> TopK<K, TS> {
+        assert!(counters > 0, "TopK number of monitored keys cannot be 0");
+        TopK {
+            buckets: RunningAverage::with_capacity(duration, capacity),
+            counters,
+            time_source,
+        }
+    }
+
+    /// Insert value to be average over now for given key.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, key: K, val: f64) {
+        let now = self.time_source.now();
+        self.buckets.accumulate(now, (key, val, self.counters))
+    }
+
+    /// Returns up to given number of keys with highest rate of values inserted within time window ending now with their estimated rates, highest first.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self, k: usize) -> Vec<(K, f64)> {
+        let now = self.time_source.now();
+        let measurement = self.buckets.measurement(now);
+        let duration = dts(measurement.duration);
+
+        let mut top: Vec<_> = measurement.value.monitored.into_iter().map(|(key, sum)| (key, sum / duration)).collect();
+        top.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        top.truncate(k);
+        top
+    }
+
+    /// Return mutable reference to time source used.
+    pub fn time_source(&mut self) -> &mut TS {
+        &mut self.time_source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn estimate_keyed_rates() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = SketchedRateMap::with_time_source(Duration::from_secs(4), 4, 512, 4, ManualTimeSource::new());
+        assert_eq!(tw.measurement().value(&0u32), 0.0);
+
+        for key in 0..1000u32 {
+            tw.insert(&key, 1.0);
+        }
+        tw.insert(&42, 99.0);
+        tw.time_source().time_shift(2.0);
+        tw.insert(&7, 10.0);
+
+        let rates = tw.measurement();
+        assert_eq!(rates.total(), 1109.0);
+        assert!(rates.value(&42) >= 100.0);
+        assert!(rates.value(&42) <= 100.0 + 1109.0 * std::f64::consts::E / 512.0);
+        assert!(rates.rate(&7) >= 11.0 / 4.0);
+
+        tw.time_source().time_shift(2.0);
+        let rates = tw.measurement();
+        assert_eq!(rates.total(), 10.0);
+        assert_eq!(rates.value(&7), 10.0);
+        assert_eq!(rates.value(&42), 0.0);
+    }
+
+    #[test]
+    fn heavy_hitters() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = TopK::with_time_source(Duration::from_secs(4), 4, 8, ManualTimeSource::new());
+        assert!(tw.measurement(3).is_empty());
+
+        for round in 0..10 {
+            tw.insert("a", 10.0);
+            tw.insert("b", 5.0);
+            tw.insert("c", 1.0);
+            for key in 0..10 {
+                tw.insert(["x", "y", "z", "v", "w", "u", "t", "s", "r", "q"][(key + round) % 10], 0.1);
+            }
+        }
+        tw.time_source().time_shift(2.0);
+        tw.insert("c", 100.0);
+
+        let top = tw.measurement(3);
+        assert_eq!(top.iter().map(|(key, _)| *key).collect::<Vec<_>>(), vec!["c", "a", "b"]);
+        assert_eq!(top[1].1, 25.0);
+        assert!(top[0].1 >= 110.0 / 4.0);
+
+        tw.time_source().time_shift(2.0);
+        assert_eq!(tw.measurement(3), vec![("c", 25.0)]);
+    }
+}
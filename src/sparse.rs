@@ -0,0 +1,229 @@
+//! Running average storing only buckets that values were inserted into for bursty, mostly idle streams.
+//!
+//! `SparseRunningAverage` keeps inserted buckets as list of bucket number and value pairs while the share of buckets in use is low
+//! and switches to dense array of all buckets when it passes given density threshold; it switches back to sparse storage when all buckets expire.
+//! ```
+//! use std::time::{Duration, Instant};
+//! use running_average::sparse::SparseRunningAverage;
+//!
+//! // One hour window with one second buckets
+//! let mut tw = SparseRunningAverage::new(Duration::from_secs(3600), 3600);
+//! let now = Instant::now();
+//!
+//! tw.insert(now, 2000);
+//!
+//! assert!(!tw.is_dense());
+//! assert_eq!(tw.measurement(now).unwrap(), 2000);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::{bucket_capacity, slot_duration, Accumulate, Measurement, TimeInstant};
+
+enum Storage<V> {
+    /// Bucket numbers and values of buckets in use, oldest first.
+    Sparse(VecDeque<(u64, V)>),
+    /// All buckets indexed by bucket number modulo capacity.
+    Dense(Vec<V>),
+}
+
+impl<V: fmt::Debug> fmt::Debug for Storage<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Storage::Sparse(buckets) => f.debug_tuple("Sparse").field(buckets).finish(),
+            Storage::Dense(buckets) => f.debug_tuple("Dense").field(buckets).finish(),
+        }
+    }
+}
+
+/// Running average calculation window with buckets stored sparsely until density threshold is passed.
+pub struct SparseRunningAverage<V: Accumulate, I: TimeInstant + Copy> {
+    storage: Storage<V>,
+    density_threshold: f64,
+    capacity: usize,
+    origin: Option<I>,
+    head: u64,
+    duration: Duration,
+    slot_nanos: u128,
+}
+
+impl<V: Accumulate + fmt::Debug, I: TimeInstant + Copy> fmt::Debug for SparseRunningAverage<V, I> where I: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SparseRunningAverage")
+            .field("storage", &self.storage)
+            .field("density_threshold", &self.density_threshold)
+            .field("capacity", &self.capacity)
+            .field("origin", &self.origin)
+            .field("head", &self.head)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> SparseRunningAverage<V, I> {
+    /// Crate new instance that will average over window of width of given duration with specific number of buckets switching to dense storage when more than quarter of buckets is in use.
+    pub fn new(duration: Duration, capacity: usize) -> SparseRunningAverage<V, I> {
+        SparseRunningAverage::with_density_threshold(duration, capacity, 0.25)
+    }
+
+    /// Crate new instance that will average over window of width of given duration with specific number of buckets switching to dense storage when more than given fraction of buckets is in use.
+    /// Panics if capacity is 0; capacity greater than number of nanoseconds in the window is clamped to it.
+    pub fn with_density_threshold(duration: Duration, capacity: usize, density_threshold: f64) -> SparseRunningAverage<V, I> {
+        assert!(capacity > 0, "SparseRunningAverage capacity cannot be 0");
+        let capacity = bucket_capacity(duration, capacity);
+        let slot_nanos = slot_duration(duration, capacity).as_nanos();
+
+        SparseRunningAverage {
+            storage: Storage::Sparse(VecDeque::new()),
+            density_threshold,
+            capacity,
+            origin: None,
+            head: 0,
+            duration,
+            slot_nanos,
+        }
+    }
+
+    fn shift(&mut self, now: I) {
+        let origin = *self.origin.get_or_insert(now);
+        let head = (now.duration_since(origin).as_nanos() / self.slot_nanos).min(u64::MAX as u128) as u64;
+        if head <= self.head {
+            return
+        }
+
+        let slots = head - self.head;
+        let capacity = self.capacity as u64;
+        // Oldest bucket number still within the window
+        let oldest = (head + 1).saturating_sub(capacity);
+
+        match &mut self.storage {
+            Storage::Sparse(buckets) => {
+                while buckets.front().map(|(slot, _)| *slot < oldest).unwrap_or(false) {
+                    buckets.pop_front();
+                }
+            }
+            Storage::Dense(_) if slots >= capacity => {
+                self.storage = Storage::Sparse(VecDeque::new());
+            }
+            Storage::Dense(buckets) => {
+                for slot in self.head + 1..=head {
+                    buckets[(slot % capacity) as usize] = V::zero();
+                }
+            }
+        }
+        self.head = head;
+    }
+
+    fn head_bucket(&mut self) -> &mut V {
+        let head = self.head;
+        let capacity = self.capacity;
+
+        if let Storage::Sparse(buckets) = &mut self.storage {
+            if buckets.back().map(|(slot, _)| *slot != head).unwrap_or(true) {
+                if (buckets.len() + 1) as f64 > capacity as f64 * self.density_threshold {
+                    let mut dense = (0..capacity).map(|_| V::zero()).collect::<Vec<_>>();
+                    for (slot, val) in buckets.drain(..) {
+                        dense[(slot % capacity as u64) as usize] = val;
+                    }
+                    self.storage = Storage::Dense(dense);
+                } else {
+                    buckets.push_back((head, V::zero()));
+                }
+            }
+        }
+
+        match &mut self.storage {
+            Storage::Sparse(buckets) => &mut buckets.back_mut().expect("SparseRunningAverage head bucket").1,
+            Storage::Dense(buckets) => &mut buckets[(head % capacity as u64) as usize],
+        }
+    }
+
+    /// Insert value to be average over at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn insert(&mut self, now: I, val: V) {
+        self.shift(now);
+        self.head_bucket().add(val);
+    }
+
+    /// Insert value of other type to be accumulated into bucket at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn accumulate<A>(&mut self, now: I, val: A) where V: Accumulate<A> {
+        self.shift(now);
+        self.head_bucket().add(val);
+    }
+
+    /// Calculate running average using time window ending at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards.
+    pub fn measurement(&mut self, now: I) -> Measurement<V> {
+        self.shift(now);
+
+        let value = match &self.storage {
+            Storage::Sparse(buckets) => buckets.iter().fold(V::zero(), |sum, (_, val)| sum.merge(val)),
+            Storage::Dense(buckets) => buckets.iter().fold(V::zero(), |sum, val| sum.merge(val)),
+        };
+
+        Measurement {
+            value,
+            duration: self.duration,
+        }
+    }
+
+    /// Returns true if buckets are currently stored in dense array.
+    pub fn is_dense(&self) -> bool {
+        matches!(self.storage, Storage::Dense(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn switch_between_sparse_and_dense() {
+        use super::*;
+
+        let mut tw = SparseRunningAverage::<u32, f64>::with_density_threshold(Duration::from_secs(10), 10, 0.3);
+        assert_eq!(tw.measurement(0.0).unwrap(), 0);
+
+        tw.insert(0.0, 1);
+        tw.insert(0.5, 1);
+        tw.insert(5.0, 1);
+        tw.insert(9.0, 1);
+        assert!(!tw.is_dense());
+        assert_eq!(tw.measurement(9.0).unwrap(), 4);
+
+        // Bucket 0 expired
+        tw.insert(10.0, 1);
+        assert!(!tw.is_dense());
+        assert_eq!(tw.measurement(10.0).unwrap(), 3);
+
+        tw.insert(11.0, 1);
+        assert!(tw.is_dense());
+        assert_eq!(tw.measurement(11.0).unwrap(), 4);
+        assert_eq!(tw.measurement(15.0).unwrap(), 3);
+
+        // All buckets expired
+        assert_eq!(tw.measurement(40.0).unwrap(), 0);
+        assert!(!tw.is_dense());
+
+        tw.accumulate(40.5, 5u32);
+        assert_eq!(tw.measurement(40.5).to_rate(), 0.5);
+    }
+
+    #[test]
+    fn same_as_running_average() {
+        use super::*;
+        use super::super::RunningAverage;
+
+        let mut sparse = SparseRunningAverage::<u64, f64>::new(Duration::from_secs(16), 16);
+        let mut flat = RunningAverage::<u64, f64>::with_capacity(Duration::from_secs(16), 16);
+
+        let mut now = 0.0;
+        for step in 0..500u64 {
+            now += (step % 11) as f64 * 0.75;
+            sparse.insert(now, step);
+            flat.insert(now, step);
+            assert_eq!(sparse.measurement(now).unwrap(), flat.measurement(now).unwrap());
+        }
+    }
+}
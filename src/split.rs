@@ -0,0 +1,103 @@
+//! Running average split into insert-only recorder and measurement-only reader handles.
+//!
+//! `RunningAverage::split()` moves the running average behind a mutex and returns `Recorder` that can only insert
+//! and `Reader` that can only take measurements, so one task can record while another reports.
+//! ```
+//! use std::thread;
+//! use std::time::Instant;
+//! use running_average::RunningAverage;
+//!
+//! let (mut recorder, reader) = RunningAverage::<u32, Instant>::default().split();
+//!
+//! thread::spawn(move || {
+//!     // Got 2KB of data
+//!     recorder.insert(Instant::now(), 2000);
+//! }).join().unwrap();
+//!
+//! assert_eq!(reader.measurement(Instant::now()).unwrap(), 2000);
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::{Accumulate, Measurement, RunningAverage, TimeInstant};
+
+type Core<V, I> = Arc<Mutex<RunningAverage<V, I>>>;
+
+fn lock<V: Accumulate, I: TimeInstant + Copy>(core: &Core<V, I>) -> MutexGuard<'_, RunningAverage<V, I>> {
+    core.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> RunningAverage<V, I> {
+    /// Split running average into insert-only `Recorder` and measurement-only `Reader` sharing it.
+    pub fn split(self) -> (Recorder<V, I>, Reader<V, I>) {
+        let core = Arc::new(Mutex::new(self));
+        (Recorder { core: core.clone() }, Reader { core })
+    }
+}
+
+/// Insert-only handle of split running average.
+pub struct Recorder<V: Accumulate, I: TimeInstant + Copy> {
+    core: Core<V, I>,
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> fmt::Debug for Recorder<V, I> where RunningAverage<V, I>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder").field("core", &self.core).finish()
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> Recorder<V, I> {
+    /// Insert value to be average over at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn insert(&mut self, now: I, val: V) {
+        lock(&self.core).insert(now, val)
+    }
+}
+
+/// Cloneable measurement-only handle of split running average.
+pub struct Reader<V: Accumulate, I: TimeInstant + Copy> {
+    core: Core<V, I>,
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> fmt::Debug for Reader<V, I> where RunningAverage<V, I>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reader").field("core", &self.core).finish()
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> Clone for Reader<V, I> {
+    fn clone(&self) -> Reader<V, I> {
+        Reader {
+            core: self.core.clone(),
+        }
+    }
+}
+
+impl<V: Accumulate, I: TimeInstant + Copy> Reader<V, I> {
+    /// Calculate running average using time window ending at given time instant.
+    /// Panics if now is less than previous now - time cannot go backwards
+    pub fn measurement(&self, now: I) -> Measurement<V> {
+        lock(&self.core).measurement(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn record_and_read_from_threads() {
+        use super::*;
+        use std::thread;
+
+        let (mut recorder, reader) = RunningAverage::<u32, f64>::with_capacity(std::time::Duration::from_secs(4), 4).split();
+
+        thread::spawn(move || {
+            recorder.insert(0.0, 10);
+            recorder.insert(1.0, 30);
+        }).join().unwrap();
+
+        let other = reader.clone();
+        assert_eq!(reader.measurement(1.0).unwrap(), 40);
+        assert_eq!(other.measurement(4.0).unwrap(), 30);
+    }
+}
@@ -0,0 +1,206 @@
+//! Recording of measurements into SQLite database table (requires `sqlite` feature).
+//!
+//! Each recorded measurement is appended as a row with `timestamp` (seconds since UNIX epoch), `window` (width of the time window in seconds),
+//! `value` (sum of all samples within time window) and `rate` columns.
+//! Non-finite numbers (e.g. rate of zero width window) are stored as `NULL`.
+//! Labelled sink, or sink recording labelled measurement of running average with name or labels, also fills `name` and `labels` (JSON object of key/value labels) columns.
+//! ```
+//! # extern crate running_average;
+//! # extern crate rusqlite;
+//! use std::time::SystemTime;
+//! use running_average::RealTimeRunningAverage;
+//! use running_average::sink::MeasurementSink;
+//! use running_average::sqlite::SqliteSink;
+//!
+//! let mut sink = SqliteSink::new(rusqlite::Connection::open_in_memory().unwrap(), "throughput").unwrap();
+//!
+//! let mut tw = RealTimeRunningAverage::default();
+//! tw.insert(2000);
+//!
+//! sink.record(SystemTime::now(), &tw.measurement()).unwrap();
+//! ```
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use rusqlite::{Connection, Error};
+
+use super::Measurement;
+use super::dts;
+use super::labels::{LabeledMeasurement, Labels};
+use super::sink::{json_labels, MeasurementSink, unix_seconds};
+
+/// MeasurementSink that appends measurements to SQLite database table.
+#[derive(Debug)]
+pub struct SqliteSink {
+    connection: Connection,
+    insert: String,
+    labels: Option<(Option<String>, String)>,
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl SqliteSink {
+    /// Create new SqliteSink appending to table of given name using given connection.
+    /// The table is created if it does not exist.
+    pub fn new(connection: Connection, table: &str) -> Result<SqliteSink, Error> {
+        let table = quote_identifier(table);
+
+        connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (timestamp REAL NOT NULL, window REAL NOT NULL, value REAL, rate REAL, name TEXT, labels TEXT)",
+            table))?;
+
+        Ok(SqliteSink {
+            connection,
+            insert: format!("INSERT INTO {} (timestamp, window, value, rate, name, labels) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table),
+            labels: None,
+        })
+    }
+
+    /// Write given name (if set) and labels with each measurement.
+    pub fn labeled(mut self, labels: &Labels) -> SqliteSink {
+        self.labels = Some((labels.name().map(str::to_owned), json_labels(labels)));
+        self
+    }
+
+    /// Open SQLite database file at given path and create new SqliteSink appending to table of given name.
+    pub fn open<P: AsRef<Path>>(path: P, table: &str) -> Result<SqliteSink, Error> {
+        SqliteSink::new(Connection::open(path)?, table)
+    }
+
+    /// Return reference to the underlying connection.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Return the underlying connection consuming self.
+    pub fn into_inner(self) -> Connection {
+        self.connection
+    }
+}
+
+impl<V> MeasurementSink<V> for SqliteSink where V: Clone + Into<f64> {
+    type Error = Error;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), Error> {
+        let (name, labels) = match &self.labels {
+            Some((name, labels)) => (name.as_deref(), Some(labels.as_str())),
+            None => (None, None),
+        };
+
+        insert(&self.connection, &self.insert, ts, measurement, name, labels)
+    }
+
+    /// Stores name and labels of the measurement unless it has none; then labels given to `labeled()` are stored.
+    fn record_labeled(&mut self, ts: SystemTime, measurement: &LabeledMeasurement<V>) -> Result<(), Error> {
+        if measurement.labels().is_empty() {
+            self.record(ts, measurement)
+        } else {
+            insert(&self.connection, &self.insert, ts, measurement, measurement.labels().name(), Some(&json_labels(measurement.labels())))
+        }
+    }
+}
+
+fn insert<V: Clone + Into<f64>>(connection: &Connection, insert: &str, ts: SystemTime, measurement: &Measurement<V>, name: Option<&str>, labels: Option<&str>) -> Result<(), Error> {
+    let value: f64 = measurement.value().clone().into();
+    let finite = |number: f64| Some(number).filter(|number| number.is_finite());
+
+    connection.prepare_cached(insert)?
+        .execute((unix_seconds(ts), dts(measurement.duration()), finite(value), finite(measurement.rate_f64()), name, labels))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn record_measurements() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), "rate \"log\"").unwrap();
+
+        sink.record(UNIX_EPOCH + Duration::from_secs(10), &Measurement { value: 40, duration: Duration::from_secs(4) }).unwrap();
+        sink.record(UNIX_EPOCH + Duration::from_secs(11), &Measurement { value: 20, duration: Duration::from_secs(4) }).unwrap();
+
+        let mut statement = sink.connection().prepare("SELECT timestamp, window, value, rate FROM \"rate \"\"log\"\"\" ORDER BY timestamp").unwrap();
+        let rows: Vec<(f64, f64, f64, f64)> = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(rows, vec![(10.0, 4.0, 40.0, 10.0), (11.0, 4.0, 20.0, 5.0)]);
+    }
+
+    #[test]
+    fn record_non_finite_as_null() {
+        use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), "rate").unwrap();
+        sink.record(UNIX_EPOCH + Duration::from_secs(1), &Measurement { value: 0, duration: Duration::from_secs(0) }).unwrap();
+        sink.record(UNIX_EPOCH + Duration::from_secs(2), &Measurement { value: f64::INFINITY, duration: Duration::from_secs(1) }).unwrap();
+
+        let mut statement = sink.connection().prepare("SELECT window, value, rate FROM rate ORDER BY timestamp").unwrap();
+        let rows: Vec<(f64, Option<f64>, Option<f64>)> = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(rows, vec![(0.0, Some(0.0), None), (1.0, None, None)]);
+    }
+
+    #[test]
+    fn reuse_existing_table() {
+        use super::*;
+        use std::time::Duration;
+
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), "rate").unwrap();
+        sink.record(SystemTime::now(), &Measurement { value: 1.5f64, duration: Duration::from_secs(1) }).unwrap();
+
+        let mut sink = SqliteSink::new(sink.into_inner(), "rate").unwrap();
+        sink.record(SystemTime::now(), &Measurement { value: 2.5f64, duration: Duration::from_secs(1) }).unwrap();
+
+        let count: i64 = sink.connection().query_row("SELECT count(*) FROM rate", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn record_labels() {
+        use super::*;
+        use std::time::Duration;
+
+        let mut labels = Labels::new();
+        labels.set_name("egress");
+        labels.insert("iface", "eth\"0\"");
+
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), "rate").unwrap();
+        sink.record(SystemTime::now(), &Measurement { value: 1.0f64, duration: Duration::from_secs(1) }).unwrap();
+        let mut sink = sink.labeled(&labels);
+        sink.record(SystemTime::now(), &Measurement { value: 2.0f64, duration: Duration::from_secs(1) }).unwrap();
+
+        let mut statement = sink.connection().prepare("SELECT name, labels FROM rate ORDER BY value").unwrap();
+        let rows: Vec<(Option<String>, Option<String>)> = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(rows, vec![(None, None), (Some("egress".to_owned()), Some("{\"iface\":\"eth\\\"0\\\"\"}".to_owned()))]);
+    }
+
+    #[test]
+    fn record_labeled_measurement() {
+        use super::*;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let mut labels = Labels::new();
+        labels.set_name("egress");
+        labels.insert("iface", "eth0");
+
+        let mut sink = SqliteSink::new(Connection::open_in_memory().unwrap(), "rate").unwrap();
+        sink.record_labeled(SystemTime::now(), &LabeledMeasurement::new(Measurement { value: 1.0f64, duration: Duration::from_secs(1) }, Arc::new(Labels::new()))).unwrap();
+        sink.record_labeled(SystemTime::now(), &LabeledMeasurement::new(Measurement { value: 2.0f64, duration: Duration::from_secs(1) }, Arc::new(labels))).unwrap();
+
+        let mut statement = sink.connection().prepare("SELECT name, labels FROM rate ORDER BY value").unwrap();
+        let rows: Vec<(Option<String>, Option<String>)> = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(rows, vec![(None, None), (Some("egress".to_owned()), Some("{\"iface\":\"eth0\"}".to_owned()))]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Running average staging inserts to apply them to the time window in batches.
+//!
+//! `StagedRunningAverage::insert()` only appends timestamped value to a small staging buffer;
+//! the buffer is applied to the time window when it fills up or when measurement is taken.
+//! Staged values falling into the same bucket are accumulated together so that the window is shifted once per bucket rather than once per value.
+//! This reduces per-insert cost for workloads with many inserts per measurement (see `staged_insert` benchmark).
+//! ```
+//! use running_average::staged::StagedRunningAverage;
+//!
+//! let mut tw = StagedRunningAverage::default();
+//!
+//! for _ in 0..1000 {
+//!     tw.insert(2);
+//! }
+//!
+//! assert_eq!(tw.measurement().unwrap(), 2000);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{Accumulate, Insert, Measurement, RealTimeRunningAverage, RealTimeSource, TimeSource};
+
+/// Running average that stages inserted values with their timestamps and applies them to the time window in batches.
+pub struct StagedRunningAverage<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    inner: RealTimeRunningAverage<V, TS>,
+    staged: Vec<(TS::Instant, V)>,
+    staging: usize,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for StagedRunningAverage<V, TS> where RealTimeRunningAverage<V, TS>: fmt::Debug, V: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StagedRunningAverage").field("inner", &self.inner).field("staged", &self.staged.len()).field("staging", &self.staging).finish()
+    }
+}
+
+impl<V: Accumulate> Default for StagedRunningAverage<V, RealTimeSource> {
+    /// Crate new StagedRunningAverage instance with window of 8 seconds width, 16 buckets and room for 32 staged values.
+    fn default() -> StagedRunningAverage<V, RealTimeSource> {
+        StagedRunningAverage::new(Duration::from_secs(8))
+    }
+}
+
+impl<V: Accumulate> StagedRunningAverage<V, RealTimeSource> {
+    /// Crate new instance with window of given width duration, room for 32 staged values and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> StagedRunningAverage<V, RealTimeSource> {
+        StagedRunningAverage::with_staging(RealTimeRunningAverage::new(duration), 32)
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> StagedRunningAverage<V, TS> {
+    /// Crate new instance staging up to given number of values before applying them to given running average.
+    pub fn with_staging(running_average: RealTimeRunningAverage<V, TS>, staging: usize) -> StagedRunningAverage<V, TS> {
+        assert!(staging > 0, "StagedRunningAverage staging capacity cannot be 0");
+        StagedRunningAverage {
+            inner: running_average,
+            staged: Vec::with_capacity(staging),
+            staging,
+        }
+    }
+
+    /// Stage value to be average over now; applies staged values if staging buffer is full.
+    /// Panics if time source time goes backwards.
+    pub fn insert(&mut self, val: V) {
+        let now = self.inner.time_source.now();
+        self.staged.push((now, val));
+
+        if self.staged.len() >= self.staging {
+            self.flush();
+        }
+    }
+
+    /// Apply staged values to the time window shifting it once per bucket the values fall into.
+    /// Panics if time source time goes backwards.
+    pub fn flush(&mut self) {
+        self.inner.inner.insert_batch(self.staged.drain(..));
+    }
+
+    /// Returns number of values staged but not yet applied to the time window.
+    pub fn staged(&self) -> usize {
+        self.staged.len()
+    }
+
+    /// Apply staged values and calculate running average using time window ending now.
+    /// Panics if time source time goes backwards.
+    pub fn measurement(&mut self) -> Measurement<V> {
+        self.flush();
+        self.inner.measurement()
+    }
+
+    /// Apply staged values and return mutable reference to the underlying running average.
+    pub fn get_mut(&mut self) -> &mut RealTimeRunningAverage<V, TS> {
+        self.flush();
+        &mut self.inner
+    }
+
+    /// Apply staged values and return the underlying running average consuming self.
+    pub fn into_inner(mut self) -> RealTimeRunningAverage<V, TS> {
+        self.flush();
+        self.inner
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> Insert for StagedRunningAverage<V, TS> {
+    type Value = V;
+
+    fn insert(&mut self, val: V) {
+        StagedRunningAverage::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn staged_inserts_keep_their_time() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let mut tw = StagedRunningAverage::with_staging(RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()), 3);
+
+        tw.insert(10);
+        tw.inner.time_source().time_shift(1.0);
+        tw.insert(20);
+        assert_eq!(tw.staged(), 2);
+
+        tw.inner.time_source().time_shift(1.0);
+        tw.insert(30);
+        assert_eq!(tw.staged(), 0);
+
+        tw.inner.time_source().time_shift(1.0);
+        tw.insert(40);
+        assert_eq!(tw.staged(), 1);
+
+        assert_eq!(tw.get_mut().buckets().cloned().collect::<Vec<_>>(), vec![40, 30, 20, 10]);
+        assert_eq!(tw.staged(), 0);
+
+        tw.inner.time_source().time_shift(1.0);
+        tw.insert(50);
+        assert_eq!(tw.measurement().unwrap(), 140);
+    }
+
+    #[test]
+    fn flush_matches_plain_inserts() {
+        use super::*;
+        use super::super::{ManualTimeSource, RunningAverage};
+
+        let mut tw = StagedRunningAverage::with_staging(RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()), 16);
+        let mut plain = RunningAverage::<u32, f64>::with_capacity(Duration::from_secs(4), 4);
+
+        for i in 0..100u32 {
+            // Several values per bucket with gaps of more than one bucket every now and then
+            let step = if i % 10 == 9 { 1.75 } else { 0.3 };
+            tw.inner.time_source().time_shift(step);
+            plain.insert(tw.inner.time_source().now_seconds(), i);
+            tw.insert(i);
+
+            if i % 7 == 0 {
+                let now = tw.inner.time_source().now_seconds();
+                assert_eq!(tw.get_mut().buckets().cloned().collect::<Vec<_>>(), plain.buckets(now).cloned().collect::<Vec<_>>());
+            }
+        }
+
+        let now = tw.inner.time_source().now_seconds();
+        assert_eq!(tw.measurement().unwrap(), plain.measurement(now).unwrap());
+    }
+}
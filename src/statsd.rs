@@ -0,0 +1,161 @@
+//! Emitting measurements as statsd/DogStatsD metrics over UDP (requires `statsd` feature).
+//!
+//! `StatsdSink` sends measured rate as gauge and estimated number of values inserted since previous report as count.
+//! Non-finite numbers (e.g. rate of zero width window) are not sent.
+//! Use with `Reporter` to emit metrics periodically.
+//! ```
+//! use std::time::Duration;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::statsd::StatsdSink;
+//! use running_average::reporter::Reporter;
+//!
+//! let shared = SharedRunningAverage::<u32>::default();
+//!
+//! // Send "myapp.download.rate" gauge and "myapp.download.count" count every 10 seconds
+//! let sink = StatsdSink::connect("127.0.0.1:8125", "download").unwrap().prefix("myapp").tag("mirror", "eu");
+//! let reporter = Reporter::spawn(shared.clone(), Duration::from_secs(10), sink);
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//! # reporter.stop().unwrap();
+//! ```
+
+use std::fmt::Write;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::SystemTime;
+
+use super::{Measurement, dts};
+use super::labels::Labels;
+use super::sink::MeasurementSink;
+
+/// MeasurementSink sending `<prefix>.<name>.rate` gauge and `<prefix>.<name>.count` count lines in single UDP datagram.
+/// Tags are appended in DogStatsD format (`|#name:value,...`).
+///
+/// Note: Count is estimated as measured rate multiplied by time elapsed since previous record (or width of the time window for the first record);
+/// it is exact when reporting interval is equal to width of the time window.
+/// Metric lines with non-finite numbers are skipped and no datagram is sent if there is no line left.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    name: String,
+    tags: String,
+    last: Option<SystemTime>,
+}
+
+impl StatsdSink {
+    /// Create new StatsdSink sending metrics of given name with given connected socket.
+    pub fn new(socket: UdpSocket, name: &str) -> StatsdSink {
+        StatsdSink {
+            socket,
+            name: name.to_owned(),
+            tags: String::new(),
+            last: None,
+        }
+    }
+
+    /// Create new StatsdSink sending metrics of given name to statsd agent listening on given address.
+    pub fn connect<A: ToSocketAddrs>(addr: A, name: &str) -> io::Result<StatsdSink> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to"))?;
+        let socket = UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+        socket.connect(addr)?;
+        Ok(StatsdSink::new(socket, name))
+    }
+
+    /// Prefix metric names with given prefix followed by `.`.
+    pub fn prefix(mut self, prefix: &str) -> StatsdSink {
+        self.name = format!("{}.{}", prefix, self.name);
+        self
+    }
+
+    /// Add tag with given name and value to sent metrics.
+    pub fn tag(mut self, name: &str, value: &str) -> StatsdSink {
+        self.tags.push_str(if self.tags.is_empty() { "|#" } else { "," });
+        self.tags.push_str(name);
+        if !value.is_empty() {
+            self.tags.push(':');
+            self.tags.push_str(value);
+        }
+        self
+    }
+
+    /// Add each of given labels as tag to sent metrics.
+    pub fn labeled(self, labels: &Labels) -> StatsdSink {
+        labels.pairs().into_iter().fold(self, |sink, (name, value)| sink.tag(name, value))
+    }
+
+    /// Return reference to the underlying socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    fn lines(&mut self, ts: SystemTime, rate: f64, window: f64) -> String {
+        let elapsed = self.last.and_then(|last| ts.duration_since(last).ok()).map(dts).unwrap_or(window);
+        self.last = Some(ts);
+
+        let mut lines = String::new();
+        for (metric, number, kind) in [("rate", rate, "g"), ("count", rate * elapsed, "c")] {
+            if number.is_finite() {
+                if !lines.is_empty() {
+                    lines.push('\n');
+                }
+                write!(lines, "{}.{}:{}|{}{}", self.name, metric, number, kind, self.tags).unwrap();
+            }
+        }
+        lines
+    }
+}
+
+impl<V> MeasurementSink<V> for StatsdSink where V: Clone + Into<f64> {
+    type Error = io::Error;
+
+    fn record(&mut self, ts: SystemTime, measurement: &Measurement<V>) -> Result<(), io::Error> {
+        let lines = self.lines(ts, measurement.rate_f64(), dts(measurement.duration()));
+        if lines.is_empty() {
+            return Ok(())
+        }
+        self.socket.send(lines.as_bytes()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn send_rate_and_count() {
+        use super::*;
+        use std::time::Duration;
+
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = StatsdSink::connect(agent.local_addr().unwrap(), "requests").unwrap().prefix("app").tag("service", "api").tag("canary", "");
+
+        let ts = SystemTime::now();
+        let measurement = Measurement { value: 40, duration: Duration::from_secs(4) };
+        sink.record(ts, &measurement).unwrap();
+        sink.record(ts + Duration::from_secs(2), &measurement).unwrap();
+
+        let mut buf = [0; 512];
+        let len = agent.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"app.requests.rate:10|g|#service:api,canary\napp.requests.count:40|c|#service:api,canary"[..]);
+
+        let len = agent.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"app.requests.rate:10|g|#service:api,canary\napp.requests.count:20|c|#service:api,canary"[..]);
+    }
+
+    #[test]
+    fn skip_non_finite() {
+        use super::*;
+        use std::time::Duration;
+
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = StatsdSink::connect(agent.local_addr().unwrap(), "requests").unwrap();
+
+        let ts = SystemTime::now();
+        sink.record(ts, &Measurement { value: 40, duration: Duration::from_secs(0) }).unwrap();
+        sink.record(ts, &Measurement { value: f64::INFINITY, duration: Duration::from_secs(4) }).unwrap();
+        sink.record(ts + Duration::from_secs(2), &Measurement { value: 40, duration: Duration::from_secs(4) }).unwrap();
+
+        let mut buf = [0; 512];
+        let len = agent.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"requests.rate:10|g\nrequests.count:20|c"[..]);
+    }
+}
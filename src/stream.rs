@@ -0,0 +1,109 @@
+//! Stream of periodically taken measurements of shared running average (requires `futures` feature).
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use std::future::poll_fn;
+//! use std::pin::Pin;
+//! use std::time::Duration;
+//! use futures_core::Stream;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::stream::MeasurementStream;
+//!
+//! let shared = SharedRunningAverage::default();
+//! let mut measurements = MeasurementStream::new(shared.clone(), Duration::from_millis(10));
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//!
+//! let measurement = poll_fn(|cx| Pin::new(&mut measurements).poll_next(cx)).await.unwrap();
+//! assert_eq!(*measurement.value(), 2000);
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_timer::Delay;
+
+use super::{Accumulate, Measurement, RealTimeSource, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Stream yielding measurement of shared running average every interval.
+/// The stream never ends.
+pub struct MeasurementStream<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    shared: SharedRunningAverage<V, TS>,
+    interval: Duration,
+    delay: Delay,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for MeasurementStream<V, TS> where SharedRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MeasurementStream").field("shared", &self.shared).field("interval", &self.interval).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> MeasurementStream<V, TS> {
+    /// Create new stream that will yield measurement of given shared running average after each interval.
+    pub fn new(shared: SharedRunningAverage<V, TS>, interval: Duration) -> MeasurementStream<V, TS> {
+        MeasurementStream {
+            shared,
+            interval,
+            delay: Delay::new(interval),
+        }
+    }
+
+    /// Return reference to shared running average measured.
+    pub fn shared(&self) -> &SharedRunningAverage<V, TS> {
+        &self.shared
+    }
+
+    /// Change interval of yielded measurements; next measurement will be yielded after given interval from now.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+        self.delay.reset(interval);
+    }
+}
+
+impl<V, TS: TimeSource> Stream for MeasurementStream<V, TS> where V: Accumulate {
+    type Item = Measurement<V>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Measurement<V>>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => {
+                this.delay.reset(this.interval);
+                Poll::Ready(Some(this.shared.measurement()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn yields_measurements() {
+        use super::*;
+        use std::future::poll_fn;
+        use std::time::Instant;
+        use super::super::ManualTimeSource;
+
+        let shared = SharedRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let mut measurements = MeasurementStream::new(shared.clone(), Duration::from_millis(10));
+        let start = Instant::now();
+
+        for value in [10, 20, 30].iter() {
+            shared.insert(10);
+            let measurement = poll_fn(|cx| Pin::new(&mut measurements).poll_next(cx)).await.unwrap();
+            assert_eq!(measurement.value(), value);
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+}
@@ -0,0 +1,89 @@
+//! Windowed quantiles with one t-digest per bucket (requires `tdigest` feature).
+//!
+//! `tdigest::TDigest` implements `Accumulate` so it can be used as running average value: values are accumulated into digest of the current bucket
+//! and digests of all buckets are merged on measurement giving accurate tail quantiles of values inserted within the time window using little memory.
+//! ```
+//! use std::time::Duration;
+//! use running_average::RealTimeRunningAverage;
+//! use tdigest::TDigest;
+//!
+//! let mut latency = RealTimeRunningAverage::<TDigest>::new(Duration::from_secs(60));
+//!
+//! for ms in 1..=100 {
+//!     latency.accumulate(ms as f64);
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! let digest = latency.measurement().unwrap();
+//! assert_eq!(digest.count(), 100.0);
+//! assert!((digest.estimate_quantile(0.99).unwrap() - 99.0).abs() <= 1.0);
+//! ```
+
+use ::tdigest::TDigest;
+
+use super::Accumulate;
+
+/// Empty bucket is digest of default size of 100 centroids; use `RunningAverage::with_capacity_and_init()` for other sizes.
+impl Accumulate<f64> for TDigest {
+    fn zero() -> TDigest {
+        TDigest::new_with_size(100)
+    }
+
+    fn add(&mut self, val: f64) {
+        self.push(val)
+    }
+
+    fn merge(&self, other: &TDigest) -> TDigest {
+        let (mut left, mut right) = (self.clone(), other.clone());
+        left.flush();
+        right.flush();
+        TDigest::merge_digests(vec![left, right])
+    }
+}
+
+impl Accumulate for TDigest {
+    fn zero() -> TDigest {
+        TDigest::new_with_size(100)
+    }
+
+    fn add(&mut self, mut digest: TDigest) {
+        self.flush();
+        digest.flush();
+        *self = TDigest::merge_digests(vec![std::mem::take(self), digest])
+    }
+
+    fn merge(&self, other: &TDigest) -> TDigest {
+        Accumulate::<f64>::merge(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn windowed_quantiles() {
+        use super::*;
+        use std::time::Duration;
+        use super::super::{ManualTimeSource, RealTimeRunningAverage};
+
+        let mut tw = RealTimeRunningAverage::<TDigest, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        assert!(tw.measurement().unwrap().estimate_quantile(0.5).is_none());
+
+        for val in 0..1000 {
+            tw.accumulate(val as f64);
+        }
+        tw.time_source().time_shift(2.0);
+        for val in 1000..2000 {
+            tw.accumulate(val as f64);
+        }
+
+        let digest = tw.measurement().unwrap();
+        assert_eq!(digest.count(), 2000.0);
+        assert!((digest.estimate_quantile(0.5).unwrap() - 1000.0).abs() < 20.0);
+        assert!((digest.estimate_quantile(0.999).unwrap() - 1998.0).abs() < 5.0);
+
+        tw.time_source().time_shift(2.0);
+        let digest = tw.measurement().unwrap();
+        assert_eq!(digest.count(), 1000.0);
+        assert_eq!(digest.min(), Some(1000.0));
+    }
+}
@@ -0,0 +1,92 @@
+//! Proptest strategies generating timestamped workloads and helpers replaying them with window invariants checked (requires `testing` feature).
+//!
+//! Workloads are sequences of values with non-decreasing offsets from the start of the workload so integrations can be fuzzed against the window semantics.
+//! ```
+//! use proptest::prelude::*;
+//! use running_average::testing::{exact_sum, replay, window, workload};
+//!
+//! proptest!(|((duration, capacity) in window(), workload in workload(0..100))| {
+//!     let (mut running_average, start) = replay(duration, capacity, &workload);
+//!     let end = workload.last().map(|(offset, _)| *offset).unwrap_or_default();
+//!
+//!     // Nothing inserted within the time window is lost
+//!     let measured = running_average.measurement(start + end).unwrap();
+//!     prop_assert!(measured >= exact_sum(&workload, end.saturating_sub(duration / 2), end) || capacity < 2);
+//! });
+//! ```
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use super::RunningAverage;
+
+/// Strategy generating window width (1 millisecond to 60 seconds) and number of buckets (1 to 64).
+pub fn window() -> impl Strategy<Value = (Duration, usize)> {
+    (1..60_000u64, 1..=64usize).prop_map(|(millis, capacity)| (Duration::from_millis(millis), capacity))
+}
+
+/// Strategy generating workload of number of values in given range with offsets from its start and values.
+/// Offsets are non-decreasing with steps mostly within a second, often repeated and sometimes long gaps of up to a minute.
+pub fn workload(len: Range<usize>) -> impl Strategy<Value = Vec<(Duration, u32)>> {
+    let step = prop_oneof![
+        4 => 0..1_000_000_000u64,
+        2 => Just(0u64),
+        1 => 0..60_000_000_000u64,
+    ];
+
+    vec((step, 0..1000u32), len).prop_map(|steps| {
+        let mut offset = Duration::from_secs(0);
+        steps.into_iter().map(|(step, value)| {
+            offset += Duration::from_nanos(step);
+            (offset, value)
+        }).collect()
+    })
+}
+
+/// Insert values of given workload into new running average of given window width and number of buckets checking its invariants before and after each insert.
+/// Returns the running average and time instant the workload offsets are relative to.
+pub fn replay(duration: Duration, capacity: usize, workload: &[(Duration, u32)]) -> (RunningAverage<u64, Instant>, Instant) {
+    let start = Instant::now();
+    let mut running_average = RunningAverage::with_capacity(duration, capacity);
+
+    for &(offset, value) in workload {
+        running_average.check_invariants(start + offset);
+        running_average.insert(start + offset, value as u64);
+        running_average.check_invariants(start + offset);
+    }
+    (running_average, start)
+}
+
+/// Returns exact sum of workload values with offsets from `since` up to `until` inclusive.
+pub fn exact_sum(workload: &[(Duration, u32)], since: Duration, until: Duration) -> u64 {
+    workload.iter()
+        .filter(|(offset, _)| since <= *offset && *offset <= until)
+        .map(|(_, value)| *value as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn measurement_within_bucket_of_exact_sum((duration, capacity) in super::window(), samples in super::workload(0..200)) {
+            use super::*;
+
+            let (mut running_average, start) = replay(duration, capacity, &samples);
+            let end = samples.last().map(|(offset, _)| *offset).unwrap_or_default();
+            let slot = running_average.bucket_duration();
+            let measured = running_average.measurement(start + end).unwrap();
+
+            // Window covers time span of all but the most recent bucket ending now and the most recent bucket up to now
+            let lower = exact_sum(&samples, end.saturating_sub(slot * (capacity as u32 - 1)), end);
+            let upper = exact_sum(&samples, end.saturating_sub(slot * capacity as u32), end);
+            prop_assert!(lower <= measured && measured <= upper, "{} <= {} <= {}", lower, measured, upper);
+            running_average.check_invariants(start + end);
+        }
+    }
+}
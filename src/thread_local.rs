@@ -0,0 +1,228 @@
+//! Per thread running averages aggregated on demand for instrumenting multi-threaded hot loops.
+//!
+//! `thread_local_rate!` declares static `ThreadLocalRate`; each thread inserts into its own running average
+//! so threads do not contend on a single lock and `measurement()` sums measurements of all threads' running averages.
+//! When thread exits its running average is merged into running average of retired threads so that threads coming and going do not accumulate.
+//! ```
+//! use std::thread;
+//! use std::time::Duration;
+//! use running_average::thread_local_rate;
+//!
+//! thread_local_rate!(static BYTES, Duration::from_secs(8));
+//!
+//! let threads: Vec<_> = (0..4).map(|_| thread::spawn(|| {
+//!     // Got 2KB of data
+//!     BYTES.insert(2000.0);
+//! })).collect();
+//!
+//! for thread in threads {
+//!     thread.join().unwrap();
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! assert_eq!(BYTES.measurement().unwrap(), 8000.0);
+//! ```
+
+use std::cell::OnceCell;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::LocalKey;
+use std::time::{Duration, Instant};
+
+use super::{Insert, Measurement, RealTimeRunningAverage};
+use super::shared::SharedRunningAverage;
+
+/// Declare static `ThreadLocalRate` of given name with running averages of given window width duration for each thread.
+/// ```
+/// use std::time::Duration;
+/// use running_average::thread_local_rate;
+///
+/// thread_local_rate!(pub static REQUESTS, Duration::from_secs(4));
+///
+/// REQUESTS.insert(1.0);
+/// ```
+#[macro_export]
+macro_rules! thread_local_rate {
+    ($(#[$attr:meta])* $vis:vis static $name:ident, $duration:expr) => {
+        $(#[$attr])* $vis static $name: $crate::thread_local::ThreadLocalRate = {
+            ::std::thread_local!(static LOCAL: $crate::thread_local::Local = const { $crate::thread_local::Local::new() });
+            $crate::thread_local::ThreadLocalRate::new(&LOCAL, || $duration)
+        };
+    };
+}
+
+/// Running average of the current thread that is retired when the thread exits; used by `thread_local_rate!`.
+#[doc(hidden)]
+pub struct Local(OnceCell<Registered>);
+
+struct Registered {
+    id: u64,
+    shared: SharedRunningAverage<f64>,
+    rate: &'static ThreadLocalRate,
+}
+
+impl Local {
+    /// Used by `thread_local_rate!`.
+    #[doc(hidden)]
+    pub const fn new() -> Local {
+        Local(OnceCell::new())
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        if let Some(registered) = self.0.get() {
+            registered.rate.retire(registered.id);
+        }
+    }
+}
+
+/// Running averages of `f64` values for each thread that inserted into them; declare with `thread_local_rate!`.
+pub struct ThreadLocalRate {
+    local: &'static LocalKey<Local>,
+    duration: fn() -> Duration,
+    next_id: AtomicU64,
+    windows: Mutex<Vec<(u64, SharedRunningAverage<f64>)>>,
+    retired: Mutex<Option<RealTimeRunningAverage<f64>>>,
+}
+
+impl fmt::Debug for ThreadLocalRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadLocalRate").field("duration", &(self.duration)()).field("windows", &self.windows).field("retired", &self.retired).finish()
+    }
+}
+
+impl ThreadLocalRate {
+    /// Used by `thread_local_rate!`.
+    #[doc(hidden)]
+    pub const fn new(local: &'static LocalKey<Local>, duration: fn() -> Duration) -> ThreadLocalRate {
+        ThreadLocalRate {
+            local,
+            duration,
+            next_id: AtomicU64::new(0),
+            windows: Mutex::new(Vec::new()),
+            retired: Mutex::new(None),
+        }
+    }
+
+    fn windows(&self) -> MutexGuard<'_, Vec<(u64, SharedRunningAverage<f64>)>> {
+        self.windows.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn retired(&self) -> MutexGuard<'_, Option<RealTimeRunningAverage<f64>>> {
+        self.retired.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn register(&'static self) -> Registered {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let shared = SharedRunningAverage::new((self.duration)());
+        self.windows().push((id, shared.clone()));
+        Registered { id, shared, rate: self }
+    }
+
+    /// Merge buckets of running average of exited thread into running average of retired threads.
+    fn retire(&self, id: u64) {
+        let mut windows = self.windows();
+        if let Some(index) = windows.iter().position(|(window_id, _)| *window_id == id) {
+            let (_, shared) = windows.swap_remove(index);
+            let mut retired = self.retired();
+            let retired = retired.get_or_insert_with(|| RealTimeRunningAverage::new((self.duration)()));
+            retired.inner.absorb(Instant::now(), &mut shared.lock().inner);
+        }
+    }
+
+    /// Returns running average of the current thread creating it on first use.
+    /// Note: values inserted into returned running average after the thread exited are not accounted for.
+    pub fn local(&'static self) -> SharedRunningAverage<f64> {
+        self.local.with(|local| local.0.get_or_init(|| self.register()).shared.clone())
+    }
+
+    /// Insert value to be average over now into running average of the current thread.
+    pub fn insert(&'static self, val: f64) {
+        self.local.with(|local| local.0.get_or_init(|| self.register()).shared.insert(val))
+    }
+
+    /// Returns number of running threads that inserted into their running average.
+    pub fn threads(&self) -> usize {
+        self.windows().len()
+    }
+
+    /// Calculate running average using time window ending now summing measurements of all threads' running averages, including threads that exited.
+    pub fn measurement(&self) -> Measurement<f64> {
+        let windows = self.windows();
+        let retired = self.retired().as_mut().map(|retired| retired.measurement().unwrap()).unwrap_or(0.0);
+
+        Measurement {
+            value: windows.iter().map(|(_, shared)| shared.measurement().unwrap()).sum::<f64>() + retired,
+            duration: (self.duration)(),
+        }
+    }
+}
+
+impl Insert for &'static ThreadLocalRate {
+    type Value = f64;
+
+    fn insert(&mut self, val: f64) {
+        ThreadLocalRate::insert(self, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn aggregate_threads() {
+        use super::*;
+        use std::thread;
+
+        thread_local_rate!(static TEST_RATE, Duration::from_secs(4));
+
+        let threads: Vec<_> = (0..4).map(|i| thread::spawn(move || for _ in 0..10 { TEST_RATE.insert(i as f64) })).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(TEST_RATE.threads(), 0);
+        assert_eq!(TEST_RATE.measurement().unwrap(), 60.0);
+        assert_eq!(TEST_RATE.measurement().rate(), 15.0);
+
+        TEST_RATE.insert(40.0);
+        assert_eq!(TEST_RATE.local().measurement().unwrap(), 40.0);
+        assert_eq!(TEST_RATE.threads(), 1);
+        assert_eq!(TEST_RATE.measurement().unwrap(), 100.0);
+    }
+
+    #[test]
+    fn retire_exited_threads() {
+        use super::*;
+        use std::sync::{Arc, Barrier};
+        use std::thread;
+
+        thread_local_rate!(static TEST_RATE, Duration::from_secs(4));
+
+        for _ in 0..100 {
+            let barrier = Arc::new(Barrier::new(5));
+            let threads: Vec<_> = (0..4).map(|_| {
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    TEST_RATE.insert(1.0);
+                    barrier.wait();
+                    barrier.wait();
+                })
+            }).collect();
+
+            barrier.wait();
+            assert_eq!(TEST_RATE.threads(), 4);
+            barrier.wait();
+
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(TEST_RATE.threads(), 0);
+        }
+
+        // Note: this may fail as it is based on real time
+        assert_eq!(TEST_RATE.measurement().unwrap(), 400.0);
+    }
+}
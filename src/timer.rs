@@ -0,0 +1,130 @@
+//! Scoped timer guard inserting elapsed time in seconds into running average when dropped.
+//!
+//! `start_timer()` returns `Timer` that measures time until it is dropped (or explicitly stopped)
+//! and inserts it as `f64` number of seconds, so running average measures total time spent per second of the window.
+//! ```
+//! use running_average::shared::SharedRunningAverage;
+//!
+//! let latency = SharedRunningAverage::<f64>::default();
+//!
+//! {
+//!     let _t = latency.start_timer();
+//!     // do some work
+//! }
+//!
+//! assert!(latency.measurement().unwrap() > 0.0);
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::{dts, Insert, RealTimeRunningAverage, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Guard that inserts time elapsed since it was started in seconds into given target when dropped.
+pub struct Timer<R: Insert<Value = f64>> {
+    target: Option<R>,
+    start: Instant,
+}
+
+impl<R: Insert<Value = f64>> fmt::Debug for Timer<R> where R: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer").field("target", &self.target).field("start", &self.start).finish()
+    }
+}
+
+impl<R: Insert<Value = f64>> Timer<R> {
+    /// Start timer inserting into given target.
+    pub fn start(target: R) -> Timer<R> {
+        Timer {
+            target: Some(target),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns time elapsed since the timer was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Stop timer inserting elapsed time now and returning it.
+    pub fn stop(mut self) -> Duration {
+        self.record()
+    }
+
+    /// Stop timer without inserting elapsed time.
+    pub fn cancel(mut self) {
+        self.target.take();
+    }
+
+    fn record(&mut self) -> Duration {
+        let elapsed = self.elapsed();
+        if let Some(mut target) = self.target.take() {
+            target.insert(dts(elapsed));
+        }
+        elapsed
+    }
+}
+
+impl<R: Insert<Value = f64>> Drop for Timer<R> {
+    fn drop(&mut self) {
+        self.record();
+    }
+}
+
+impl<TS: TimeSource> RealTimeRunningAverage<f64, TS> {
+    /// Start timer that inserts elapsed time in seconds when dropped.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn start_timer(&mut self) -> Timer<&mut RealTimeRunningAverage<f64, TS>> {
+        Timer::start(self)
+    }
+
+    /// Call given closure inserting time it took to complete in seconds and return its result.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn measure_call<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let _t = self.start_timer();
+        f()
+    }
+}
+
+impl<TS: TimeSource> SharedRunningAverage<f64, TS> {
+    /// Start timer that inserts elapsed time in seconds when dropped.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn start_timer(&self) -> Timer<SharedRunningAverage<f64, TS>> {
+        Timer::start(self.clone())
+    }
+
+    /// Call given closure inserting time it took to complete in seconds and return its result.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn measure_call<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _t = self.start_timer();
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn record_elapsed_on_drop() {
+        use super::*;
+        use std::thread::sleep;
+        use super::super::ManualTimeSource;
+
+        let mut tw = RealTimeRunningAverage::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+
+        {
+            let _t = tw.start_timer();
+            sleep(Duration::from_millis(10));
+        }
+        let recorded = tw.measurement().unwrap();
+        assert!(recorded >= 0.01);
+
+        let elapsed = tw.start_timer().stop();
+        assert!(elapsed < Duration::from_secs(1));
+        assert_eq!(tw.measurement().unwrap(), recorded + dts(elapsed));
+
+        let recorded = tw.measurement().unwrap();
+        tw.start_timer().cancel();
+        assert_eq!(tw.measurement().unwrap(), recorded);
+    }
+}
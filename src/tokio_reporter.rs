@@ -0,0 +1,220 @@
+//! Asynchronous tasks periodically recording measurements of shared running average to a sink or publishing them via watch channel (requires `tokio` feature).
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use std::io;
+//! use std::time::Duration;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::sink::JsonLinesSink;
+//! use running_average::tokio_reporter::report_every_until;
+//! use tokio::sync::oneshot;
+//!
+//! let shared = SharedRunningAverage::default();
+//! let (shutdown, signal) = oneshot::channel::<()>();
+//!
+//! // Print JSON line with measurement every second until shutdown is signalled
+//! let reporter = tokio::spawn(report_every_until(shared.clone(), Duration::from_secs(1), JsonLinesSink::new(io::stdout()), async {
+//!     signal.await.ok();
+//! }));
+//!
+//! // Got 2KB of data
+//! shared.insert(2000);
+//!
+//! shutdown.send(()).unwrap();
+//! reporter.await.unwrap().unwrap();
+//! # }
+//! ```
+
+use std::future::{self, Future};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+use tokio::time::{interval_at, Instant};
+
+use super::{Accumulate, Measurement, RealTimeSource, TimeInstant, TimeSource, TimeWentBackwards};
+use super::shared::SharedRunningAverage;
+use super::sink::MeasurementSink;
+
+impl TimeInstant for Instant {
+    fn duration_since(&self, earlier: Self) -> Duration {
+        self.duration_since(earlier)
+    }
+
+    fn try_duration_since(&self, earlier: Self) -> Result<Duration, TimeWentBackwards> {
+        self.checked_duration_since(earlier).ok_or(TimeWentBackwards)
+    }
+
+    fn forward(&mut self, duration: Duration) {
+        *self += duration;
+    }
+}
+
+/// TimeSource that uses Tokio clock via `tokio::time::Instant::now()`.
+/// Note: Tokio clock can be paused and advanced in tests.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioTimeSource;
+
+impl TimeSource for TokioTimeSource {
+    type Instant = Instant;
+
+    fn now(&self) -> Self::Instant {
+        Instant::now()
+    }
+}
+
+/// Record measurement of given shared running average to given sink every interval.
+/// Measurements are recorded with name and labels of the shared running average (see `MeasurementSink::record_labeled()`).
+/// Completes only if the sink fails to record measurement; the task can be cancelled at any time by dropping or aborting it.
+pub async fn report_every<V, TS, S>(shared: SharedRunningAverage<V, TS>, interval: Duration, sink: S) -> Result<S, S::Error>
+    where V: Accumulate, TS: TimeSource, S: MeasurementSink<V> {
+    report_every_until(shared, interval, sink, future::pending()).await
+}
+
+/// Record measurement of given shared running average to given sink every interval until given shutdown future completes.
+/// Returns the sink on shutdown or error if the sink failed to record measurement.
+pub async fn report_every_until<V, TS, S, F>(shared: SharedRunningAverage<V, TS>, interval: Duration, mut sink: S, shutdown: F) -> Result<S, S::Error>
+    where V: Accumulate, TS: TimeSource, S: MeasurementSink<V>, F: Future<Output = ()> {
+    let mut ticks = interval_at(Instant::now() + interval, interval);
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => return Ok(sink),
+            _ = ticks.tick() => {
+                let measurement = shared.labeled_measurement();
+                sink.record_labeled(SystemTime::now(), &measurement)?;
+            }
+        }
+    }
+}
+
+/// Spawn task that will publish measurement of given shared running average every interval to returned watch channel receiver.
+/// Receivers observe the latest measurement without locking the running average; the task stops when all receivers are dropped.
+/// Panics if called outside of Tokio runtime.
+pub fn publish_every<V, TS>(shared: SharedRunningAverage<V, TS>, interval: Duration) -> watch::Receiver<Measurement<V>>
+    where V: Accumulate + Send + Sync + 'static, TS: TimeSource + Send + 'static, TS::Instant: Send {
+    let (sender, receiver) = watch::channel(shared.measurement());
+
+    tokio::spawn(async move {
+        let mut ticks = interval_at(Instant::now() + interval, interval);
+
+        loop {
+            tokio::select! {
+                _ = sender.closed() => return,
+                _ = ticks.tick() => {
+                    if sender.send(shared.measurement()).is_err() {
+                        return
+                    }
+                }
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Create new shared running average with window of given width duration and watch channel receiver of its measurement published every interval.
+/// Panics if called outside of Tokio runtime.
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use std::time::Duration;
+/// use running_average::tokio_reporter::watch_channel;
+///
+/// let (recorder, mut measurement) = watch_channel(Duration::from_secs(8), Duration::from_millis(100));
+///
+/// // Got 2KB of data
+/// recorder.insert(2000);
+///
+/// measurement.changed().await.unwrap();
+/// assert_eq!(*measurement.borrow().value(), 2000);
+/// # }
+/// ```
+pub fn watch_channel<V>(duration: Duration, interval: Duration) -> (SharedRunningAverage<V, RealTimeSource>, watch::Receiver<Measurement<V>>)
+    where V: Accumulate + Send + Sync + 'static {
+    let shared = SharedRunningAverage::new(duration);
+    let receiver = publish_every(shared.clone(), interval);
+    (shared, receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use super::{Measurement, MeasurementSink, SystemTime};
+
+    #[derive(Debug, Default)]
+    struct CollectSink {
+        values: Vec<i32>,
+    }
+
+    impl MeasurementSink<i32> for CollectSink {
+        type Error = Infallible;
+
+        fn record(&mut self, _ts: SystemTime, measurement: &Measurement<i32>) -> Result<(), Infallible> {
+            self.values.push(*measurement.value());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct FailingSink;
+
+    impl MeasurementSink<i32> for FailingSink {
+        type Error = &'static str;
+
+        fn record(&mut self, _ts: SystemTime, _measurement: &Measurement<i32>) -> Result<(), &'static str> {
+            Err("sink full")
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_until_shutdown() {
+        use super::*;
+
+        let shared = SharedRunningAverage::default();
+        shared.insert(10);
+
+        let sink = report_every_until(shared, Duration::from_secs(1), CollectSink::default(), tokio::time::sleep(Duration::from_millis(3500))).await.unwrap();
+        assert_eq!(sink.values, vec![10, 10, 10]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_on_sink_error() {
+        use super::*;
+
+        let shared = SharedRunningAverage::default();
+
+        assert_eq!(report_every(shared, Duration::from_secs(1), FailingSink).await.unwrap_err(), "sink full");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishes_latest_measurement() {
+        use super::*;
+
+        let (recorder, mut measurement) = watch_channel(Duration::from_secs(3600), Duration::from_secs(1));
+        assert_eq!(*measurement.borrow().value(), 0);
+
+        recorder.insert(10);
+        measurement.changed().await.unwrap();
+        assert_eq!(*measurement.borrow_and_update().value(), 10);
+
+        recorder.insert(10);
+        measurement.changed().await.unwrap();
+        assert_eq!(*measurement.borrow_and_update().value(), 20);
+    }
+
+    #[test]
+    fn tokio_instant_backwards() {
+        use super::*;
+        use super::super::RunningAverage;
+
+        let now = Instant::now();
+        assert_eq!(now.try_duration_since(now + Duration::from_secs(1)), Err(TimeWentBackwards));
+        assert_eq!((now + Duration::from_secs(1)).try_duration_since(now), Ok(Duration::from_secs(1)));
+
+        let mut tw = RunningAverage::<u32, Instant>::with_capacity(Duration::from_secs(4), 4);
+        assert_eq!(tw.try_insert(now + Duration::from_secs(2), 1), Ok(()));
+        assert_eq!(tw.try_insert(now, 1), Err(TimeWentBackwards));
+    }
+}
@@ -0,0 +1,208 @@
+//! Measuring throughput and latency of `tracing` spans (requires `tracing` feature).
+//!
+//! `SpanThroughputLayer` is a `tracing-subscriber` layer that records closed spans (count and duration) into running averages kept per span name
+//! which can be queried via `SpanThroughput` handle.
+//! ```
+//! use std::time::Duration;
+//! use tracing_subscriber::layer::SubscriberExt;
+//! use running_average::tracing::SpanThroughput;
+//!
+//! let throughput = SpanThroughput::new(Duration::from_secs(8));
+//! let subscriber = tracing_subscriber::registry().with(throughput.layer());
+//!
+//! tracing::subscriber::with_default(subscriber, || {
+//!     let _span = tracing::info_span!("handle_request").entered();
+//! });
+//!
+//! let measurement = throughput.measurement("handle_request").unwrap();
+//! println!("{} requests/s with average latency of {:?}", measurement.rate(), measurement.average_latency());
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use tracing_core::Subscriber;
+use tracing_core::span::{Attributes, Id};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::{Measurement, RealTimeRunningAverage, dts};
+
+/// Measurement of closed spans of given name.
+#[derive(Debug, Clone)]
+pub struct SpanMeasurement {
+    count: Measurement<u32>,
+    latency: Measurement<f64>,
+}
+
+impl SpanMeasurement {
+    /// Returns measurement of number of closed spans.
+    pub fn count(&self) -> &Measurement<u32> {
+        &self.count
+    }
+
+    /// Returns measurement of sum of durations of closed spans in seconds.
+    pub fn latency(&self) -> &Measurement<f64> {
+        &self.latency
+    }
+
+    /// Returns number of spans closed per second.
+    pub fn rate(&self) -> f64 {
+        self.count.rate()
+    }
+
+    /// Returns average duration of spans closed in the time window; None if no spans were closed.
+    pub fn average_latency(&self) -> Option<Duration> {
+        match *self.count.value() {
+            0 => None,
+            count => Some(Duration::from_secs_f64(self.latency.value() / f64::from(count))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SpanWindows {
+    count: RealTimeRunningAverage<u32>,
+    latency: RealTimeRunningAverage<f64>,
+}
+
+/// Cloneable handle to running averages of spans closed per span name.
+#[derive(Debug, Clone)]
+pub struct SpanThroughput {
+    spans: Arc<Mutex<HashMap<&'static str, SpanWindows>>>,
+    duration: Duration,
+}
+
+impl Default for SpanThroughput {
+    /// Crate new SpanThroughput instance with windows of 8 seconds width.
+    fn default() -> SpanThroughput {
+        SpanThroughput::new(Duration::from_secs(8))
+    }
+}
+
+impl SpanThroughput {
+    /// Crate new instance keeping windows of given width duration for each span name.
+    pub fn new(duration: Duration) -> SpanThroughput {
+        SpanThroughput {
+            spans: Default::default(),
+            duration,
+        }
+    }
+
+    /// Create layer recording closed spans into this SpanThroughput.
+    pub fn layer(&self) -> SpanThroughputLayer {
+        SpanThroughputLayer {
+            throughput: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<&'static str, SpanWindows>> {
+        self.spans.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record closed span of given name that lasted given duration.
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        let window = self.duration;
+        let mut spans = self.lock();
+        let windows = spans.entry(name).or_insert_with(|| SpanWindows {
+            count: RealTimeRunningAverage::new(window),
+            latency: RealTimeRunningAverage::new(window),
+        });
+
+        windows.count.insert(1);
+        windows.latency.insert(dts(duration));
+    }
+
+    /// Calculate measurement of spans of given name closed in time window ending now; None if no span of that name was closed yet.
+    pub fn measurement(&self, name: &str) -> Option<SpanMeasurement> {
+        self.lock().get_mut(name).map(|windows| SpanMeasurement {
+            count: windows.count.measurement(),
+            latency: windows.latency.measurement(),
+        })
+    }
+
+    /// Calculate measurements of spans closed in time window ending now for all known span names ordered by name.
+    pub fn measurements(&self) -> Vec<(&'static str, SpanMeasurement)> {
+        let mut measurements: Vec<_> = self.lock().iter_mut().map(|(name, windows)| (*name, SpanMeasurement {
+            count: windows.count.measurement(),
+            latency: windows.latency.measurement(),
+        })).collect();
+        measurements.sort_by_key(|&(name, _)| name);
+        measurements
+    }
+}
+
+struct Opened(Instant);
+
+/// Layer recording closed spans into `SpanThroughput`.
+/// Span duration is measured from span creation until it is closed.
+#[derive(Debug, Clone)]
+pub struct SpanThroughputLayer {
+    throughput: SpanThroughput,
+}
+
+impl SpanThroughputLayer {
+    /// Return reference to SpanThroughput closed spans are recorded into.
+    pub fn throughput(&self) -> &SpanThroughput {
+        &self.throughput
+    }
+}
+
+impl<S> Layer<S> for SpanThroughputLayer where S: Subscriber + for<'a> LookupSpan<'a> {
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            let opened = span.extensions().get::<Opened>().map(|opened| opened.0);
+            if let Some(opened) = opened {
+                self.throughput.record(span.name(), opened.elapsed());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn record_closed_spans_per_name() {
+        use super::*;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let throughput = SpanThroughput::default();
+        let subscriber = tracing_subscriber::registry().with(throughput.layer());
+
+        ::tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..4 {
+                let _span = ::tracing::info_span!("query").entered();
+            }
+            let _span = ::tracing::info_span!("connect").entered();
+        });
+
+        let measurements = throughput.measurements();
+        assert_eq!(measurements.iter().map(|&(name, ref measurement)| (name, *measurement.count().value())).collect::<Vec<_>>(), vec![("connect", 1), ("query", 4)]);
+
+        // Note: this may fail as it is based on real time
+        let query = throughput.measurement("query").unwrap();
+        assert_eq!(query.rate(), 0.5);
+        assert!(query.average_latency().unwrap() < Duration::from_secs(1));
+        assert!(throughput.measurement("missing").is_none());
+    }
+
+    #[test]
+    fn average_latency() {
+        use super::*;
+
+        let throughput = SpanThroughput::default();
+        throughput.record("query", Duration::from_millis(100));
+        throughput.record("query", Duration::from_millis(300));
+
+        let query = throughput.measurement("query").unwrap();
+        assert_eq!(query.average_latency(), Some(Duration::from_millis(200)));
+        assert_eq!(query.latency().rate(), 0.05);
+    }
+}
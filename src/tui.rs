@@ -0,0 +1,147 @@
+//! Minimal terminal dashboard showing rates and sparklines of named shared running averages (requires `tui` feature).
+//!
+//! `LiveDashboard` renders line for each running average with its name, rate and sparkline of the time window buckets
+//! and can redraw it in place on a terminal on an interval using ANSI escape codes.
+//! ```
+//! use std::io;
+//! use std::time::Duration;
+//! use running_average::shared::SharedRunningAverage;
+//! use running_average::tui::LiveDashboard;
+//!
+//! let ingress = SharedRunningAverage::<u32>::default();
+//! let egress = SharedRunningAverage::<u32>::default();
+//!
+//! let dashboard = LiveDashboard::new(vec![("ingress", ingress.clone()), ("egress", egress.clone())]).spawn(Duration::from_secs(1), io::stdout());
+//!
+//! // Got 2KB of data
+//! ingress.insert(2000);
+//! # dashboard.stop().unwrap();
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::panic;
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::{Accumulate, RealTimeSource, TimeSource};
+use super::shared::SharedRunningAverage;
+
+/// Named shared running averages rendered as dashboard lines.
+pub struct LiveDashboard<V: Accumulate, TS: TimeSource = RealTimeSource> {
+    entries: Vec<(String, SharedRunningAverage<V, TS>)>,
+}
+
+impl<V: Accumulate, TS: TimeSource> fmt::Debug for LiveDashboard<V, TS> where SharedRunningAverage<V, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LiveDashboard").field("entries", &self.entries).finish()
+    }
+}
+
+impl<V: Accumulate, TS: TimeSource> LiveDashboard<V, TS> {
+    /// Create new dashboard showing given named shared running averages in given order.
+    pub fn new(entries: Vec<(&str, SharedRunningAverage<V, TS>)>) -> LiveDashboard<V, TS> {
+        LiveDashboard {
+            entries: entries.into_iter().map(|(name, shared)| (name.to_owned(), shared)).collect(),
+        }
+    }
+
+    /// Render dashboard lines with name, rate and sparkline of time window ending now for each running average.
+    /// Panics if time source time goes backwards.
+    pub fn render(&self) -> String where V: Clone + Into<f64> {
+        let width = self.entries.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+
+        self.entries.iter().map(|(name, shared)| {
+            let mut running_average = shared.lock();
+            let rate = running_average.measurement().rate_f64();
+            format!("{:<width$} {:>12.3}/s {}\n", name, rate, running_average.sparkline(), width = width)
+        }).collect()
+    }
+
+    /// Spawn thread that redraws the dashboard in place on given terminal output every interval.
+    pub fn spawn<W>(self, interval: Duration, mut out: W) -> DashboardHandle
+        where V: Clone + Into<f64> + Send + 'static, TS: Send + 'static, TS::Instant: Send, W: Write + Send + 'static {
+        let (shutdown, signal) = channel();
+
+        let thread = thread::Builder::new().name("running-average-dashboard".to_owned()).spawn(move || {
+            let mut deadline = Instant::now();
+            let mut drawn = false;
+
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+
+                match signal.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+
+                if drawn && !self.entries.is_empty() {
+                    // Move cursor back to the first line
+                    write!(out, "\x1b[{}A", self.entries.len())?;
+                }
+                for line in self.render().lines() {
+                    writeln!(out, "\x1b[2K{}", line)?;
+                }
+                out.flush()?;
+                drawn = true;
+
+                deadline += interval;
+            }
+        }).expect("failed to spawn dashboard thread");
+
+        DashboardHandle {
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Owns a thread redrawing `LiveDashboard`.
+/// The thread is stopped when DashboardHandle is dropped or explicitly with `stop()`.
+#[derive(Debug)]
+pub struct DashboardHandle {
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl DashboardHandle {
+    fn shutdown(&mut self) -> Option<thread::Result<io::Result<()>>> {
+        self.shutdown.take();
+        self.thread.take().map(|thread| thread.join())
+    }
+
+    /// Stop dashboard thread returning error if writing to the output failed.
+    /// Panics if dashboard thread panicked.
+    pub fn stop(mut self) -> io::Result<()> {
+        match self.shutdown().expect("dashboard already stopped") {
+            Ok(result) => result,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for DashboardHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn render_rates_and_sparklines() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let ingress = SharedRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let egress = SharedRunningAverage::<u32, _>::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new());
+        let dashboard = LiveDashboard::new(vec![("ingress", ingress.clone()), ("egress", egress.clone())]);
+
+        ingress.insert(40);
+        ingress.lock().time_source().time_shift(1.0);
+        ingress.insert(20);
+
+        assert_eq!(&dashboard.render(), "ingress       15.000/s \u{2581}\u{2581}\u{2588}\u{2585}\negress         0.000/s \u{2581}\u{2581}\u{2581}\u{2581}\n");
+    }
+}
@@ -0,0 +1,146 @@
+//! Busy fraction of wall time measured over time window.
+//!
+//! `UtilizationTracker` sums busy time recorded directly or with `enter()` guards and reports it as fraction of the time window
+//! (and of number of workers sharing the tracker), e.g. utilization of thread-pool or connection.
+//! ```
+//! use std::time::Duration;
+//! use running_average::utilization::UtilizationTracker;
+//!
+//! let pool = UtilizationTracker::new(Duration::from_secs(8)).workers(4);
+//!
+//! // One of four workers was busy for 4 seconds
+//! pool.record(Duration::from_secs(4));
+//!
+//! {
+//!     let _busy = pool.enter();
+//!     // do some work
+//! }
+//!
+//! // Note: this may fail as it is based on real time
+//! assert!(pool.utilization() >= 0.125);
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use super::{dts, RealTimeRunningAverage, RealTimeSource, TimeSource};
+use super::shared::SharedRunningAverage;
+use super::timer::Timer;
+
+/// Cloneable handle to time window of busy time in seconds shared between threads.
+pub struct UtilizationTracker<TS: TimeSource = RealTimeSource> {
+    shared: SharedRunningAverage<f64, TS>,
+    workers: usize,
+}
+
+impl<TS: TimeSource> fmt::Debug for UtilizationTracker<TS> where SharedRunningAverage<f64, TS>: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UtilizationTracker").field("shared", &self.shared).field("workers", &self.workers).finish()
+    }
+}
+
+impl<TS: TimeSource> Clone for UtilizationTracker<TS> {
+    fn clone(&self) -> UtilizationTracker<TS> {
+        UtilizationTracker {
+            shared: self.shared.clone(),
+            workers: self.workers,
+        }
+    }
+}
+
+impl Default for UtilizationTracker<RealTimeSource> {
+    /// Crate new UtilizationTracker instance with window of 8 seconds width and 16 buckets.
+    fn default() -> UtilizationTracker<RealTimeSource> {
+        UtilizationTracker::new(Duration::from_secs(8))
+    }
+}
+
+impl UtilizationTracker<RealTimeSource> {
+    /// Crate new instance with window of given width duration and using RealTimeSource as time source for `now` instant.
+    pub fn new(duration: Duration) -> UtilizationTracker<RealTimeSource> {
+        RealTimeRunningAverage::new(duration).into()
+    }
+}
+
+impl<TS: TimeSource> From<RealTimeRunningAverage<f64, TS>> for UtilizationTracker<TS> {
+    fn from(running_average: RealTimeRunningAverage<f64, TS>) -> UtilizationTracker<TS> {
+        UtilizationTracker {
+            shared: running_average.into(),
+            workers: 1,
+        }
+    }
+}
+
+impl<TS: TimeSource> UtilizationTracker<TS> {
+    /// Crate new instance with window of given width duration and using given as time source for `now` instant.
+    pub fn with_time_source(duration: Duration, capacity: usize, time_source: TS) -> UtilizationTracker<TS> {
+        RealTimeRunningAverage::with_time_source(duration, capacity, time_source).into()
+    }
+
+    /// Set number of workers recording busy time so that utilization of all of them busy all the time is 1.
+    /// Panics if workers is 0.
+    pub fn workers(mut self, workers: usize) -> UtilizationTracker<TS> {
+        assert!(workers > 0, "UtilizationTracker workers cannot be 0");
+        self.workers = workers;
+        self
+    }
+
+    /// Record given busy time ending now.
+    /// Note: all of the busy time is accounted to the current bucket.
+    /// Panics if time source time goes backwards.
+    pub fn record(&self, busy: Duration) {
+        self.shared.insert(dts(busy))
+    }
+
+    /// Enter busy period returning guard that records time elapsed until it is dropped as busy time.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn enter(&self) -> Timer<SharedRunningAverage<f64, TS>> {
+        self.shared.start_timer()
+    }
+
+    /// Call given closure recording time it took to complete as busy time and return its result.
+    /// Note: elapsed time is measured with real time clock regardless of time source used.
+    pub fn measure_call<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.shared.measure_call(f)
+    }
+
+    /// Returns busy fraction of wall time of all workers over time window ending now.
+    /// Result may exceed 1 if more busy time was recorded than there were workers or when busy periods recorded were longer than the time window.
+    /// Panics if time source time goes backwards.
+    pub fn utilization(&self) -> f64 {
+        self.shared.measurement().to_rate() / self.workers as f64
+    }
+
+    /// Returns the underlying shared running average of busy time in seconds.
+    pub fn shared(&self) -> &SharedRunningAverage<f64, TS> {
+        &self.shared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn busy_fraction() {
+        use super::*;
+        use super::super::ManualTimeSource;
+
+        let pool = UtilizationTracker::with_time_source(Duration::from_secs(4), 4, ManualTimeSource::new()).workers(2);
+        assert_eq!(pool.utilization(), 0.0);
+
+        pool.record(Duration::from_secs(2));
+        pool.shared().lock().time_source().time_shift(1.0);
+        pool.record(Duration::from_secs(4));
+        assert_eq!(pool.utilization(), 0.75);
+
+        pool.clone().record(Duration::from_secs(2));
+        assert_eq!(pool.utilization(), 1.0);
+
+        pool.shared().lock().time_source().time_shift(3.0);
+        assert_eq!(pool.utilization(), 0.75);
+
+        let before = pool.shared().measurement().unwrap();
+        assert_eq!(pool.measure_call(|| 42), 42);
+        drop(pool.enter());
+        assert!(pool.shared().measurement().unwrap() >= before);
+    }
+}